@@ -1,3 +1,146 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+/// Embedded plugins live in this folder. Each subfolder with a `manifest.json` is an embedded plugin
+const EMBEDDED_PLUGINS_DIR: &str = "assets/plugins";
+/// Manifest versions the app understands. Must match `MANIFEST_VERSIONS` in `src/plugins/plugin_manifest.rs`
+const MANIFEST_VERSIONS: &[&str] = &["v1alpha", "v1"];
+
 fn main() {
+    generate_embedded_plugins();
     tauri_build::build()
 }
+
+/// Discovers the embedded plugins, validates their manifests and writes `embedded_plugins.rs` to `OUT_DIR`.
+/// See `src/plugins/embedded_plugins.rs` for how the generated code is used.
+fn generate_embedded_plugins() {
+    let embed_assets = std::env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_some();
+    let plugins_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(EMBEDDED_PLUGINS_DIR);
+    println!("cargo:rerun-if-changed={}", plugins_dir.display());
+
+    let mut plugin_dirs = fs::read_dir(&plugins_dir)
+        .expect("failed to read embedded plugins dir")
+        .flatten()
+        .map(|x| x.path())
+        .filter(|x| x.join("manifest.json").is_file())
+        .collect::<Vec<_>>();
+    plugin_dirs.sort();
+
+    let mut out = String::from("pub(crate) const EMBEDDED_PLUGINS: &[EmbeddedPlugin] = &[\n");
+    for plugin_dir in plugin_dirs {
+        let id = plugin_dir
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        if !id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            panic!(
+                "embedded plugin ID {id:?} may only contain lowercase letters, digits and dashes"
+            );
+        }
+
+        let manifest_path = plugin_dir.join("manifest.json");
+        println!("cargo:rerun-if-changed={}", manifest_path.display());
+        validate_manifest(&id, &manifest_path);
+
+        let frontend_dir = plugin_dir.join("frontend");
+        println!("cargo:rerun-if-changed={}", frontend_dir.display());
+        let files = match frontend_dir.is_dir() {
+            true => {
+                let mut files = vec![];
+                collect_files(&frontend_dir, &frontend_dir, &mut files);
+                files.sort();
+                Some(files)
+            }
+            false => {
+                println!(
+                    "cargo:warning=embedded plugin {id} has no built frontend. Run `npm run build:plugins`. Its hash is computed at runtime"
+                );
+                None
+            }
+        };
+
+        let frontend_hash = files.as_deref().map(hash_frontend);
+        let embedded_files = match (&files, embed_assets) {
+            (Some(files), true) => files
+                .iter()
+                .map(|(relative, path)| {
+                    format!(
+                        "({relative:?}, include_bytes!({:?}))",
+                        path.display().to_string()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::new(),
+        };
+
+        writeln!(
+            out,
+            "    EmbeddedPlugin {{ id: {id:?}, manifest: include_str!({:?}), frontend_hash: {frontend_hash:?}, files: &[{embedded_files}] }},",
+            manifest_path.display().to_string()
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+
+    let out_path = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("embedded_plugins.rs");
+    fs::write(out_path, out).expect("failed to write embedded_plugins.rs");
+}
+
+/// Fails the build if the manifest is not valid JSON, has an unknown version, or has no name.
+/// The full schema is checked at runtime; this only catches mistakes that would make the plugin disappear silently
+fn validate_manifest(id: &str, manifest_path: &Path) {
+    let manifest: serde_json::Value = serde_json::from_slice(
+        &fs::read(manifest_path).expect("failed to read embedded plugin manifest"),
+    )
+    .unwrap_or_else(|e| panic!("manifest of embedded plugin {id} is not valid JSON: {e}"));
+
+    match manifest.get("type").and_then(|x| x.as_str()) {
+        Some(x) if MANIFEST_VERSIONS.contains(&x) => {}
+        x => panic!("manifest of embedded plugin {id} has unsupported type {x:?}"),
+    }
+    match manifest.get("name").and_then(|x| x.as_str()) {
+        Some(x) if !x.trim().is_empty() => {}
+        _ => panic!("manifest of embedded plugin {id} has no name"),
+    }
+}
+
+/// Collects all files below `dir` as (path relative to `root` with `/` separators, absolute path)
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, PathBuf)>) {
+    for entry in fs::read_dir(dir).expect("failed to read embedded plugin frontend") {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(root, &path, out);
+        } else {
+            let relative = path
+                .strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|x| x.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+            out.push((relative, path));
+        }
+    }
+}
+
+/// Same hash as `FrontendHashCache::hash_frontend_dir`, so an embedded plugin hashes the same as when it is loaded from disk
+fn hash_frontend(files: &[(String, PathBuf)]) -> String {
+    let mut hasher = Sha256::new();
+    for (relative, path) in files {
+        let digest = Sha256::digest(fs::read(path).expect("failed to read embedded plugin file"));
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update(digest);
+    }
+    format!("{:x}", hasher.finalize())
+}
@@ -0,0 +1,19 @@
+//! Writes the TypeScript and JSON Schema definitions of all commands and events to `src/bindings`, or to the directory given as first argument.
+//! Also writes the JSON Schemas of the plugin manifest to `src-tauri/schemas`. See `npm run bindings`
+
+use std::path::{Path, PathBuf};
+
+fn main() -> anyhow::Result<()> {
+    // `src/bindings` of the repository, no matter where this is run from
+    let dir = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("../src/bindings"));
+    elite_dangerous_plugin_framework_lib::export_bindings(&dir)?;
+    println!("wrote bindings to {}", dir.display());
+
+    let schemas = Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+    elite_dangerous_plugin_framework_lib::export_manifest_schemas(&schemas)?;
+    println!("wrote manifest schemas to {}", schemas.display());
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use chrono::{DateTime, TimeDelta, Utc};
 use ed_journals::logs::LogEventContent;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Debug,
@@ -10,13 +11,15 @@ use std::{
     thread,
     time::Duration,
 };
-use tauri::{AppHandle, Emitter, Manager, Wry};
+use tauri::{AppHandle, Manager, Wry};
 use tokio::{
     sync::{mpsc, RwLock},
     time::sleep,
 };
 use tracing::{error, info, info_span, warn, Instrument};
 
+use crate::plugins::PluginsState;
+
 pub(super) async fn event_watchdog(app_handle: &AppHandle<Wry>) -> ! {
     // We spawn a background thread that is responsible to listen for changes to the journal directory.
     // This contains essentially nested threads. We make the assumption that multiple players can be active as the same
@@ -141,7 +144,12 @@ pub(super) async fn event_watchdog(app_handle: &AppHandle<Wry>) -> ! {
                             continue;
                         }
 
-                        if let Err(e) = app_handle.emit("journal_events", &buffer) {
+                        let plugins_state = app_handle.state::<Arc<RwLock<PluginsState>>>();
+                        let emitted = plugins_state
+                            .read()
+                            .await
+                            .emit_journal_events(&app_handle, &buffer);
+                        if let Err(e) = emitted {
                             warn!("failed to emit journal_events message: {}", e);
                         } else {
                             info!("Pushed {} journal events.", buffer.len())
@@ -242,7 +250,7 @@ fn get_last_event_ts_and_name_from_log(
 }
 
 /// An "enhanced" Log Entry containing where that log entry is from (which file), and who it belongs to
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub(crate) struct LogEventWithContext {
     // contains a stringified JSON
     pub(crate) event: String,
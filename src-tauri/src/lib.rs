@@ -1,5 +1,6 @@
 pub(crate) mod event_watchdog;
 pub(crate) mod plugins;
+pub mod portable;
 pub(crate) mod updates;
 use std::{
     env,
@@ -7,7 +8,10 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use plugins::PluginsState;
+use plugins::{
+    audit_log::AuditLog, journal_stream::JournalStreams, rate_limits::RateLimiter,
+    url_policy::UrlPrompts, PluginsState,
+};
 use tauri::{
     menu::{MenuBuilder, MenuItem, MenuItemBuilder},
     tray::TrayIconBuilder,
@@ -18,9 +22,20 @@ use tokio::sync::RwLock;
 use tracing::info;
 use updates::PendingUpdate;
 
+/// Writes the JSON Schema and TypeScript definitions of all commands and events to `dir`. Used by the `export-bindings` binary
+pub fn export_bindings(dir: &std::path::Path) -> anyhow::Result<()> {
+    plugins::bindings::export(dir)
+}
+
+/// Writes the JSON Schemas of the plugin manifest to `dir`. Used by the `export-bindings` binary
+pub fn export_manifest_schemas(dir: &std::path::Path) -> anyhow::Result<()> {
+    plugins::plugin_manifest::PluginManifest::export_json_schemas(dir)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // This should be called as early in the execution of the app as possible
+    portable::prepare();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -31,7 +46,11 @@ pub fn run() {
                 .expect("missing main window")
                 .set_focus();
         }))
-        .plugin(tauri_plugin_window_state::Builder::new().build())
+        .plugin(
+            tauri_plugin_window_state::Builder::new()
+                .with_filename(portable::window_state_filename())
+                .build(),
+        )
         .setup(|app| {
             #[cfg(debug_assertions)]
             app.get_webview_window("main").unwrap().open_devtools();
@@ -51,6 +70,10 @@ pub fn run() {
             app.manage(Arc::new(
                 RwLock::new(bimap::BiMap::<String, PathBuf>::new()),
             ));
+            app.manage(JournalStreams::new());
+            app.manage(AuditLog::new(portable::audit_log_dir(app.app_handle())));
+            app.manage(RateLimiter::new());
+            app.manage(UrlPrompts::new());
             tauri::async_runtime::spawn(async move {
                 let _ = event_watchdog::event_watchdog(&handle).await;
             });
@@ -97,27 +120,33 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .on_page_load(|window, event| {
             let w = window.label();
-            if let PageLoadEvent::Finished = event.event() {
-                info!("page load finished. id: {}", w)
-            }
-            let state = window.app_handle().state::<Arc<RwLock<PluginsState>>>();
-            let mut data = state.blocking_write();
-
-            match w {
-                "settings" => data.allow_request_root_key_settings = true,
-                "main" => data.allow_request_root_key_main = true,
-                _ => {}
+            match event.event() {
+                PageLoadEvent::Finished => info!("page load finished. id: {}", w),
+                // The new page gets a new root token. Re-arming only when the load starts makes sure the page can fetch it exactly once
+                PageLoadEvent::Started if matches!(w, "main" | "settings") => {
+                    let state = window.app_handle().state::<Arc<RwLock<PluginsState>>>();
+                    state.blocking_write().rearm_root_token(w);
+                    window.app_handle().state::<JournalStreams>().forget_window(w);
+                }
+                PageLoadEvent::Started => {}
             }
         })
-        .on_window_event(|w, event| {
-            if let tauri::WindowEvent::CloseRequested { .. } = event {
-                if w.label() == "main" {
-                    w.app_handle().exit(0);
-                }
+        .on_window_event(|w, event| match event {
+            tauri::WindowEvent::CloseRequested { .. } if w.label() == "main" => {
+                w.app_handle().exit(0);
+            }
+            tauri::WindowEvent::Destroyed => {
+                let state = w.app_handle().state::<Arc<RwLock<PluginsState>>>();
+                state.blocking_write().forget_root_token(w.label());
+                w.app_handle()
+                    .state::<JournalStreams>()
+                    .forget_window(w.label());
             }
+            _ => {}
         })
         .invoke_handler(tauri::generate_handler![
             plugins::commands::fetch_all_plugins,
+            plugins::commands::fetch_broken_plugins,
             plugins::commands::get_import_path_for_plugin,
             plugins::commands::open_settings,
             plugins::commands::open_plugins_dir,
@@ -126,15 +155,43 @@ pub fn run() {
             plugins::commands::start_plugin_failed,
             plugins::commands::finalize_stop_plugin,
             plugins::commands::finalize_start_plugin,
+            plugins::commands::retry_plugin,
             plugins::commands::sync_main_layout,
             plugins::commands::reread_active_journal,
+            plugins::commands::open_journal_stream,
+            plugins::commands::ack_journal_frames,
             plugins::commands::write_setting,
             plugins::commands::read_setting,
+            plugins::commands::list_plugin_files,
+            plugins::commands::read_plugin_file,
+            plugins::commands::write_plugin_file,
+            plugins::commands::delete_plugin_file,
             plugins::commands::get_plugin,
             plugins::commands::open_url,
+            plugins::commands::answer_url_prompt,
+            plugins::commands::forget_url_decision,
             plugins::get_root_token_once,
             plugins::commands::check_update_edpf,
             plugins::commands::commit_update_edpf,
+            plugins::commands::trust_plugin_publisher,
+            plugins::commands::untrust_plugin_publisher,
+            plugins::commands::set_trust_policy,
+            plugins::commands::sync_transition_timeouts,
+            plugins::commands::sync_command_limits,
+            plugins::commands::fetch_dev_plugins,
+            plugins::commands::link_dev_plugin,
+            plugins::commands::unlink_dev_plugin,
+            plugins::commands::set_plugin_dir,
+            plugins::commands::fetch_plugin_roots,
+            plugins::commands::set_plugin_roots,
+            plugins::commands::send_sidecar_message,
+            plugins::commands::publish_bus_message,
+            plugins::commands::fetch_retained_bus_messages,
+            plugins::commands::fetch_plugin_permissions,
+            plugins::commands::grant_plugin_permissions,
+            plugins::commands::fetch_permission_requests,
+            plugins::commands::fetch_audit_log,
+            plugins::commands::run_batch,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
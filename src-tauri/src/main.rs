@@ -1,7 +1,10 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tracing_subscriber::EnvFilter;
+use std::{fs::File, sync::Mutex};
+
+use elite_dangerous_plugin_framework_lib::portable;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
 
 fn main() {
     // Install your own global subscriber first
@@ -16,14 +19,26 @@ fn main() {
         .with_thread_names(true)
         .init();
      */
-    tracing_subscriber::fmt()
+    let subscriber = tracing_subscriber::fmt()
         .pretty()
         .with_line_number(true)
+        // reports how long spans like the per-plugin reconcile took
+        .with_span_events(FmtSpan::CLOSE)
         .with_env_filter(
             EnvFilter::new("info")
                 .add_directive("notify=off".parse().unwrap())
                 .add_directive("notify::inotify=off".parse().unwrap()),
-        )
-        .init();
+        );
+    // In portable mode, logs are kept next to the executable as well
+    match portable::log_file().and_then(|path| {
+        std::fs::create_dir_all(path.parent()?).ok()?;
+        File::options().create(true).append(true).open(path).ok()
+    }) {
+        Some(file) => subscriber
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .init(),
+        None => subscriber.init(),
+    }
     elite_dangerous_plugin_framework_lib::run()
 }
@@ -0,0 +1,200 @@
+//! An append-only record of privileged actions, e.g. which plugin opened which URL or who started a plugin.
+//!
+//! Entries are written by the commands (see [AuditLog::record]) as JSON lines to [AUDIT_FILE] in [crate::portable::audit_log_dir].
+//! Once the file grows beyond [MAX_FILE_BYTES], it is rotated: `audit.jsonl` becomes `audit.1.jsonl`, `audit.1.jsonl` becomes `audit.2.jsonl` and so on.
+//! At most [KEPT_ROTATIONS] rotated files are kept. The settings window reads the log via `fetch_audit_log`.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use super::{permissions::Permission, rate_limits::CommandClass};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+/// The current file is rotated once it is larger than this
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+/// How many rotated files are kept besides the current one
+const KEPT_ROTATIONS: usize = 4;
+
+/// A privileged action
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum AuditAction {
+    #[serde(rename_all = "camelCase")]
+    OpenUrl {
+        url: String,
+    },
+    /// The user answered a prompt of `open_url`, see [super::url_policy]
+    #[serde(rename_all = "camelCase")]
+    AnswerUrlPrompt {
+        url: String,
+        allowed: bool,
+        remembered: bool,
+    },
+    /// The value is not recorded, as settings may hold secrets
+    #[serde(rename_all = "camelCase")]
+    WriteSetting {
+        key: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    WriteFile {
+        path: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    DeleteFile {
+        path: String,
+    },
+    Start,
+    /// A start that also cleared the failure history, see `retry_plugin`
+    Retry,
+    Stop,
+    #[serde(rename_all = "camelCase")]
+    LinkDevPlugin {
+        plugin_dir: PathBuf,
+    },
+    UnlinkDevPlugin,
+    #[serde(rename_all = "camelCase")]
+    GrantPermissions {
+        granted: Vec<Permission>,
+    },
+    #[serde(rename_all = "camelCase")]
+    UpdateEdpf {
+        version: String,
+    },
+    /// A call was rejected with `RATE_LIMITED` or `QUOTA_EXCEEDED`. See [super::rate_limits]
+    #[serde(rename_all = "camelCase")]
+    Throttled {
+        command: CommandClass,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AuditEntry {
+    pub(crate) at: DateTime<Utc>,
+    /// The plugin that performed the action, `None` if the core frontend (i.e. the user) did
+    pub(crate) actor: Option<String>,
+    /// The plugin the action concerns, `None` for actions concerning EDPF itself
+    pub(crate) plugin_id: Option<String>,
+    pub(crate) action: AuditAction,
+}
+
+impl AuditEntry {
+    /// Returns whether `plugin_id` performed this action or is concerned by it
+    fn involves(&self, plugin_id: &str) -> bool {
+        self.actor.as_deref() == Some(plugin_id) || self.plugin_id.as_deref() == Some(plugin_id)
+    }
+}
+
+/// Managed by Tauri. Appends entries to the audit log
+pub(crate) struct AuditLog {
+    /// `None` if the directory could not be resolved. Nothing is recorded then
+    dir: Option<PathBuf>,
+    /// Held while writing or rotating, so entries of concurrent commands aren't interleaved
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(dir: Option<PathBuf>) -> Self {
+        if dir.is_none() {
+            error!(
+                "failed to resolve the audit log directory, privileged actions are not recorded"
+            );
+        }
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// The current file at index 0, then the rotated files from newest to oldest
+    fn file(&self, index: usize) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(match index {
+            0 => dir.join(AUDIT_FILE),
+            x => dir.join(format!("audit.{x}.jsonl")),
+        })
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        for index in (0..KEPT_ROTATIONS).rev() {
+            let (Some(from), Some(to)) = (self.file(index), self.file(index + 1)) else {
+                return Ok(());
+            };
+            if from.exists() {
+                fs::rename(from, to)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&self, entry: &AuditEntry) -> anyhow::Result<()> {
+        let (Some(dir), Some(path)) = (&self.dir, self.file(0)) else {
+            return Ok(());
+        };
+        let _guard = self.lock.lock().unwrap();
+        fs::create_dir_all(dir)?;
+        if fs::metadata(&path).is_ok_and(|x| x.len() > MAX_FILE_BYTES) {
+            self.rotate()?;
+        }
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// Records an action. Failing to record never fails the action itself
+    pub(crate) fn record(&self, actor: Option<&str>, plugin_id: Option<&str>, action: AuditAction) {
+        let entry = AuditEntry {
+            at: Utc::now(),
+            actor: actor.map(str::to_string),
+            plugin_id: plugin_id.map(str::to_string),
+            action,
+        };
+        if let Err(e) = self.append(&entry) {
+            warn!("failed to record {entry:?} in the audit log: {e}");
+        }
+    }
+
+    /// Returns the latest `limit` entries, newest first. If `plugin_id` is set, only entries involving that plugin are returned
+    pub(crate) fn query(&self, plugin_id: Option<&str>, limit: usize) -> Vec<AuditEntry> {
+        let _guard = self.lock.lock().unwrap();
+        let mut entries = Vec::new();
+        for index in 0..=KEPT_ROTATIONS {
+            let Some(content) = self.file(index).and_then(|x| fs::read_to_string(x).ok()) else {
+                continue;
+            };
+            let matching = content
+                .lines()
+                .rev()
+                .filter_map(|x| match serde_json::from_str::<AuditEntry>(x) {
+                    Ok(x) => Some(x),
+                    Err(e) => {
+                        warn!("skipping malformed audit log entry: {e}");
+                        None
+                    }
+                })
+                .filter(|x| plugin_id.is_none_or(|id| x.involves(id)));
+            for entry in matching {
+                entries.push(entry);
+                if entries.len() == limit {
+                    return entries;
+                }
+            }
+        }
+        entries
+    }
+}
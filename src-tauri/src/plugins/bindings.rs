@@ -0,0 +1,336 @@
+//! Writes the JSON Schema and TypeScript definitions of all commands and events declared in [super::contracts].
+//!
+//! Run through `npm run bindings`, which writes them to `src/bindings`. The generated files are committed, CI fails if they are outdated.
+//! Both files are generated from a single [SchemaGenerator],
+//! so they share the type names of the `$defs`. The TypeScript emitter only understands the subset of JSON Schema `schemars` produces.
+
+use std::{fmt::Write as _, fs, path::Path};
+
+use anyhow::Context;
+use schemars::{generate::SchemaSettings, SchemaGenerator};
+use serde_json::{json, Map, Value};
+
+use super::contracts::{command_contracts, event_contracts, Access, CommandError, EventTarget};
+
+const HEADER: &str =
+    "// Generated by `npm run bindings` from `src-tauri/src/plugins/contracts.rs`. Do not edit\n";
+
+/// Writes `commands.schema.json` and `commands.ts` to `dir`, creating it if needed
+pub(crate) fn export(dir: &Path) -> anyhow::Result<()> {
+    let mut generator = SchemaGenerator::new(SchemaSettings::draft2020_12());
+    let commands = command_contracts(&mut generator);
+    let events = event_contracts(&mut generator);
+    let error = generator.subschema_for::<CommandError>();
+    let definitions = generator.definitions().clone();
+
+    let mut schema_commands = Map::new();
+    for command in &commands {
+        let mut entry = json!({
+            "access": access_name(command.access),
+            "request": command.request.as_value(),
+            "response": command.response.as_value(),
+        });
+        if let Some(channel) = &command.channel {
+            entry["channel"] = channel.as_value().clone();
+        }
+        if let Some(docs) = docs(command.docs) {
+            entry["description"] = json!(docs);
+        }
+        schema_commands.insert(command.name.to_string(), entry);
+    }
+    let mut schema_events = Map::new();
+    for event in &events {
+        let mut entry = json!({
+            "target": target_name(event.target),
+            "payload": event.payload.as_value(),
+        });
+        if let Some(docs) = docs(event.docs) {
+            entry["description"] = json!(docs);
+        }
+        schema_events.insert(event.name.to_string(), entry);
+    }
+    let schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "EDPF commands and events",
+        "commands": schema_commands,
+        "events": schema_events,
+        "error": error.as_value(),
+        "$defs": definitions,
+    });
+
+    let mut ts = String::from(HEADER);
+    for (name, schema) in &definitions {
+        ts.push('\n');
+        write_docs(
+            &mut ts,
+            schema.get("description").and_then(Value::as_str),
+            "",
+        );
+        writeln!(
+            ts,
+            "export type {} = {};",
+            type_name(name),
+            ts_type(schema, "")
+        )?;
+    }
+
+    ts.push_str("\nexport interface Commands {\n");
+    for command in &commands {
+        write_docs(&mut ts, docs(command.docs).as_deref(), "  ");
+        writeln!(ts, "  {:?}: {{", command.name)?;
+        writeln!(ts, "    access: {:?};", access_name(command.access))?;
+        writeln!(
+            ts,
+            "    request: {};",
+            ts_type(command.request.as_value(), "    ")
+        )?;
+        writeln!(
+            ts,
+            "    response: {};",
+            ts_type(command.response.as_value(), "    ")
+        )?;
+        if let Some(channel) = &command.channel {
+            writeln!(ts, "    channel: {};", ts_type(channel.as_value(), "    "))?;
+        }
+        ts.push_str("  };\n");
+    }
+    ts.push_str("}\n\nexport interface Events {\n");
+    for event in &events {
+        write_docs(&mut ts, docs(event.docs).as_deref(), "  ");
+        writeln!(ts, "  {:?}: {{", event.name)?;
+        writeln!(ts, "    target: {:?};", target_name(event.target))?;
+        writeln!(
+            ts,
+            "    payload: {};",
+            ts_type(event.payload.as_value(), "    ")
+        )?;
+        ts.push_str("  };\n");
+    }
+    ts.push_str("}\n\n");
+    writeln!(
+        ts,
+        "/** What a failed command answers with */\nexport type CommandFailure = {{ success: false }} & {};",
+        ts_type(error.as_value(), "")
+    )?;
+
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    fs::write(
+        dir.join("commands.schema.json"),
+        serde_json::to_string_pretty(&schema)? + "\n",
+    )?;
+    fs::write(dir.join("commands.ts"), ts)?;
+    Ok(())
+}
+
+fn access_name(access: Access) -> &'static str {
+    match access {
+        Access::Root => "root",
+        Access::Caller => "caller",
+        Access::Unarmored => "unarmored",
+    }
+}
+
+fn target_name(target: EventTarget) -> &'static str {
+    match target {
+        EventTarget::Root => "root",
+        EventTarget::Plugin => "plugin",
+    }
+}
+
+/// The doc comment of a contract, without the leading space rustdoc keeps
+fn docs(raw: &str) -> Option<String> {
+    let docs = raw
+        .lines()
+        .map(|x| x.strip_prefix(' ').unwrap_or(x))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let docs = docs.trim();
+    (!docs.is_empty()).then(|| docs.to_string())
+}
+
+fn write_docs(out: &mut String, docs: Option<&str>, indent: &str) {
+    let Some(docs) = docs else {
+        return;
+    };
+    out.push_str(indent);
+    out.push_str("/**\n");
+    for line in docs.lines() {
+        // a `*/` would end the comment early
+        let _ = writeln!(out, "{indent} * {}", line.replace("*/", "*\\/"));
+    }
+    out.push_str(indent);
+    out.push_str(" */\n");
+}
+
+/// `$defs` keys may contain characters TypeScript does not allow in identifiers
+fn type_name(def: &str) -> String {
+    def.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || c == '_' {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+fn property_name(name: &str) -> String {
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    match is_identifier {
+        true => name.to_string(),
+        false => format!("{name:?}"),
+    }
+}
+
+/// Wraps a union or intersection in parentheses, so it can be combined with other types
+fn group(ts: String) -> String {
+    match ts.contains(" | ") || ts.contains(" & ") {
+        true => format!("({ts})"),
+        false => ts,
+    }
+}
+
+/// The TypeScript type of a schema. `indent` is the indentation of the line the type starts on
+fn ts_type(schema: &Value, indent: &str) -> String {
+    let object = match schema {
+        Value::Bool(true) => return "unknown".into(),
+        Value::Bool(false) => return "never".into(),
+        Value::Object(x) => x,
+        _ => return "unknown".into(),
+    };
+
+    if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+        return type_name(reference.trim_start_matches("#/$defs/"));
+    }
+    if let Some(constant) = object.get("const") {
+        return constant.to_string();
+    }
+    if let Some(variants) = object.get("enum").and_then(Value::as_array) {
+        return variants
+            .iter()
+            .map(Value::to_string)
+            .collect::<Vec<_>>()
+            .join(" | ");
+    }
+
+    let mut parts = vec![];
+    if let Some(base) = base_type(object, indent) {
+        parts.push(base);
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(variants) = object.get(key).and_then(Value::as_array) {
+            let union = variants
+                .iter()
+                .map(|x| group(ts_type(x, indent)))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            parts.push(union);
+        }
+    }
+    if let Some(all) = object.get("allOf").and_then(Value::as_array) {
+        parts.extend(all.iter().map(|x| ts_type(x, indent)));
+    }
+
+    match parts.len() {
+        0 => "unknown".into(),
+        1 => parts.remove(0),
+        _ => parts.into_iter().map(group).collect::<Vec<_>>().join(" & "),
+    }
+}
+
+/// The type described by `type` and its keywords, ignoring the combinators
+fn base_type(object: &Map<String, Value>, indent: &str) -> Option<String> {
+    let types: Vec<&str> = match object.get("type") {
+        Some(Value::String(x)) => vec![x.as_str()],
+        Some(Value::Array(x)) => x.iter().filter_map(Value::as_str).collect(),
+        _ if object.contains_key("properties") => vec!["object"],
+        _ => return None,
+    };
+    let union = types
+        .into_iter()
+        .map(|x| match x {
+            "string" => "string".into(),
+            "integer" | "number" => "number".into(),
+            "boolean" => "boolean".into(),
+            "null" => "null".into(),
+            "array" => array_type(object, indent),
+            "object" => object_type(object, indent),
+            _ => "unknown".into(),
+        })
+        .collect::<Vec<String>>();
+    Some(match union.len() {
+        1 => union.into_iter().next().unwrap(),
+        _ => union.into_iter().map(group).collect::<Vec<_>>().join(" | "),
+    })
+}
+
+fn array_type(object: &Map<String, Value>, indent: &str) -> String {
+    if let Some(items) = object.get("prefixItems").and_then(Value::as_array) {
+        let items = items
+            .iter()
+            .map(|x| ts_type(x, indent))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return format!("[{items}]");
+    }
+    match object.get("items") {
+        Some(items) => format!("Array<{}>", ts_type(items, indent)),
+        None => "Array<unknown>".into(),
+    }
+}
+
+fn object_type(object: &Map<String, Value>, indent: &str) -> String {
+    let inner = format!("{indent}  ");
+    let required: Vec<&str> = object
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|x| x.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let mut fields = String::new();
+    if let Some(properties) = object.get("properties").and_then(Value::as_object) {
+        for (name, schema) in properties {
+            write_docs(
+                &mut fields,
+                schema.get("description").and_then(Value::as_str),
+                &inner,
+            );
+            let optional = match required.contains(&name.as_str()) {
+                true => "",
+                false => "?",
+            };
+            let _ = writeln!(
+                fields,
+                "{inner}{}{optional}: {};",
+                property_name(name),
+                ts_type(schema, &inner)
+            );
+        }
+    }
+    let fields = match fields.is_empty() {
+        true => None,
+        false => Some(format!("{{\n{fields}{indent}}}")),
+    };
+
+    let additional = match object.get("additionalProperties") {
+        None | Some(Value::Bool(false)) => None,
+        Some(schema) => {
+            let value = ts_type(schema, indent);
+            Some(match object.get("propertyNames") {
+                Some(keys) => format!("Partial<Record<{}, {value}>>", ts_type(keys, indent)),
+                None => format!("{{ [key: string]: {value} }}"),
+            })
+        }
+    };
+
+    match (fields, additional) {
+        (Some(fields), Some(additional)) => format!("{fields} & {additional}"),
+        (Some(fields), None) => fields,
+        (None, Some(additional)) => additional,
+        (None, None) => "Record<string, never>".into(),
+    }
+}
@@ -1,26 +1,43 @@
-use std::{path::PathBuf, str::FromStr, sync::Arc};
+//! The commands the frontend invokes. What each command takes and returns is declared in [contracts], see [contracts::handle] for how a call is processed.
 
-use chrono::{DateTime, Utc};
-use dirs::data_local_dir;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use tauri::{ipc::Channel, path::BaseDirectory, Emitter, Manager, Runtime};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use chrono::Utc;
+use serde_json::Value;
+use tauri::{ipc::Channel, AppHandle, Manager, Runtime};
 use tauri_plugin_opener::OpenerExt;
-use tauri_plugin_store::StoreExt;
 use tauri_plugin_updater::UpdaterExt;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
 
 use crate::{
-    plugins::{commands_armor, plugin_settings, PluginStateSource},
-    updates::{PendingUpdate, ReleaseChannel},
+    event_watchdog::LogEventWithContext,
+    plugins::{plugin_settings, PluginStateSource},
+    updates::PendingUpdate,
 };
 
 use super::{
+    audit_log::{AuditAction, AuditLog},
+    commands_armor::Caller,
+    contracts::{self, *},
+    dev_plugins::DevPluginLinks,
     frontend_server::HttpServerState,
-    generic_plugin_settings::{GenericPluginSettings, PluginsUiConfig},
-    plugin_settings::parse_key,
-    PluginsState,
+    generic_plugin_settings::{CommandLimits, GenericPluginSettings, TransitionTimeouts},
+    journal_stream::JournalStreams,
+    message_bus::{resolve_topic, BusMessage, MessageBus},
+    move_user_plugins,
+    package_signature::{PluginVerification, TrustStore},
+    permissions::{self, Permission},
+    plugin_files, plugin_id_problem,
+    plugin_roots::{search_roots, PluginRoot, PluginRootsConfig},
+    plugin_settings::{parse_key, QuotaExceeded},
+    rate_limits::{CommandClass, RateLimiter},
+    set_user_plugin_dir,
+    sidecar::SidecarSupervisor,
+    url_policy::{self, UrlDecision, UrlPrompts},
+    user_plugin_dir,
+    wasm_backend::{BackendCapability, WasmBackends},
+    BrokenPlugin, PluginState, PluginsState, ReconcileTrigger,
 };
 
 #[tauri::command]
@@ -28,56 +45,186 @@ pub(crate) async fn fetch_all_plugins<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-
-    #[derive(Deserialize)]
-    struct Input {} // admittedly, this is a bit silly
-    let data = state.read().await;
-    if let Err(e) = commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        return e.into();
-    };
+) -> Value {
+    contracts::handle(
+        FetchAllPlugins,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            fetch_all_plugins_from(&data, request)
+        },
+    )
+    .await
+}
 
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
+fn fetch_all_plugins_from(
+    data: &PluginsState,
+    _: Empty,
+) -> Result<HashMap<String, PluginState>, CommandError> {
+    Ok(data.plugin_states.clone())
+}
 
-    let response = match serde_json::to_value(&data.plugin_states) {
-        Ok(x) => x,
-        Err(_) => return json!({"success": false, "reason": "INTERNAL_FAILED_CONVERSION"}),
-    };
+/// Returns all plugins that were found during the last reconcile, but could not be loaded (e.g. due to a malformed manifest)
+#[tauri::command]
+pub(crate) async fn fetch_broken_plugins<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        FetchBrokenPlugins,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            fetch_broken_plugins_from(&data, request)
+        },
+    )
+    .await
+}
 
-    match commands_armor::encrypt(&data.root_token, &response) {
-        Ok(encrypted_with_iv) => encrypted_with_iv,
-        Err(e) => e.into(),
-    }
+fn fetch_broken_plugins_from(
+    data: &PluginsState,
+    _: Empty,
+) -> Result<Vec<BrokenPlugin>, CommandError> {
+    Ok(data.broken_plugins.clone())
 }
 
+/// Opens a URL in the user's browser. Only `http` and `https` URLs are opened.
+/// URLs outside the plugin's granted domains need the user's confirmation, see [url_policy]
 #[tauri::command]
 pub(crate) async fn open_url<R: Runtime>(
     app: tauri::AppHandle<R>,
+    window: tauri::Window<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String, // unused, maybe relevant later
-        url: String,
-    }
+) -> Value {
+    contracts::handle(
+        OpenUrl,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            let plugin_state = match data.get_cloned(caller.plugin_id(&request.plugin_id)) {
+                Some(x) => x,
+                None => return Err(CommandError::PluginStateNotFound),
+            };
+            app.state::<RateLimiter>().check(
+                &app,
+                caller.plugin.as_deref(),
+                CommandClass::OpenUrl,
+            )?;
+
+            let host = url_policy::host_of(&request.url)?;
+            match url_policy::decided(&app, &plugin_state, &host) {
+                Some(true) => drop(data),
+                Some(false) => return Err(CommandError::UrlDenied),
+                None => {
+                    let prompts = app.state::<UrlPrompts>();
+                    let (prompt, answer) = prompts.open(&plugin_state, &request.url, &host)?;
+                    if let Err(e) =
+                        data.emit_armored_to::<UrlPromptOpened, _>(&app, window.label(), &prompt)
+                    {
+                        prompts.cancel(&prompt.prompt_id);
+                        return Err(e.into());
+                    }
+                    // the user may take a while, other commands must not wait for them
+                    drop(data);
+                    prompts.wait(&prompt.prompt_id, answer).await?;
+                }
+            }
 
-    let data = state.read().await;
+            if let Err(e) = app.opener().open_url(&request.url, None::<&str>) {
+                return Err(CommandError::OpenUrlFailed {
+                    message: e.to_string(),
+                });
+            }
+            app.state::<AuditLog>().record(
+                caller.plugin.as_deref(),
+                Some(&plugin_state.id),
+                AuditAction::OpenUrl { url: request.url },
+            );
+            Ok(())
+        },
+    )
+    .await
+}
 
-    let url = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Err(e) => return e.into(),
-        Ok(data) => data.url,
-    };
+/// Answers a prompt of `open_url`. If `remember` is set, the decision applies to all further URLs of the plugin on that host
+#[tauri::command]
+pub(crate) async fn answer_url_prompt<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        AnswerUrlPrompt,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let prompt = match app
+                .state::<UrlPrompts>()
+                .answer(&request.prompt_id, request.allow)
+            {
+                Some(x) => x,
+                None => return Err(CommandError::PromptNotFound),
+            };
+            if request.remember {
+                let decision = match request.allow {
+                    true => UrlDecision::Allow,
+                    false => UrlDecision::Deny,
+                };
+                if let Err(e) =
+                    url_policy::remember(&app, &prompt.plugin_id, &prompt.host, Some(decision))
+                {
+                    error!("failed to remember URL decision: {e}");
+                    return Err(CommandError::InternalFetchStoreError);
+                }
+            }
+            app.state::<AuditLog>().record(
+                None,
+                Some(&prompt.plugin_id),
+                AuditAction::AnswerUrlPrompt {
+                    url: prompt.url,
+                    allowed: request.allow,
+                    remembered: request.remember,
+                },
+            );
+            Ok(())
+        },
+    )
+    .await
+}
 
-    match app.opener().open_url(url, None::<&str>) {
-        Ok(_) => json!({"success": true}),
-        Err(x) => json!({"success": false, "reason": x}),
-    }
+/// Forgets a remembered decision of `open_url`, so the user is asked again for that host
+#[tauri::command]
+pub(crate) async fn forget_url_decision<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        ForgetUrlDecision,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            if let Err(e) = url_policy::remember(&app, &request.plugin_id, &request.host, None) {
+                error!("failed to forget URL decision: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            Ok(())
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -85,55 +232,50 @@ pub(crate) async fn check_update_edpf<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-
-    #[derive(Deserialize)]
-    struct Input {
-        channel: ReleaseChannel,
-    }
-    let endpoint = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Err(e) => return e.into(),
-        Ok(a) => a.channel.infer_endpoint(),
-    };
-
-    let pending_update_state = app.state::<PendingUpdate>();
-
-    let updater = match match app.updater_builder().endpoints(vec![endpoint]) {
-        Ok(x) => x,
-        Err(e) => return json!({"err": e, "hint": "build endpoints"}),
-    }
-    .build()
-    {
-        Ok(x) => x,
-        Err(e) => return json!({"err": e, "hint": "build updater"}),
-    };
-
-    let update_check_response = match updater.check().await {
-        Ok(x) => x,
-        Err(e) => return json!({"err": e, "hint": "look for updates"}),
-    };
-
-    info!("has update: {}", update_check_response.is_some());
+) -> Value {
+    contracts::handle(
+        CheckUpdateEdpf,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let endpoint = request.channel.infer_endpoint();
+            let pending_update_state = app.state::<PendingUpdate>();
+
+            let updater = match app
+                .updater_builder()
+                .endpoints(vec![endpoint])
+                .and_then(|x| x.build())
+            {
+                Ok(x) => x,
+                Err(e) => {
+                    return Err(CommandError::UpdateCheckFailed {
+                        message: format!("failed to build updater: {e}"),
+                    })
+                }
+            };
 
-    #[derive(Serialize)]
-    struct Response {
-        new_version: String,
-        current_version: String,
-    }
+            let update_check_response = match updater.check().await {
+                Ok(x) => x,
+                Err(e) => {
+                    return Err(CommandError::UpdateCheckFailed {
+                        message: format!("failed to look for updates: {e}"),
+                    })
+                }
+            };
 
-    let command_resp = update_check_response.as_ref().map(|update| Response {
-        new_version: update.version.clone(),
-        current_version: update.current_version.clone(),
-    });
+            info!("has update: {}", update_check_response.is_some());
 
-    *pending_update_state.0.lock().unwrap() = update_check_response;
+            let command_resp = update_check_response.as_ref().map(|update| UpdateInfo {
+                new_version: update.version.clone(),
+                current_version: update.current_version.clone(),
+            });
 
-    match commands_armor::encrypt(&data.root_token, &command_resp) {
-        Ok(encrypted_with_iv) => encrypted_with_iv,
-        Err(e) => e.into(),
-    }
+            *pending_update_state.0.lock().unwrap() = update_check_response;
+            Ok(command_resp)
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -141,47 +283,48 @@ pub(crate) async fn commit_update_edpf<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-    on_event: Channel<serde_json::Value>,
-) -> serde_json::Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-
-    #[derive(Deserialize)]
-    struct Input {}
-
-    if let Err(e) = commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        return e.into();
-    };
-    let pending_update_state = app.state::<PendingUpdate>();
-
-    let mut started = false;
-    let Some(update) = pending_update_state.0.lock().unwrap().take() else {
-        return json!({"success": false, "reason": "NO_PENDING_UPDATE"});
-    };
-
-    match update
-        .download_and_install(
-            |chunk_len, content_len| {
-                if !started {
-                    let _ = on_event.send(json!({"type": "Started", "content_len": content_len}));
-                    started = true;
-                }
-
-                let _ = on_event.send(json!({"type": "Progress", "chunk_len": chunk_len }));
-            },
-            || {
-                let _ = on_event.send(json!({"type": "Finished"}));
-            },
-        )
-        .await
-    {
-        Ok(_) => {
-            json!({"success": true})
-        }
-        Err(e) => {
-            json!({"success": false, "reason": "UPDATE_FAILED", "meta": e})
-        }
-    }
+    on_event: Channel<UpdateProgress>,
+) -> Value {
+    contracts::handle(
+        CommitUpdateEdpf,
+        &app,
+        &iv,
+        &payload,
+        |app, _, _| async move {
+            let pending_update_state = app.state::<PendingUpdate>();
+
+            let mut started = false;
+            let Some(update) = pending_update_state.0.lock().unwrap().take() else {
+                return Err(CommandError::NoPendingUpdate);
+            };
+            let version = update.version.clone();
+
+            let installed = update
+                .download_and_install(
+                    |chunk_len, content_len| {
+                        if !started {
+                            let _ = on_event.send(UpdateProgress::Started { content_len });
+                            started = true;
+                        }
+
+                        let _ = on_event.send(UpdateProgress::Progress { chunk_len });
+                    },
+                    || {
+                        let _ = on_event.send(UpdateProgress::Finished);
+                    },
+                )
+                .await;
+            if let Err(e) = installed {
+                return Err(CommandError::UpdateFailed {
+                    meta: e.to_string(),
+                });
+            }
+            app.state::<AuditLog>()
+                .record(None, None, AuditAction::UpdateEdpf { version });
+            Ok(())
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -189,44 +332,34 @@ pub(crate) async fn get_import_path_for_plugin<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
+) -> Value {
+    contracts::handle(
+        GetImportPathForPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            get_import_path_for_plugin_from(&app, &data, request)
+        },
+    )
+    .await
+}
 
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-    }
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
+fn get_import_path_for_plugin_from<R: Runtime>(
+    app: &AppHandle<R>,
+    data: &PluginsState,
+    request: PluginIdRequest,
+) -> Result<ImportPath, CommandError> {
+    let Some(x) = data.plugin_states.get(&request.plugin_id) else {
+        return Err(CommandError::PluginNotFound);
     };
-
-    if let Some(x) = data.plugin_states.get(&payload.plugin_id) {
-        let http_state = app.state::<HttpServerState>();
-        let import = http_state.make_import_base(x);
-
-        #[derive(Serialize)]
-        #[serde(rename_all = "camelCase")]
-        struct Response {
-            hash: String,
-            import: String,
-        }
-
-        match commands_armor::encrypt(
-            &data.root_token,
-            &Response {
-                hash: x.frontend_hash.clone(),
-                import: format!("{import}/index.js"),
-            },
-        ) {
-            Ok(encrypted_with_iv) => encrypted_with_iv,
-            Err(e) => e.into(),
-        }
-    } else {
-        json!({"success": false, "reason": "PLUGIN_NOT_FOUND"})
-    }
+    let import = app.state::<HttpServerState>().make_import_base(x);
+    Ok(ImportPath {
+        hash: x.frontend_hash.clone(),
+        import: format!("{import}/index.js"),
+    })
 }
 
 #[tauri::command]
@@ -234,67 +367,51 @@ pub(crate) async fn open_plugins_dir<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    use tauri_plugin_opener::OpenerExt;
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: Option<String>,
-    }
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-
-    let plugin_id = match payload.plugin_id {
-        Some(x) => x,
-        None => {
-            // no plugin ID specified -> we return the user plugin folder
-            let user_plugin_dir: String = match app.store("store.json") {
-                Ok(x) => x,
-                Err(e) => {
-                    error!("failed to open store.json: {e}");
-                    return json!({"success": false, "reason": "INTERNAL_FETCH_STORE_ERROR"});
+) -> Value {
+    contracts::handle(
+        OpenPluginsDir,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let plugin_id = match request.plugin_id {
+                Some(x) => x,
+                None => {
+                    // no plugin ID specified -> we return the user plugin folder
+                    let user_plugin_dir = match user_plugin_dir(&app) {
+                        Ok(x) => x.display().to_string(),
+                        Err(e) => {
+                            error!("failed to open store.json: {e}");
+                            return Err(CommandError::InternalFetchStoreError);
+                        }
+                    };
+                    if let Err(e) = app.opener().open_path(user_plugin_dir, None::<&str>) {
+                        error!("failed to open dir: {e}");
+                        return Err(CommandError::InternalOpenPluginDirError);
+                    }
+                    return Ok(());
                 }
+            };
+
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            let Some(x) = data.plugin_states.get(&plugin_id) else {
+                return Err(CommandError::PluginNotFound);
+            };
+            if x.source == PluginStateSource::Embedded {
+                return Err(CommandError::PluginNotUserprovided);
             }
-            .get("plugin_dir")
-            .and_then(|x| {
-                let x = x.to_string();
-                PathBuf::from_str(&x).ok()
-            })
-            .unwrap_or(data_local_dir().unwrap().join("edpf-plugins"))
-            .display()
-            .to_string();
-            if let Err(e) = app.opener().open_path(user_plugin_dir, None::<&str>) {
-                error!("failed to open dir: {e}");
-                return json!({"success": false, "reason": "INTERNAL_OPEN_PLUGIN_DIR_ERROR"});
-            }
-            return json!({"success": true});
-        }
-    };
-
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-    if let Some(x) = data.plugin_states.get(&plugin_id) {
-        if x.source != PluginStateSource::UserProvided {
-            json!({"success": false, "reason": "PLUGIN_NOT_USERPROVIDED"})
-        } else {
             if let Err(e) = app.opener().open_path(
                 x.frontend_path().parent().unwrap().display().to_string(),
                 None::<&str>,
             ) {
                 error!("failed to open dir: {e}");
-                return json!({"success": false, "reason": "INTERNAL_OPEN_PLUGIN_DIR_ERROR"});
+                return Err(CommandError::InternalOpenPluginDirError);
             }
-
-            json!({"success": true})
-        }
-    } else {
-        json!({"success": false, "reason": "PLUGIN_NOT_FOUND"})
-    }
+            Ok(())
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -302,38 +419,27 @@ pub(crate) async fn open_settings<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-
-    #[derive(Deserialize)]
-    struct Input {} // admittedly, this is a bit silly
-    let data = state.read().await;
-    if let Err(e) = commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        return e.into();
-    };
-
-    let resp = if let Some(win) = app.get_webview_window("settings") {
-        win.set_focus()
-    } else {
-        let win = tauri::WebviewWindowBuilder::new(
-            &app,
-            "settings",
-            tauri::WebviewUrl::App("index.html#/settings".into()),
-        )
-        .title("EDPF Settings")
-        .build()
-        .unwrap();
-        win.set_focus()
-    };
-
-    match resp {
-        Ok(_) => {
-            json!({"success": true})
-        }
-        Err(e) => {
-            json!({"success": false, "reason": e.to_string()})
+) -> Value {
+    contracts::handle(OpenSettings, &app, &iv, &payload, |app, _, _| async move {
+        let resp = match app.get_webview_window("settings") {
+            Some(win) => win.set_focus(),
+            None => tauri::WebviewWindowBuilder::new(
+                &app,
+                "settings",
+                tauri::WebviewUrl::App("index.html#/settings".into()),
+            )
+            .title("EDPF Settings")
+            .build()
+            .and_then(|win| win.set_focus()),
+        };
+        match resp {
+            Ok(_) => Ok(()),
+            Err(e) => Err(CommandError::WindowFailed {
+                message: e.to_string(),
+            }),
         }
-    }
+    })
+    .await
 }
 
 #[tauri::command]
@@ -342,40 +448,85 @@ pub(crate) async fn start_plugin<R: Runtime>(
     payload: String,
     iv: String,
 ) -> Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let mut data = state.write().await;
+    contracts::handle(
+        StartPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let mut data = state.write().await;
+
+            let mut settings = match GenericPluginSettings::get_by_id(&app, &request.plugin_id) {
+                Ok(it) => it,
+                Err(err) => {
+                    error!("failed to get generic plugin settings by ID: {err}");
+                    return Err(CommandError::InternalMissingGeneralSettings);
+                }
+            }
+            .unwrap_or_default();
+            if !settings.enabled {
+                settings.enabled = true;
+                _ = settings.commit(&app, &request.plugin_id)
+            }
 
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-    }
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
+            if let Err(e) = data.start(request.plugin_id.clone(), &app).await {
+                return Err(CommandError::TransitionFailed {
+                    message: e.to_string(),
+                });
+            }
+            app.state::<AuditLog>()
+                .record(None, Some(&request.plugin_id), AuditAction::Start);
+            Ok(())
+        },
+    )
+    .await
+}
 
-    let mut settings = match GenericPluginSettings::get_by_id(&app, &payload.plugin_id) {
-        Ok(it) => it,
-        Err(err) => {
-            error!("failed to get generic plugin settings by ID: {err}");
-            return json!({"success": false, "reason": "INTERNAL_MISSING_GENERAL_SETTINGS"});
-        }
-    }
-    .unwrap_or_default();
-    if !settings.enabled {
-        settings.enabled = true;
-        _ = settings.commit(&app, &payload.plugin_id)
-    }
+/// Clears the failure history of a plugin, lifts its quarantine and starts it right away, skipping any backoff
+#[tauri::command]
+pub(crate) async fn retry_plugin<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        RetryPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let mut data = state.write().await;
+
+            let mut settings = match GenericPluginSettings::get_by_id(&app, &request.plugin_id) {
+                Ok(it) => it,
+                Err(err) => {
+                    error!("failed to get generic plugin settings by ID: {err}");
+                    return Err(CommandError::InternalMissingGeneralSettings);
+                }
+            }
+            .unwrap_or_default();
+            settings.reset_failures();
+            if let Err(e) = settings.commit(&app, &request.plugin_id) {
+                error!(
+                    "failed to reset failures of plugin {}: {e}",
+                    request.plugin_id
+                );
+                return Err(CommandError::InternalFetchStoreError);
+            }
 
-    match data.start(payload.plugin_id, &app).await {
-        Ok(_) => {
-            json!({"success": true})
-        }
-        Err(e) => {
-            json!({"success": false, "reason": e.to_string()})
-        }
-    }
+            if let Err(e) = data.start(request.plugin_id.clone(), &app).await {
+                return Err(CommandError::TransitionFailed {
+                    message: e.to_string(),
+                });
+            }
+            app.state::<AuditLog>()
+                .record(None, Some(&request.plugin_id), AuditAction::Retry);
+            Ok(())
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -384,27 +535,23 @@ pub(crate) async fn finalize_start_plugin<R: Runtime>(
     payload: String,
     iv: String,
 ) -> Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let mut data = state.write().await;
-
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-    }
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-
-    match data.finalize_start(payload.plugin_id, &app).await {
-        Ok(_) => {
-            json!({"success": true})
-        }
-        Err(e) => {
-            json!({"success": false, "reason": e.to_string()})
-        }
-    }
+    contracts::handle(
+        FinalizeStartPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let mut data = state.write().await;
+            match data.finalize_start(request.plugin_id, &app).await {
+                Ok(_) => Ok(()),
+                Err(e) => Err(CommandError::TransitionFailed {
+                    message: e.to_string(),
+                }),
+            }
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -413,29 +560,26 @@ pub(crate) async fn start_plugin_failed<R: Runtime>(
     payload: String,
     iv: String,
 ) -> Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let mut data = state.write().await;
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-        reasons: Vec<String>,
-    }
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-    match data
-        .start_failed(payload.plugin_id, payload.reasons, &app)
-        .await
-    {
-        Ok(_) => {
-            json!({"success": true})
-        }
-        Err(e) => {
-            json!({"success": false, "reason": e.to_string()})
-        }
-    }
+    contracts::handle(
+        StartPluginFailed,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let mut data = state.write().await;
+            match data
+                .start_failed(request.plugin_id, request.reasons, &app)
+                .await
+            {
+                Ok(_) => Ok(()),
+                Err(e) => Err(CommandError::TransitionFailed {
+                    message: e.to_string(),
+                }),
+            }
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -444,39 +588,39 @@ pub(crate) async fn stop_plugin<R: Runtime>(
     payload: String,
     iv: String,
 ) -> Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let mut data = state.write().await;
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-    }
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-
-    let mut settings = match GenericPluginSettings::get_by_id(&app, &payload.plugin_id) {
-        Ok(it) => it,
-        Err(err) => {
-            error!("failed to get generic plugin settings by ID: {err}");
-            return json!({"success": false, "reason": "INTERNAL_MISSING_GENERAL_SETTINGS"});
-        }
-    }
-    .unwrap_or_default();
-    if settings.enabled {
-        settings.enabled = false;
-        _ = settings.commit(&app, &payload.plugin_id)
-    }
+    contracts::handle(
+        StopPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let mut data = state.write().await;
+
+            let mut settings = match GenericPluginSettings::get_by_id(&app, &request.plugin_id) {
+                Ok(it) => it,
+                Err(err) => {
+                    error!("failed to get generic plugin settings by ID: {err}");
+                    return Err(CommandError::InternalMissingGeneralSettings);
+                }
+            }
+            .unwrap_or_default();
+            if settings.enabled {
+                settings.enabled = false;
+                _ = settings.commit(&app, &request.plugin_id)
+            }
 
-    match data.stop(payload.plugin_id, &app).await {
-        Ok(_) => {
-            json!({"success": true})
-        }
-        Err(e) => {
-            json!({"success": false, "reason": e.to_string()})
-        }
-    }
+            if let Err(e) = data.stop(request.plugin_id.clone(), &app).await {
+                return Err(CommandError::TransitionFailed {
+                    message: e.to_string(),
+                });
+            }
+            app.state::<AuditLog>()
+                .record(None, Some(&request.plugin_id), AuditAction::Stop);
+            Ok(())
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -485,26 +629,23 @@ pub(crate) async fn finalize_stop_plugin<R: Runtime>(
     payload: String,
     iv: String,
 ) -> Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-    }
-    let mut data = state.write().await;
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-    match data.finalize_stop(payload.plugin_id, &app).await {
-        Ok(_) => {
-            json!({"success": true})
-        }
-        Err(e) => {
-            json!({"success": false, "reason": e.to_string()})
-        }
-    }
+    contracts::handle(
+        FinalizeStopPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let mut data = state.write().await;
+            match data.finalize_stop(request.plugin_id, &app).await {
+                Ok(_) => Ok(()),
+                Err(e) => Err(CommandError::TransitionFailed {
+                    message: e.to_string(),
+                }),
+            }
+        },
+    )
+    .await
 }
 
 #[tauri::command]
@@ -513,25 +654,27 @@ pub(crate) async fn get_plugin<R: Runtime>(
     payload: String,
     iv: String,
 ) -> Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-    }
-    let data = state.read().await;
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-
-    match data.plugin_states.get(&payload.plugin_id).cloned() {
-        None => json!({"success": false, "reason": "PLUGIN_STATE_NOT_FOUND"}),
-        Some(state) => match commands_armor::encrypt(&data.root_token, &state) {
-            Ok(encrypted_with_iv) => encrypted_with_iv,
-            Err(e) => e.into(),
+    contracts::handle(
+        GetPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            get_plugin_from(&data, request)
         },
+    )
+    .await
+}
+
+fn get_plugin_from(
+    data: &PluginsState,
+    request: PluginIdRequest,
+) -> Result<PluginState, CommandError> {
+    match data.get_cloned(&request.plugin_id) {
+        Some(x) => Ok(x),
+        None => Err(CommandError::PluginStateNotFound),
     }
 }
 
@@ -541,223 +684,1310 @@ pub(crate) async fn write_setting<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-        key: String,
-        value: serde_json::Value,
-    }
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-    let plugin_state = match data.get_cloned(&payload.plugin_id) {
-        Some(x) => x,
-        None => return json!({"success": false, "reason": "PLUGIN_STATE_NOT_FOUND"}),
-    };
-
-    // each plugin stores settings in its own file
-    let key = match parse_key(&payload.key) {
-        Ok(x) => x,
-        Err(e) => return json!({"success": false, "reason": e}),
-    };
-
-    if !key.is_writable_by(&plugin_state.id) {
-        return json!({"success": false, "reason": "SECRET_NOT_WRITABLE_MISSING_PERMISSION"});
-    }
-
-    let resp_value = match plugin_settings::write_setting(&app, &key, payload.value) {
-        Ok(parsed_val) => parsed_val,
-        Err(_) => return json!({"success": false, "reason": "FAILED_WRITE"}),
-    };
-
-    #[derive(Serialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Response {
-        value: serde_json::Value,
-        key: String,
-    }
-
-    let resp = Response {
-        key: payload.key,
-        value: resp_value,
-    };
-    let encrypted = match commands_armor::encrypt(&data.root_token, &resp) {
-        Ok(encrypted_with_iv) => encrypted_with_iv,
-        Err(e) => return e.into(),
-    };
+) -> Value {
+    contracts::handle(
+        WriteSetting,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            let plugin_state = match data.get_cloned(caller.plugin_id(&request.plugin_id)) {
+                Some(x) => x,
+                None => return Err(CommandError::PluginStateNotFound),
+            };
+            app.state::<RateLimiter>().check(
+                &app,
+                caller.plugin.as_deref(),
+                CommandClass::WriteSetting,
+            )?;
+
+            // each plugin stores settings in its own file
+            let key = parse_key(&request.key)?;
+
+            if !key.is_writable_by(&plugin_state.id) {
+                return Err(CommandError::SecretNotWritableMissingPermission);
+            }
 
-    _ = app.emit("settings_update", &encrypted);
-    encrypted
+            let value = match plugin_settings::write_setting(&app, &key, request.value) {
+                Ok(parsed_val) => parsed_val,
+                Err(e) if e.is::<QuotaExceeded>() => {
+                    app.state::<RateLimiter>().record(
+                        &app,
+                        &plugin_state.id,
+                        CommandClass::WriteSetting,
+                        "QUOTA_EXCEEDED",
+                    );
+                    return Err(CommandError::QuotaExceeded);
+                }
+                Err(_) => return Err(CommandError::FailedWrite),
+            };
+            app.state::<AuditLog>().record(
+                caller.plugin.as_deref(),
+                Some(key.plugin_id()),
+                AuditAction::WriteSetting {
+                    key: request.key.clone(),
+                },
+            );
+
+            let resp = SettingValue {
+                key: request.key,
+                value: Some(value),
+            };
+            data.emit_settings_update(&app, &key, &resp)?;
+            Ok(resp)
+        },
+    )
+    .await
 }
 
-/// Write a Setting. On success, get back the value in the response. The response is the stored value, meaning any serializing has taken place.
+/// Read a Setting. Public settings of other plugins need a [Permission::SettingsRead] grant
 #[tauri::command]
 pub(crate) async fn read_setting<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
-) -> serde_json::Value {
-    #[derive(Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Input {
-        plugin_id: String,
-        key: String,
-    }
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-    let payload = match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-        Ok(x) => x,
-        Err(e) => return e.into(),
-    };
-
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
+) -> Value {
+    contracts::handle(
+        ReadSetting,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            read_setting_from(&app, &caller, &data, request)
+        },
+    )
+    .await
+}
 
-    let plugin_state = match data.get_cloned(&payload.plugin_id) {
+fn read_setting_from<R: Runtime>(
+    app: &AppHandle<R>,
+    caller: &Caller,
+    data: &PluginsState,
+    request: ReadSettingRequest,
+) -> Result<SettingValue, CommandError> {
+    let plugin_state = match data.get_cloned(caller.plugin_id(&request.plugin_id)) {
         Some(x) => x,
-        None => return json!({"success": false, "reason": "MISSING_OR_BAD_TOKEN"}),
+        None => return Err(CommandError::MissingOrBadToken),
     };
+    app.state::<RateLimiter>()
+        .check(app, caller.plugin.as_deref(), CommandClass::ReadSetting)?;
 
     // each plugin stores settings in its own file
-    let key = match parse_key(&payload.key) {
-        Ok(x) => x,
-        Err(e) => return json!({"success": false, "reason": e}),
-    };
+    let key = parse_key(&request.key)?;
 
     if !key.is_readable_by(&plugin_state.id) {
-        return json!({"success": false, "reason": "SECRET_NOT_READABLE_MISSING_PERMISSION"});
+        return Err(CommandError::SecretNotReadableMissingPermission);
     }
-
-    let resp_value = match plugin_settings::read_setting(&app, &key) {
-        Ok(x) => x,
-        Err(_) => return json!({"success": false, "reason": "FAILED_READ"}),
-    };
-
-    #[derive(Serialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Response {
-        value: Option<serde_json::Value>,
-        key: String,
+    // Public settings of other plugins need a grant
+    if key.plugin_id() != plugin_state.id {
+        let needed = Permission::SettingsRead {
+            plugin_id: key.plugin_id().to_string(),
+        };
+        permissions::check(app, &plugin_state, &needed)?;
     }
 
-    let resp = Response {
-        value: resp_value,
-        key: payload.key,
-    };
-
-    match commands_armor::encrypt(&data.root_token, &resp) {
-        Ok(encrypted_with_iv) => encrypted_with_iv,
-        Err(e) => e.into(),
+    match plugin_settings::read_setting(app, &key) {
+        Ok(value) => Ok(SettingValue {
+            key: request.key,
+            value,
+        }),
+        Err(_) => Err(CommandError::FailedRead),
     }
 }
 
-/// This command looks at all active CMDR journals (taken from last updated) and returns their entire contents
-#[tauri::command]
-pub(crate) async fn reread_active_journal<R: Runtime>(app: tauri::AppHandle<R>) -> Value {
-    let state = app
-        .state::<Arc<RwLock<bimap::BiMap<String, PathBuf>>>>()
-        .inner()
-        .clone();
-
-    let items: Vec<(String, PathBuf)> = {
-        let data = state.read().await;
-        data.iter()
-            .map(|(cmdr, path)| (cmdr.clone(), path.clone()))
-            .collect()
-    };
-
-    #[derive(Serialize)]
-    struct Response {
-        cmdr: String,
-        file: PathBuf,
-        entries: Vec<String>,
-    }
-    let futures = items.into_iter().map(|(cmdr, path)| async move {
-        let mut reader = match ed_journals::logs::asynchronous::RawLogFileReader::open(&path).await
-        {
-            Ok(x) => x,
-            Err(e) => {
-                error!(
-                    "failed to open journal to reread: {}, skipping. reason: {}",
-                    path.display(),
-                    e
-                );
-                return None; // skip this one
-            }
-        };
-
-        let mut entries = Vec::new();
-        while let Some(res) = reader.next().await {
-            match res {
-                Ok(ev) => entries.push(serde_json::to_string(&ev).unwrap()),
-                Err(err) => {
-                    warn!(
-                        "failed to read entry in {}: {err}, skipping entry",
-                        path.display()
-                    );
-                }
-            }
-        }
-
-        Some(Response {
-            cmdr,
-            file: path,
-            entries,
-        })
-    });
-    // We run the fetching / collecting of existing lines in parallel
-    let results = futures::future::join_all(futures).await;
-    // get rid of any Nones (skipped / errored)
-    let response: Vec<_> = results.into_iter().flatten().collect();
-
-    let root_token = {
+/// Looks up the calling plugin and its storage folder, after checking its rate limit and [Permission::FileStorage] grant
+async fn plugin_storage<R: Runtime>(
+    app: &AppHandle<R>,
+    caller: &Caller,
+    plugin_id: &str,
+    class: CommandClass,
+) -> Result<(PluginState, PathBuf), CommandError> {
+    let plugin_state = {
         let state = app.state::<Arc<RwLock<PluginsState>>>();
         let data = state.read().await;
-        data.root_token
+        match data.get_cloned(caller.plugin_id(plugin_id)) {
+            Some(x) => x,
+            None => return Err(CommandError::PluginStateNotFound),
+        }
     };
-
-    match commands_armor::encrypt(&root_token, &response) {
-        Ok(encrypted_with_iv) => encrypted_with_iv,
-        Err(e) => e.into(),
+    app.state::<RateLimiter>()
+        .check(app, caller.plugin.as_deref(), class)?;
+    permissions::check(app, &plugin_state, &Permission::FileStorage)?;
+    match plugin_files::storage_dir(app, &plugin_state.id) {
+        Ok(dir) => Ok((plugin_state, dir)),
+        Err(e) => {
+            error!("failed to resolve storage folder: {e}");
+            Err(CommandError::FailedRead)
+        }
     }
 }
 
-/// This command is invoked by the PluginManager when elements in the UI are moved around. This same command is used to just fetch the config
+/// Lists the files in the plugin's storage folder. See [plugin_files]
 #[tauri::command]
-pub(crate) async fn sync_main_layout<R: Runtime>(
+pub(crate) async fn list_plugin_files<R: Runtime>(
     app: tauri::AppHandle<R>,
     payload: String,
     iv: String,
 ) -> Value {
-    let state = app.state::<Arc<RwLock<PluginsState>>>();
-    let data = state.read().await;
-    let layout = {
-        #[derive(Deserialize)]
-        #[serde(rename_all = "camelCase")]
-        struct Input {
-            layout: Option<PluginsUiConfig>,
-        }
-        match commands_armor::decrypt_str::<Input>(&data.root_token, &iv, &payload) {
-            Ok(x) => x.layout,
-            Err(e) => return e.into(),
-        }
-    };
-
-    let resp = match GenericPluginSettings::sync_ui_layout(&app, layout) {
-        Ok(x) => x,
-        Err(e) => return json!({"success": true, "data": format!("failed to sync ui layout: {e}")}),
-    };
+    contracts::handle(
+        ListPluginFiles,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let (_, dir) =
+                plugin_storage(&app, &caller, &request.plugin_id, CommandClass::ReadFile).await?;
+            plugin_files::list(&dir).map_err(|e| {
+                error!("failed to list plugin files: {e}");
+                CommandError::FailedRead
+            })
+        },
+    )
+    .await
+}
+
+#[tauri::command]
+pub(crate) async fn read_plugin_file<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        ReadPluginFile,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let (_, dir) =
+                plugin_storage(&app, &caller, &request.plugin_id, CommandClass::ReadFile).await?;
+            let path = plugin_files::parse_path(&request.path)?;
+            match plugin_files::read(&dir, &path) {
+                Ok(Some(x)) => String::from_utf8(x).map_err(|_| CommandError::FailedRead),
+                Ok(None) => Err(CommandError::FileNotFound),
+                Err(e) => {
+                    error!("failed to read plugin file: {e}");
+                    Err(CommandError::FailedRead)
+                }
+            }
+        },
+    )
+    .await
+}
+
+#[tauri::command]
+pub(crate) async fn write_plugin_file<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        WritePluginFile,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let (plugin_state, dir) =
+                plugin_storage(&app, &caller, &request.plugin_id, CommandClass::WriteFile).await?;
+            let path = plugin_files::parse_path(&request.path)?;
+            let quota = CommandLimits::for_plugin(&app, &plugin_state.id)
+                .map_err(|_| CommandError::InternalFetchStoreError)?
+                .files_quota_bytes;
+            match plugin_files::write(&dir, &path, request.contents.as_bytes(), quota) {
+                Ok(()) => {}
+                Err(e) if e.is::<QuotaExceeded>() => {
+                    app.state::<RateLimiter>().record(
+                        &app,
+                        &plugin_state.id,
+                        CommandClass::WriteFile,
+                        "QUOTA_EXCEEDED",
+                    );
+                    return Err(CommandError::QuotaExceeded);
+                }
+                Err(e) => {
+                    error!("failed to write plugin file: {e}");
+                    return Err(CommandError::FailedWrite);
+                }
+            };
+            app.state::<AuditLog>().record(
+                caller.plugin.as_deref(),
+                Some(&plugin_state.id),
+                AuditAction::WriteFile { path: request.path },
+            );
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Deletes a file from the plugin's storage folder. Deleting a file that does not exist succeeds
+#[tauri::command]
+pub(crate) async fn delete_plugin_file<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        DeletePluginFile,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let (plugin_state, dir) =
+                plugin_storage(&app, &caller, &request.plugin_id, CommandClass::WriteFile).await?;
+            let path = plugin_files::parse_path(&request.path)?;
+            match plugin_files::delete(&dir, &path) {
+                Ok(true) => app.state::<AuditLog>().record(
+                    caller.plugin.as_deref(),
+                    Some(&plugin_state.id),
+                    AuditAction::DeleteFile { path: request.path },
+                ),
+                Ok(false) => {}
+                Err(e) => {
+                    error!("failed to delete plugin file: {e}");
+                    return Err(CommandError::FailedWrite);
+                }
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Opens the live journal stream of a plugin. New journal events of the classes the plugin was granted (see [Permission::JournalEvents])
+/// are sent as frames on `on_frame` (see [super::journal_stream]), until the page is unloaded or the plugin opens a new stream
+#[tauri::command]
+pub(crate) async fn open_journal_stream<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    window: tauri::Window<R>,
+    payload: String,
+    iv: String,
+    on_frame: Channel<tauri::ipc::Response>,
+) -> Value {
+    contracts::handle(
+        OpenJournalStream,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            let Some(plugin_state) = data.get_cloned(caller.plugin_id(&request.plugin_id)) else {
+                return Err(CommandError::PluginStateNotFound);
+            };
+
+            app.state::<JournalStreams>().open(
+                window.label(),
+                &plugin_state.id,
+                caller.key,
+                on_frame,
+                true,
+            );
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Acknowledges that the facade handled the first `received` frames of a journal stream, so more frames can be sent
+#[tauri::command]
+pub(crate) async fn ack_journal_frames<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        AckJournalFrames,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let streams = app.state::<JournalStreams>();
+            let stream = match streams.get(request.channel_id) {
+                Some(x) if x.plugin_id() == caller.plugin_id(&request.plugin_id) => x,
+                _ => return Err(CommandError::StreamNotFound),
+            };
+            if let Err(e) = stream.ack(request.received) {
+                streams.close(request.channel_id);
+                return Err(e.into());
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// How many events a frame of a reread carries at most
+const REREAD_FRAME_EVENTS: usize = 1000;
+
+/// This command looks at all active CMDR journals (taken from last updated) and sends their entire contents as frames on `on_frame` (see [super::journal_stream]).
+/// Only events of classes the plugin was granted (see [Permission::JournalEvents]) are sent.
+/// Returns once the facade acknowledged all frames
+#[tauri::command]
+pub(crate) async fn reread_active_journal<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    window: tauri::Window<R>,
+    payload: String,
+    iv: String,
+    on_frame: Channel<tauri::ipc::Response>,
+) -> Value {
+    contracts::handle(
+        RereadActiveJournal,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            app.state::<RateLimiter>().check(
+                &app,
+                caller.plugin.as_deref(),
+                CommandClass::RereadJournal,
+            )?;
+            let plugin_state = {
+                let state = app.state::<Arc<RwLock<PluginsState>>>();
+                let data = state.read().await;
+                match data.get_cloned(caller.plugin_id(&request.plugin_id)) {
+                    Some(x) => x,
+                    None => return Err(CommandError::PluginStateNotFound),
+                }
+            };
+            let granted = permissions::granted(&app, &plugin_state);
+            let is_granted = |class: &str| {
+                granted.iter().any(|x| {
+                    x.covers(&Permission::JournalEvents {
+                        class: class.into(),
+                    })
+                })
+            };
+
+            let state = app
+                .state::<Arc<RwLock<bimap::BiMap<String, PathBuf>>>>()
+                .inner()
+                .clone();
+
+            let items: Vec<(String, PathBuf)> = {
+                let data = state.read().await;
+                data.iter()
+                    .map(|(cmdr, path)| (cmdr.clone(), path.clone()))
+                    .collect()
+            };
+
+            let streams = app.state::<JournalStreams>();
+            let stream = streams.open(
+                window.label(),
+                &plugin_state.id,
+                caller.key,
+                on_frame,
+                false,
+            );
+            let is_granted = &is_granted;
+            let stream = &stream;
+            let futures = items.into_iter().map(|(cmdr, path)| async move {
+                let mut reader =
+                    match ed_journals::logs::asynchronous::RawLogFileReader::open(&path).await {
+                        Ok(x) => x,
+                        Err(e) => {
+                            error!(
+                                "failed to open journal to reread: {}, skipping. reason: {}",
+                                path.display(),
+                                e
+                            );
+                            return Ok(()); // skip this one
+                        }
+                    };
+
+                let mut entries = Vec::new();
+                while let Some(res) = reader.next().await {
+                    match res {
+                        Ok(ev) => {
+                            let ev = serde_json::to_value(&ev).unwrap();
+                            if ev
+                                .get("event")
+                                .and_then(Value::as_str)
+                                .is_some_and(is_granted)
+                            {
+                                entries.push(LogEventWithContext {
+                                    event: ev.to_string(),
+                                    file: path.clone(),
+                                    cmdr: cmdr.clone(),
+                                });
+                            }
+                            if entries.len() == REREAD_FRAME_EVENTS {
+                                stream.send(&entries).await?;
+                                entries.clear();
+                            }
+                        }
+                        Err(err) => {
+                            warn!(
+                                "failed to read entry in {}: {err}, skipping entry",
+                                path.display()
+                            );
+                        }
+                    }
+                }
+                match entries.is_empty() {
+                    true => Ok(()),
+                    false => stream.send(&entries).await,
+                }
+            });
+            // We run the fetching / sending of existing lines in parallel
+            let sent = futures::future::try_join_all(futures).await;
+            let flushed = match sent {
+                Ok(_) => stream.flush().await,
+                Err(e) => Err(e),
+            };
+            streams.close(stream.channel_id());
+            match flushed {
+                Ok(()) => Ok(()),
+                Err(e) => Err(e.into()),
+            }
+        },
+    )
+    .await
+}
+
+/// How many audit log entries [fetch_audit_log] returns at most
+const MAX_AUDIT_ENTRIES: usize = 1000;
+
+/// Returns the latest entries of the audit log, newest first. If `pluginId` is set, only entries the plugin performed or is concerned by are returned
+#[tauri::command]
+pub(crate) async fn fetch_audit_log<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        FetchAuditLog,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let limit = request.limit.unwrap_or(200).clamp(1, MAX_AUDIT_ENTRIES);
+            Ok(app
+                .state::<AuditLog>()
+                .query(request.plugin_id.as_deref(), limit))
+        },
+    )
+    .await
+}
+
+/// This command is invoked by the PluginManager when elements in the UI are moved around. This same command is used to just fetch the config
+#[tauri::command]
+pub(crate) async fn sync_main_layout<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        SyncMainLayout,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            match GenericPluginSettings::sync_ui_layout(&app, request.layout) {
+                Ok(x) => Ok(x),
+                Err(e) => {
+                    error!("failed to sync ui layout: {e}");
+                    Err(CommandError::InternalFetchStoreError)
+                }
+            }
+        },
+    )
+    .await
+}
 
-    match commands_armor::encrypt(&data.root_token, &resp) {
-        Ok(encrypted_with_iv) => encrypted_with_iv,
-        Err(e) => e.into(),
+/// Explicitly pins the publisher key a plugin is currently signed with. Used by the Settings window when a plugin shows up as [PluginVerification::SignedUnknown]
+#[tauri::command]
+pub(crate) async fn trust_plugin_publisher<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        TrustPluginPublisher,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let plugin_state = {
+                let state = app.state::<Arc<RwLock<PluginsState>>>();
+                let data = state.read().await;
+                match data.get_cloned(&request.plugin_id) {
+                    Some(x) => x,
+                    None => return Err(CommandError::PluginStateNotFound),
+                }
+            };
+            // Only a valid signature may be pinned. A tampered plugin must never become trusted through this
+            let public_key = match (
+                plugin_state.verification,
+                plugin_state.manifest.publisher_key(),
+            ) {
+                (
+                    PluginVerification::SignedUnknown | PluginVerification::SignedTrusted,
+                    Some(public_key),
+                ) => public_key.to_string(),
+                _ => return Err(CommandError::PluginNotValidlySigned),
+            };
+
+            let mut trust_store = match TrustStore::load(&app) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("failed to load trust store: {e}");
+                    return Err(CommandError::InternalFetchStoreError);
+                }
+            };
+            trust_store.pin(&request.plugin_id, &public_key, true);
+            if let Err(e) = trust_store.commit(&app) {
+                error!("failed to persist trust store: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Removes the pinned publisher key of a plugin. The next key seen is handled according to the trust-on-first-use setting
+#[tauri::command]
+pub(crate) async fn untrust_plugin_publisher<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        UntrustPluginPublisher,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let mut trust_store = match TrustStore::load(&app) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("failed to load trust store: {e}");
+                    return Err(CommandError::InternalFetchStoreError);
+                }
+            };
+            if !trust_store.unpin(&request.plugin_id) {
+                return Err(CommandError::NoPinnedKey);
+            }
+            if let Err(e) = trust_store.commit(&app) {
+                error!("failed to persist trust store: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Changes the Trust Store policy: the minimum verification level a plugin needs before it is started, and if keys are pinned on first use
+#[tauri::command]
+pub(crate) async fn set_trust_policy<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        SetTrustPolicy,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let mut trust_store = match TrustStore::load(&app) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("failed to load trust store: {e}");
+                    return Err(CommandError::InternalFetchStoreError);
+                }
+            };
+            if let Some(minimum_level) = request.minimum_level {
+                trust_store.minimum_level = minimum_level;
+            }
+            if let Some(trust_on_first_use) = request.trust_on_first_use {
+                trust_store.trust_on_first_use = trust_on_first_use;
+            }
+            if let Err(e) = trust_store.commit(&app) {
+                error!("failed to persist trust store: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(trust_store)
+        },
+    )
+    .await
+}
+
+/// Reads the deadlines for Starting / Disabling / Restarting plugins. If `timeouts` is set, they are replaced first
+#[tauri::command]
+pub(crate) async fn sync_transition_timeouts<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        SyncTransitionTimeouts,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            match TransitionTimeouts::sync(&app, request.timeouts) {
+                Ok(x) => Ok(x),
+                Err(e) => {
+                    error!("failed to sync transition timeouts: {e}");
+                    Err(CommandError::InternalFetchStoreError)
+                }
+            }
+        },
+    )
+    .await
+}
+
+/// Optionally replaces the command limits (see [super::rate_limits]), then returns the limits in effect.
+/// If `pluginId` is set, the limits of that plugin are synced instead of the global ones. `inherit` drops the plugin's own limits
+#[tauri::command]
+pub(crate) async fn sync_command_limits<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        SyncCommandLimits,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let resp = match request.plugin_id {
+                None => CommandLimits::sync(&app, request.limits),
+                Some(plugin_id) => CommandLimits::sync_for_plugin(
+                    &app,
+                    &plugin_id,
+                    request.limits,
+                    request.inherit,
+                ),
+            };
+            match resp {
+                Ok(x) => Ok(x),
+                Err(e) => {
+                    error!("failed to sync command limits: {e}");
+                    Err(CommandError::InternalFetchStoreError)
+                }
+            }
+        },
+    )
+    .await
+}
+
+/// Returns the linked dev plugins, mapping the plugin ID to the linked folder
+#[tauri::command]
+pub(crate) async fn fetch_dev_plugins<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        FetchDevPlugins,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move { fetch_dev_plugins_from(&app, request) },
+    )
+    .await
+}
+
+fn fetch_dev_plugins_from<R: Runtime>(
+    app: &AppHandle<R>,
+    _: Empty,
+) -> Result<DevPluginLinks, CommandError> {
+    match DevPluginLinks::load(app) {
+        Ok(x) => Ok(x),
+        Err(e) => {
+            error!("failed to load dev plugin links: {e}");
+            Err(CommandError::InternalFetchStoreError)
+        }
     }
 }
+
+/// Links a folder on disk as a dev plugin and enables it. If the plugin ID is already linked, the link is replaced
+#[tauri::command]
+pub(crate) async fn link_dev_plugin<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        LinkDevPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            if plugin_id_problem(&request.plugin_id).is_some() {
+                return Err(CommandError::InvalidPluginId);
+            }
+            if !request.plugin_dir.is_absolute() || !request.plugin_dir.is_dir() {
+                return Err(CommandError::DevPluginDirNotFound);
+            }
+
+            let mut links = match DevPluginLinks::load(&app) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("failed to load dev plugin links: {e}");
+                    return Err(CommandError::InternalFetchStoreError);
+                }
+            };
+            links
+                .0
+                .insert(request.plugin_id.clone(), request.plugin_dir.clone());
+            if let Err(e) = links.commit(&app) {
+                error!("failed to persist dev plugin links: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            app.state::<AuditLog>().record(
+                None,
+                Some(&request.plugin_id),
+                AuditAction::LinkDevPlugin {
+                    plugin_dir: request.plugin_dir,
+                },
+            );
+
+            // Linking a plugin is an explicit choice, so we skip the discovery popup and start it right away
+            let mut settings = match GenericPluginSettings::get_by_id(&app, &request.plugin_id) {
+                Ok(it) => it,
+                Err(err) => {
+                    error!("failed to get generic plugin settings by ID: {err}");
+                    return Err(CommandError::InternalMissingGeneralSettings);
+                }
+            }
+            .unwrap_or_default();
+            settings.enabled = true;
+            settings.already_known = true;
+            _ = settings.commit(&app, &request.plugin_id);
+
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Removes the link of a dev plugin. The plugin is stopped by the next reconcile
+#[tauri::command]
+pub(crate) async fn unlink_dev_plugin<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        UnlinkDevPlugin,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let mut links = match DevPluginLinks::load(&app) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("failed to load dev plugin links: {e}");
+                    return Err(CommandError::InternalFetchStoreError);
+                }
+            };
+            if links.0.remove(&request.plugin_id).is_none() {
+                return Err(CommandError::DevPluginNotLinked);
+            }
+            if let Err(e) = links.commit(&app) {
+                error!("failed to persist dev plugin links: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            app.state::<AuditLog>().record(
+                None,
+                Some(&request.plugin_id),
+                AuditAction::UnlinkDevPlugin,
+            );
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Changes the user plugin directory without a restart. The directory is created if it does not exist.
+///
+/// If `movePlugins` is set, the plugins in the current directory are moved over. This is best-effort, plugins that could not be moved are returned in `notMoved`.
+#[tauri::command]
+pub(crate) async fn set_plugin_dir<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        SetPluginDir,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            // We hold the write lock, so no reconcile runs while plugins are being moved
+            let _data = state.write().await;
+
+            if !request.plugin_dir.is_absolute() || request.plugin_dir.to_str().is_none() {
+                return Err(CommandError::InvalidPluginDir);
+            }
+            if request.plugin_dir.exists() && !request.plugin_dir.is_dir() {
+                return Err(CommandError::PluginDirNotADirectory);
+            }
+            let current_plugin_dir = match user_plugin_dir(&app) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("failed to get current plugin dir: {e}");
+                    return Err(CommandError::InternalFetchStoreError);
+                }
+            };
+            if request.move_plugins
+                && request.plugin_dir != current_plugin_dir
+                && (request.plugin_dir.starts_with(&current_plugin_dir)
+                    || current_plugin_dir.starts_with(&request.plugin_dir))
+            {
+                return Err(CommandError::PluginDirNested);
+            }
+            if let Err(e) = std::fs::create_dir_all(&request.plugin_dir) {
+                error!(
+                    "failed to create plugin dir {}: {e}",
+                    request.plugin_dir.display()
+                );
+                return Err(CommandError::PluginDirNotCreatable);
+            }
+
+            let not_moved = match request.move_plugins && request.plugin_dir != current_plugin_dir {
+                true => match move_user_plugins(&current_plugin_dir, &request.plugin_dir) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        error!("failed to move plugins: {e}");
+                        return Err(CommandError::MovePluginsFailed);
+                    }
+                },
+                false => vec![],
+            };
+
+            if let Err(e) = set_user_plugin_dir(&app, &request.plugin_dir) {
+                error!("failed to persist plugin dir: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(SetPluginDirResponse {
+                plugin_dir: request.plugin_dir,
+                not_moved,
+            })
+        },
+    )
+    .await
+}
+
+/// Returns all plugin roots, ordered from highest to lowest precedence. See [super::plugin_roots]
+#[tauri::command]
+pub(crate) async fn fetch_plugin_roots<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        FetchPluginRoots,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move { fetch_plugin_roots_from(&app, request) },
+    )
+    .await
+}
+
+fn fetch_plugin_roots_from<R: Runtime>(
+    app: &AppHandle<R>,
+    _: Empty,
+) -> Result<Vec<PluginRoot>, CommandError> {
+    match search_roots(app) {
+        Ok(x) => Ok(x),
+        Err(e) => {
+            error!("failed to get plugin roots: {e}");
+            Err(CommandError::InternalFetchStoreError)
+        }
+    }
+}
+
+/// Replaces the shared and system plugin roots. Each list is ordered from highest to lowest precedence
+#[tauri::command]
+pub(crate) async fn set_plugin_roots<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        SetPluginRoots,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            if request
+                .shared
+                .iter()
+                .chain(request.system.iter())
+                .any(|x| !x.is_absolute())
+            {
+                return Err(CommandError::InvalidPluginDir);
+            }
+
+            let config = PluginRootsConfig {
+                shared: request.shared,
+                system: request.system,
+            };
+            if let Err(e) = config.commit(&app) {
+                error!("failed to persist plugin roots: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Sends a message from a plugin's frontend to its sidecar and WASM backend. See [super::sidecar] and [super::wasm_backend]
+#[tauri::command]
+pub(crate) async fn send_sidecar_message<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        SendSidecarMessage,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let plugin_state = {
+                let state = app.state::<Arc<RwLock<PluginsState>>>();
+                let data = state.read().await;
+                match data.get_cloned(caller.plugin_id(&request.plugin_id)) {
+                    Some(x) => x,
+                    None => return Err(CommandError::PluginStateNotFound),
+                }
+            };
+            app.state::<RateLimiter>().check(
+                &app,
+                caller.plugin.as_deref(),
+                CommandClass::BackendMessage,
+            )?;
+            // Each process the message is passed to needs its own grant
+            let backend = plugin_state.manifest.backend().is_some();
+            if backend {
+                permissions::check(
+                    &app,
+                    &plugin_state,
+                    &BackendCapability::FrontendMessages.permission(),
+                )?;
+            }
+            if !backend || plugin_state.manifest.sidecar().is_some() {
+                permissions::check(&app, &plugin_state, &Permission::Sidecar)?;
+            }
+            let sent_to_sidecar = match app.try_state::<SidecarSupervisor>() {
+                Some(x) => x.notify(&plugin_state.id, "message", &request.payload),
+                None => Err(CommandError::SidecarNotRunning),
+            };
+            let sent_to_backend = app
+                .try_state::<WasmBackends>()
+                .is_some_and(|x| x.send_message(&plugin_state.id, &request.payload));
+            match sent_to_sidecar {
+                Err(CommandError::SidecarNotRunning) if sent_to_backend => Ok(()),
+                x => x,
+            }
+        },
+    )
+    .await
+}
+
+/// Publishes a message on one of the plugin's own topics. See [super::message_bus]
+#[tauri::command]
+pub(crate) async fn publish_bus_message<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        PublishBusMessage,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            let plugin_state = match data.get_cloned(caller.plugin_id(&request.plugin_id)) {
+                Some(x) => x,
+                None => return Err(CommandError::PluginStateNotFound),
+            };
+            app.state::<RateLimiter>().check(
+                &app,
+                caller.plugin.as_deref(),
+                CommandClass::BusPublish,
+            )?;
+            let topic = resolve_topic(
+                &plugin_state.id,
+                plugin_state.manifest.message_bus(),
+                &request.topic,
+            )?;
+            let needed = Permission::BusPublish {
+                topic: request.topic.clone(),
+            };
+            permissions::check(&app, &plugin_state, &needed)?;
+
+            let message = BusMessage {
+                topic,
+                publisher: plugin_state.id,
+                payload: request.payload,
+                published_at: Utc::now(),
+            };
+            if let Err(e) = app
+                .state::<MessageBus>()
+                .publish(&app, &data, message, request.retain)
+            {
+                error!("failed to publish bus message: {e}");
+                return Err(CommandError::InternalAesError);
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Returns the retained messages of all topics the plugin subscribes to, so it can catch up after starting
+#[tauri::command]
+pub(crate) async fn fetch_retained_bus_messages<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        FetchRetainedBusMessages,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            fetch_retained_bus_messages_from(&app, &caller, &data, request)
+        },
+    )
+    .await
+}
+
+fn fetch_retained_bus_messages_from<R: Runtime>(
+    app: &AppHandle<R>,
+    caller: &Caller,
+    data: &PluginsState,
+    request: PluginIdRequest,
+) -> Result<Vec<BusMessage>, CommandError> {
+    let Some(plugin_state) = data.get_cloned(caller.plugin_id(&request.plugin_id)) else {
+        return Err(CommandError::PluginStateNotFound);
+    };
+    Ok(match permissions::granted_message_bus(app, &plugin_state) {
+        Some(config) => app.state::<MessageBus>().retained_for(&config),
+        None => vec![],
+    })
+}
+
+/// Returns the permissions the plugin requests in its manifest and the ones the user granted. See [super::permissions]
+#[tauri::command]
+pub(crate) async fn fetch_plugin_permissions<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        FetchPluginPermissions,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            fetch_plugin_permissions_from(&app, &data, request)
+        },
+    )
+    .await
+}
+
+fn fetch_plugin_permissions_from<R: Runtime>(
+    app: &AppHandle<R>,
+    data: &PluginsState,
+    request: PluginIdRequest,
+) -> Result<PluginPermissionsResponse, CommandError> {
+    let Some(plugin_state) = data.get_cloned(&request.plugin_id) else {
+        return Err(CommandError::PluginStateNotFound);
+    };
+
+    let url_decisions = match GenericPluginSettings::get_by_id(app, &request.plugin_id) {
+        Ok(x) => x.map(|x| x.url_decisions).unwrap_or_default(),
+        Err(e) => {
+            error!("failed to get generic plugin settings by ID: {e}");
+            return Err(CommandError::InternalMissingGeneralSettings);
+        }
+    };
+
+    Ok(PluginPermissionsResponse {
+        requested: permissions::requested(&plugin_state.manifest),
+        granted: permissions::granted(app, &plugin_state),
+        url_decisions,
+    })
+}
+
+/// Replaces the permissions granted to the plugin. Only permissions the plugin requests can be granted. Also marks the plugin as known
+#[tauri::command]
+pub(crate) async fn grant_plugin_permissions<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        GrantPluginPermissions,
+        &app,
+        &iv,
+        &payload,
+        |app, _, request| async move {
+            let plugin_state = {
+                let state = app.state::<Arc<RwLock<PluginsState>>>();
+                let data = state.read().await;
+                match data.get_cloned(&request.plugin_id) {
+                    Some(x) => x,
+                    None => return Err(CommandError::PluginStateNotFound),
+                }
+            };
+
+            let requested = permissions::requested(&plugin_state.manifest);
+            let mut settings = match GenericPluginSettings::get_by_id(&app, &request.plugin_id) {
+                Ok(x) => x.unwrap_or_default(),
+                Err(e) => {
+                    error!("failed to get generic plugin settings by ID: {e}");
+                    return Err(CommandError::InternalMissingGeneralSettings);
+                }
+            };
+            settings.granted_permissions = request
+                .permissions
+                .into_iter()
+                .filter(|x| requested.contains(x))
+                .collect();
+            settings.already_known = true;
+            if let Err(e) = settings.commit(&app, &request.plugin_id) {
+                error!("failed to persist plugin permissions: {e}");
+                return Err(CommandError::InternalFetchStoreError);
+            }
+            app.state::<AuditLog>().record(
+                None,
+                Some(&request.plugin_id),
+                AuditAction::GrantPermissions {
+                    granted: settings.granted_permissions,
+                },
+            );
+            // e.g. a sidecar might have to be started or stopped now
+            if let Some(trigger) = app.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Returns the plugins whose requested permissions were not shown to the user yet. Answering with `grant_plugin_permissions` removes a plugin from the list
+#[tauri::command]
+pub(crate) async fn fetch_permission_requests<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        FetchPermissionRequests,
+        &app,
+        &iv,
+        &payload,
+        |app, _, _| async move {
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            Ok(data.permission_requests(&app))
+        },
+    )
+    .await
+}
+
+/// Runs several read-only commands under one read lock of [PluginsState], so all of them see the same state.
+/// Each operation is checked and answered as if it was invoked on its own, a failing operation does not affect the others
+#[tauri::command]
+pub(crate) async fn run_batch<R: Runtime>(
+    app: tauri::AppHandle<R>,
+    payload: String,
+    iv: String,
+) -> Value {
+    contracts::handle(
+        RunBatch,
+        &app,
+        &iv,
+        &payload,
+        |app, caller, request| async move {
+            if request.operations.len() > MAX_BATCH_OPERATIONS {
+                return Err(CommandError::BatchTooLarge);
+            }
+            let state = app.state::<Arc<RwLock<PluginsState>>>();
+            let data = state.read().await;
+            let outcomes = request
+                .operations
+                .into_iter()
+                .map(|operation| {
+                    // on its own, a root-only command would not have decrypted with a plugin's key
+                    if operation.access() == Access::Root && caller.plugin.is_some() {
+                        return BatchOutcome::Error(CommandError::IncorrectAesKey);
+                    }
+                    let (app, caller, data) = (&app, &caller, &*data);
+                    match operation {
+                        BatchOperation::FetchAllPlugins(x) => {
+                            fetch_all_plugins_from(data, x).into()
+                        }
+                        BatchOperation::FetchBrokenPlugins(x) => {
+                            fetch_broken_plugins_from(data, x).into()
+                        }
+                        BatchOperation::GetPlugin(x) => get_plugin_from(data, x).into(),
+                        BatchOperation::GetImportPathForPlugin(x) => {
+                            get_import_path_for_plugin_from(app, data, x).into()
+                        }
+                        BatchOperation::ReadSetting(x) => {
+                            read_setting_from(app, caller, data, x).into()
+                        }
+                        BatchOperation::FetchRetainedBusMessages(x) => {
+                            fetch_retained_bus_messages_from(app, caller, data, x).into()
+                        }
+                        BatchOperation::FetchPluginPermissions(x) => {
+                            fetch_plugin_permissions_from(app, data, x).into()
+                        }
+                        BatchOperation::FetchDevPlugins(x) => fetch_dev_plugins_from(app, x).into(),
+                        BatchOperation::FetchPluginRoots(x) => {
+                            fetch_plugin_roots_from(app, x).into()
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+            Ok(outcomes)
+        },
+    )
+    .await
+}
@@ -1,18 +1,47 @@
 //! This module contains utils to encrypt and decrypt Payloads to be sent via Commands
+//!
+//! Each window has its own root token (see [RootTokens]). It is rotated on every page load of the window, so a token never outlives the page it was handed to.
+//! Besides the root token, each plugin has its own key, derived from the window's root token via HKDF (see [plugin_key]).
+//! The window derives the keys when it creates a plugin's facade. Commands a plugin may invoke accept both keys (see [RootTokens::decrypt_as_caller]):
+//! if a plugin key decrypts the payload, the command acts on behalf of that plugin, no matter what plugin ID the payload claims.
+//!
+//! Events are armored the same way: events for the core frontend with the root tokens (see [RootTokens::emit]), events for a plugin with its key (see [RootTokens::emit_to_plugin]).
+//! They are only sent to the windows that fetched a token, never app-wide.
+//!
+//! The frontend wraps each payload in an [Envelope] with a timestamp and a counter. Stale payloads and reused counters are rejected (see [ReplayGuard]),
+//! so a captured payload cannot be replayed.
 
-use std::fmt::Display;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
 
 use aes_gcm::{aead::Aead, Aes128Gcm, KeyInit, Nonce};
 use base64::prelude::*;
+use chrono::{DateTime, TimeDelta, Utc};
+use hkdf::Hkdf;
 use rand::RngCore;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
-use tracing::error;
+use sha2::Sha256;
+use tauri::{AppHandle, Emitter, Runtime};
+use tracing::{error, warn};
 
+use super::contracts::CommandError;
+
+/// Payloads older (or further in the future) than this are rejected
+const MAX_PAYLOAD_AGE: TimeDelta = TimeDelta::seconds(30);
+/// How far a counter may lag behind the highest counter seen. Commands run concurrently, so payloads may arrive slightly out of order
+const REPLAY_WINDOW: u64 = 1024;
+
+#[derive(Debug)]
 pub(crate) enum DearmorError {
     IncorrectAesKey,
     FailedParsePayloadStructure,
     InternalAesError,
+    StalePayload,
+    ReplayedPayload,
 }
 
 impl Display for DearmorError {
@@ -24,29 +53,53 @@ impl Display for DearmorError {
                 DearmorError::IncorrectAesKey => "INCORRECT_AES_KEY",
                 DearmorError::FailedParsePayloadStructure => "FAILED_PARSE_PAYLOAD_STRUCTURE",
                 DearmorError::InternalAesError => "INTERNAL_AES_ERROR",
+                DearmorError::StalePayload => "STALE_PAYLOAD",
+                DearmorError::ReplayedPayload => "REPLAYED_PAYLOAD",
             })
         )
     }
 }
 
-pub(crate) fn decrypt_str<T>(key: &[u8; 16], iv: &str, payload: &str) -> Result<T, DearmorError>
-where
-    T: DeserializeOwned,
-{
-    let iv = BASE64_STANDARD_NO_PAD
-        .decode(iv)
-        .map_err(|_| DearmorError::FailedParsePayloadStructure)?;
-    let payload = BASE64_STANDARD_NO_PAD
-        .decode(payload)
-        .map_err(|_| DearmorError::FailedParsePayloadStructure)?;
-
-    decrypt(key, &iv, &payload)
+/// What the frontend encrypts: the command's payload plus what is needed to detect replays. See `encryptPayload` in `commandUtils.ts`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope<T> {
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    issued_at: DateTime<Utc>,
+    /// Increases with every payload the window encrypts, no matter which key is used
+    counter: u64,
+    body: T,
 }
 
-pub(crate) fn decrypt<T>(key: &[u8; 16], iv: &[u8], payload: &[u8]) -> Result<T, DearmorError>
-where
-    T: DeserializeOwned,
-{
+/// Remembers the counters of the recently accepted payloads of a window
+#[derive(Default)]
+struct ReplayGuard {
+    highest: u64,
+    seen: BTreeSet<u64>,
+}
+
+impl ReplayGuard {
+    fn accept(&mut self, issued_at: DateTime<Utc>, counter: u64) -> Result<(), DearmorError> {
+        if (Utc::now() - issued_at).abs() > MAX_PAYLOAD_AGE
+            || counter.saturating_add(REPLAY_WINDOW) <= self.highest
+        {
+            return Err(DearmorError::StalePayload);
+        }
+        if !self.seen.insert(counter) {
+            return Err(DearmorError::ReplayedPayload);
+        }
+        if counter > self.highest {
+            self.highest = counter;
+            // everything below the window is rejected as stale anyways
+            self.seen = self
+                .seen
+                .split_off(&self.highest.saturating_sub(REPLAY_WINDOW));
+        }
+        Ok(())
+    }
+}
+
+fn decrypt_bytes(key: &[u8; 16], iv: &[u8], payload: &[u8]) -> Result<Vec<u8>, DearmorError> {
     let cipher = match Aes128Gcm::new_from_slice(key) {
         Ok(x) => x,
         Err(e) => {
@@ -54,17 +107,216 @@ where
             return Err(DearmorError::InternalAesError);
         }
     };
+    if iv.len() != 12 {
+        return Err(DearmorError::FailedParsePayloadStructure);
+    }
     let nonce = Nonce::from_slice(iv);
-    let decrypted_bytes = cipher
+    cipher
         .decrypt(nonce, payload)
-        .map_err(|_| DearmorError::IncorrectAesKey)?;
-    serde_json::from_slice(&decrypted_bytes).map_err(|_| DearmorError::FailedParsePayloadStructure)
+        .map_err(|_| DearmorError::IncorrectAesKey)
+}
+
+/// Derives the key of a plugin from a root token. The frontend derives the same key with WebCrypto (see `CommandWrapper.forPlugin`)
+pub(crate) fn plugin_key(root_token: &[u8; 16], plugin_id: &str) -> [u8; 16] {
+    let mut key = [0u8; 16];
+    Hkdf::<Sha256>::new(None, root_token)
+        .expand(format!("edpf/plugin/{plugin_id}").as_bytes(), &mut key)
+        .expect("16 bytes is a valid HKDF output length");
+    key
+}
+
+/// Who invoked an armored command, determined by the key that decrypted its payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Caller {
+    /// The plugin whose key was used, `None` for a window's root token
+    pub(crate) plugin: Option<String>,
+    /// The key that decrypted the payload. Responses are encrypted with it
+    pub(crate) key: [u8; 16],
+}
+
+impl Caller {
+    /// The plugin the command acts on behalf of. Only a root token may act on behalf of the `claimed` plugin
+    pub(crate) fn plugin_id<'a>(&'a self, claimed: &'a str) -> &'a str {
+        self.plugin.as_deref().unwrap_or(claimed)
+    }
+}
+
+/// The root token of a window. See [RootTokens]
+#[derive(Clone)]
+struct RootToken {
+    key: [u8; 16],
+    /// Set on page load, cleared once the window fetched the key via [super::get_root_token_once]
+    requestable: bool,
+    replay_guard: Arc<Mutex<ReplayGuard>>,
+}
+
+/// The root tokens of all windows, keyed by window label
+#[derive(Default, Clone)]
+pub(crate) struct RootTokens(HashMap<String, RootToken>);
+
+impl RootTokens {
+    /// Called when a page loads in `window`. Replaces its root token and allows the page to fetch the new one once
+    pub(crate) fn rearm(&mut self, window: &str) {
+        let mut key = [0u8; 16];
+        rand::rng().fill_bytes(&mut key);
+        self.0.insert(
+            window.to_string(),
+            RootToken {
+                key,
+                requestable: true,
+                replay_guard: Arc::new(Mutex::new(ReplayGuard::default())),
+            },
+        );
+    }
+
+    /// Forgets the root token of a closed window
+    pub(crate) fn remove(&mut self, window: &str) {
+        self.0.remove(window);
+    }
+
+    /// Hands out the root token of `window`. This only succeeds once per page load
+    pub(crate) fn take(&mut self, window: &str) -> Option<[u8; 16]> {
+        let token = self.0.get_mut(window)?;
+        match std::mem::replace(&mut token.requestable, false) {
+            true => Some(token.key),
+            false => None,
+        }
+    }
+
+    /// Decrypts a payload encrypted with the root token of any window
+    pub(crate) fn decrypt<T>(&self, iv: &str, payload: &str) -> Result<(Caller, T), DearmorError>
+    where
+        T: DeserializeOwned,
+    {
+        self.decrypt_as_caller(std::iter::empty(), iv, payload)
+    }
+
+    /// Like [RootTokens::decrypt], but also accepts the keys of `plugin_ids`. Returns who encrypted the payload
+    pub(crate) fn decrypt_as_caller<'a, T>(
+        &self,
+        plugin_ids: impl IntoIterator<Item = &'a str> + Clone,
+        iv: &str,
+        payload: &str,
+    ) -> Result<(Caller, T), DearmorError>
+    where
+        T: DeserializeOwned,
+    {
+        let iv = BASE64_STANDARD_NO_PAD
+            .decode(iv)
+            .map_err(|_| DearmorError::FailedParsePayloadStructure)?;
+        let payload = BASE64_STANDARD_NO_PAD
+            .decode(payload)
+            .map_err(|_| DearmorError::FailedParsePayloadStructure)?;
+
+        for token in self.0.values() {
+            let candidates = std::iter::once((None, token.key)).chain(
+                plugin_ids
+                    .clone()
+                    .into_iter()
+                    .map(|x| (Some(x), plugin_key(&token.key, x))),
+            );
+            for (plugin, key) in candidates {
+                let decrypted_bytes = match decrypt_bytes(&key, &iv, &payload) {
+                    Ok(x) => x,
+                    Err(DearmorError::IncorrectAesKey) => continue,
+                    Err(e) => return Err(e),
+                };
+                let envelope: Envelope<T> = serde_json::from_slice(&decrypted_bytes)
+                    .map_err(|_| DearmorError::FailedParsePayloadStructure)?;
+                token
+                    .replay_guard
+                    .lock()
+                    .unwrap()
+                    .accept(envelope.issued_at, envelope.counter)?;
+                let caller = Caller {
+                    plugin: plugin.map(str::to_string),
+                    key,
+                };
+                return Ok((caller, envelope.body));
+            }
+        }
+        Err(DearmorError::IncorrectAesKey)
+    }
+
+    /// Emits `payload` to each window that fetched its root token, armored with that token
+    pub(crate) fn emit<R: Runtime, T: Serialize>(
+        &self,
+        app_handle: &AppHandle<R>,
+        event: &str,
+        payload: &T,
+    ) -> Result<(), DearmorError> {
+        for (window, token) in self.0.iter().filter(|(_, x)| !x.requestable) {
+            let encrypted = encrypt(&token.key, payload)?;
+            if let Err(e) = app_handle.emit_to(window.as_str(), event, &encrypted) {
+                warn!("failed to emit {event} to {window}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits `payload` to a single window, armored with its root token. Does nothing if the window has not fetched its root token yet
+    pub(crate) fn emit_to_window<R: Runtime, T: Serialize>(
+        &self,
+        app_handle: &AppHandle<R>,
+        window: &str,
+        event: &str,
+        payload: &T,
+    ) -> Result<(), DearmorError> {
+        let token = match self.0.get(window) {
+            Some(x) if !x.requestable => x,
+            _ => return Ok(()),
+        };
+        let encrypted = encrypt(&token.key, payload)?;
+        if let Err(e) = app_handle.emit_to(window, event, &encrypted) {
+            warn!("failed to emit {event} to {window}: {e}");
+        }
+        Ok(())
+    }
+
+    /// Emits `payload` to the facade of `plugin_id` in each of `windows`, armored with the plugin's key.
+    /// The plugin ID is added in plaintext, so the facades of other plugins can skip the event without trying to decrypt it
+    pub(crate) fn emit_to_plugin<R: Runtime, T: Serialize>(
+        &self,
+        app_handle: &AppHandle<R>,
+        windows: &[&str],
+        plugin_id: &str,
+        event: &str,
+        payload: &T,
+    ) -> Result<(), DearmorError> {
+        for window in windows {
+            let token = match self.0.get(*window) {
+                Some(x) if !x.requestable => x,
+                _ => continue,
+            };
+            let mut encrypted = encrypt(&plugin_key(&token.key, plugin_id), payload)?;
+            encrypted["pluginId"] = json!(plugin_id);
+            if let Err(e) = app_handle.emit_to(*window, event, &encrypted) {
+                warn!("failed to emit {event} to {plugin_id} in {window}: {e}");
+            }
+        }
+        Ok(())
+    }
 }
 
 pub(crate) fn encrypt<T>(key: &[u8; 16], payload: &T) -> Result<serde_json::Value, DearmorError>
 where
     T: Serialize,
 {
+    let payload =
+        serde_json::to_vec(payload).map_err(|_| DearmorError::FailedParsePayloadStructure)?;
+    let (iv, encrypted_bytes) = encrypt_bytes(key, &payload)?;
+    Ok(json!({
+        "iv": BASE64_STANDARD_NO_PAD.encode(iv),
+        "payload": BASE64_STANDARD_NO_PAD.encode(&encrypted_bytes),
+        "success": true
+    }))
+}
+
+/// Encrypts `payload` with a random IV. Returns the IV and the ciphertext
+pub(crate) fn encrypt_bytes(
+    key: &[u8; 16],
+    payload: &[u8],
+) -> Result<([u8; 12], Vec<u8>), DearmorError> {
     let cipher = match Aes128Gcm::new_from_slice(key) {
         Ok(x) => x,
         Err(e) => {
@@ -79,22 +331,80 @@ where
     let mut iv = [0u8; 12];
     rand::rng().fill_bytes(&mut iv);
 
-    let payload =
-        serde_json::to_vec(payload).map_err(|_| DearmorError::FailedParsePayloadStructure)?;
-    let payload_ref: &[u8] = &payload;
     let nonce = Nonce::from_slice(&iv);
     let encrypted_bytes = cipher
-        .encrypt(nonce, payload_ref)
+        .encrypt(nonce, payload)
         .map_err(|_| DearmorError::IncorrectAesKey)?;
-    Ok(json!({
-        "iv": BASE64_STANDARD_NO_PAD.encode(nonce),
-        "payload": BASE64_STANDARD_NO_PAD.encode(&encrypted_bytes),
-        "success": true
-    }))
+    Ok((iv, encrypted_bytes))
 }
 
-impl From<DearmorError> for serde_json::Value {
+impl From<DearmorError> for CommandError {
     fn from(value: DearmorError) -> Self {
-        json!({"success": false, "reason": value.to_string()})
+        match value {
+            DearmorError::IncorrectAesKey => CommandError::IncorrectAesKey,
+            DearmorError::FailedParsePayloadStructure => CommandError::FailedParsePayloadStructure,
+            DearmorError::InternalAesError => CommandError::InternalAesError,
+            DearmorError::StalePayload => CommandError::StalePayload,
+            DearmorError::ReplayedPayload => CommandError::ReplayedPayload,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_guard_rejects_duplicates() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(Utc::now(), 1).is_ok());
+        assert!(matches!(
+            guard.accept(Utc::now(), 1),
+            Err(DearmorError::ReplayedPayload)
+        ));
+    }
+
+    #[test]
+    fn replay_guard_rejects_counters_older_than_the_window() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(Utc::now(), 2000).is_ok());
+        assert!(matches!(
+            guard.accept(Utc::now(), 2000 - REPLAY_WINDOW),
+            Err(DearmorError::StalePayload)
+        ));
+        assert!(matches!(
+            guard.accept(Utc::now(), 0),
+            Err(DearmorError::StalePayload)
+        ));
+    }
+
+    #[test]
+    fn replay_guard_rejects_stale_issued_at() {
+        let mut guard = ReplayGuard::default();
+        let too_old = Utc::now() - MAX_PAYLOAD_AGE - TimeDelta::seconds(1);
+        assert!(matches!(
+            guard.accept(too_old, 1),
+            Err(DearmorError::StalePayload)
+        ));
+        let too_new = Utc::now() + MAX_PAYLOAD_AGE + TimeDelta::seconds(1);
+        assert!(matches!(
+            guard.accept(too_new, 2),
+            Err(DearmorError::StalePayload)
+        ));
+        // a rejected payload does not burn its counter
+        assert!(guard.accept(Utc::now(), 1).is_ok());
+    }
+
+    #[test]
+    fn replay_guard_accepts_out_of_order_counters_inside_the_window() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.accept(Utc::now(), 2000).is_ok());
+        assert!(guard.accept(Utc::now(), 1999).is_ok());
+        assert!(guard.accept(Utc::now(), 2001 - REPLAY_WINDOW).is_ok());
+        assert!(guard.accept(Utc::now(), 2002).is_ok());
+        assert!(matches!(
+            guard.accept(Utc::now(), 1999),
+            Err(DearmorError::ReplayedPayload)
+        ));
     }
 }
@@ -0,0 +1,759 @@
+//! The typed contract between the backend and the frontend: what each command takes and returns, and what each event carries.
+//!
+//! Every armored command in [super::commands] is described by a marker type implementing [Command] (see the [commands!] list below).
+//! The command itself only deals with typed values: [handle] decrypts the request, runs the command and encrypts the response.
+//! Failures are a [CommandError], which is sent as `{"success": false, "reason": "SOME_REASON", ...}`.
+//!
+//! Events are described the same way, see [Event]. The JSON Schema and TypeScript definitions of all commands and events are generated
+//! from this module by [super::bindings], so the frontend doesn't have to mirror them by hand.
+
+use std::{
+    any::TypeId, collections::BTreeMap, collections::HashMap, future::Future, path::PathBuf,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Runtime};
+use tokio::sync::RwLock;
+use tracing::debug;
+
+use crate::{event_watchdog::LogEventWithContext, updates::ReleaseChannel};
+
+use super::{
+    audit_log::AuditEntry,
+    commands_armor::{self, Caller},
+    dev_plugins::DevPluginLinks,
+    generic_plugin_settings::{CommandLimits, PluginsUiConfig, TransitionTimeouts},
+    journal_stream::FrameBody,
+    message_bus::BusMessage,
+    package_signature::{PluginVerification, TrustStore},
+    permissions::Permission,
+    plugin_files::PluginFile,
+    plugin_roots::PluginRoot,
+    url_policy::{UrlDecision, UrlPrompt},
+    BrokenPlugin, PluginCurrentState, PluginState, PluginsState,
+};
+
+/// Why a command failed. Serialized as the `reason` of the error response, next to `"success": false` and the variant's fields
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "reason", rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum CommandError {
+    /// The payload was not encrypted with a key the command accepts
+    IncorrectAesKey,
+    FailedParsePayloadStructure,
+    InternalAesError,
+    /// The payload was issued too long ago, see [commands_armor::ReplayGuard]
+    StalePayload,
+    ReplayedPayload,
+    /// `get_root_token_once` was called by a window that must not have a root token
+    CalledFromInvalidWindow,
+    TokenAlreadyRequested,
+    InternalFailedConversion,
+    InternalFetchStoreError,
+    InternalMissingGeneralSettings,
+    PluginStateNotFound,
+    PluginNotFound,
+    /// Embedded plugins have no folder that could be opened
+    PluginNotUserprovided,
+    InternalOpenPluginDirError,
+    MissingOrBadToken,
+    /// The plugin misses a permission, see [super::permissions]
+    PermissionDenied {
+        permission: Permission,
+    },
+    /// The plugin called a command too often, see [super::rate_limits]
+    RateLimited {
+        meta: RateLimitedMeta,
+    },
+    /// The plugin's settings store or storage folder is full, see [super::rate_limits]
+    QuotaExceeded,
+    InvalidUrl,
+    UrlSchemeNotAllowed,
+    UrlDenied,
+    /// The plugin already waits for the user to answer a prompt, see [super::url_policy]
+    UrlPromptPending,
+    UrlPromptTimeout,
+    PromptNotFound,
+    /// The URL was allowed, but the browser could not be opened
+    OpenUrlFailed {
+        message: String,
+    },
+    /// Opening or focusing a window failed
+    WindowFailed {
+        message: String,
+    },
+    /// Starting or stopping a plugin failed
+    TransitionFailed {
+        message: String,
+    },
+    KeyInvalidFormat,
+    KeyNotEnoughSegments,
+    KeyEmptySegment,
+    SecretNotWritableMissingPermission,
+    SecretNotReadableMissingPermission,
+    FailedWrite,
+    FailedRead,
+    /// The path is not valid for the storage folder of a plugin, see [super::plugin_files::parse_path]
+    FilePathInvalid,
+    FileNotFound,
+    UpdateCheckFailed {
+        message: String,
+    },
+    NoPendingUpdate,
+    UpdateFailed {
+        meta: String,
+    },
+    PluginNotValidlySigned,
+    NoPinnedKey,
+    InvalidPluginId,
+    DevPluginDirNotFound,
+    DevPluginNotLinked,
+    InvalidPluginDir,
+    PluginDirNotADirectory,
+    PluginDirNested,
+    PluginDirNotCreatable,
+    MovePluginsFailed,
+    SidecarNotRunning,
+    /// The sidecar does not read its stdin fast enough, see [super::sidecar]
+    SidecarBusy,
+    TopicInvalid,
+    TopicNotDeclared,
+    StreamNotFound,
+    /// The webview no longer accepts frames on the channel, see [super::journal_stream]
+    StreamClosed,
+    /// The facade did not acknowledge frames in time, see [super::journal_stream]
+    StreamStalled,
+    /// `run_batch` got more than [MAX_BATCH_OPERATIONS] operations
+    BatchTooLarge,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RateLimitedMeta {
+    /// When the next call would be accepted. `None` if the limit has no refill
+    pub(crate) retry_after_ms: Option<u64>,
+}
+
+impl From<CommandError> for Value {
+    fn from(value: CommandError) -> Self {
+        let mut response = serde_json::to_value(&value)
+            .unwrap_or_else(|_| json!({"reason": "INTERNAL_FAILED_CONVERSION"}));
+        response["success"] = json!(false);
+        response
+    }
+}
+
+/// Which keys a command accepts, see [commands_armor]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    /// Only the root token of a window. These commands are reserved to the core frontend
+    Root,
+    /// The root token or the key of a plugin. The command acts on behalf of the plugin whose key was used, see [Caller::plugin_id]
+    Caller,
+    /// Not armored at all. Only used to hand out the root token
+    Unarmored,
+}
+
+/// A command the frontend can invoke. Implemented by the marker types of [commands!]
+pub(crate) trait Command {
+    /// The name the command is registered with in `generate_handler!`
+    const NAME: &'static str;
+    const ACCESS: Access;
+    type Request: DeserializeOwned + JsonSchema;
+    /// `()` if the command only reports success, in which case the response is not encrypted
+    type Response: Serialize + JsonSchema + 'static;
+}
+
+/// Runs an armored command: decrypts the request according to [Command::ACCESS], hands it to `handler` and encrypts its response with the key of the caller.
+///
+/// The lock on [PluginsState] is only held while decrypting. Handlers lock it again if they need it, with a write lock if they change it
+pub(crate) async fn handle<C, R, F, Fut>(
+    _: C,
+    app: &AppHandle<R>,
+    iv: &str,
+    payload: &str,
+    handler: F,
+) -> Value
+where
+    C: Command,
+    R: Runtime,
+    F: FnOnce(AppHandle<R>, Caller, C::Request) -> Fut,
+    Fut: Future<Output = Result<C::Response, CommandError>>,
+{
+    let decrypted = {
+        let state = app.state::<Arc<RwLock<PluginsState>>>();
+        let data = state.read().await;
+        match C::ACCESS {
+            Access::Root => data.decrypt::<C::Request>(iv, payload),
+            Access::Caller => data.decrypt_as_caller::<C::Request>(iv, payload),
+            Access::Unarmored => unreachable!("{} is not armored", C::NAME),
+        }
+    };
+    let (caller, request) = match decrypted {
+        Ok(x) => x,
+        Err(e) => return CommandError::from(e).into(),
+    };
+    let key = caller.key;
+
+    let response = match handler(app.clone(), caller, request).await {
+        Ok(x) => x,
+        Err(e) => {
+            debug!("{} failed: {e:?}", C::NAME);
+            return e.into();
+        }
+    };
+    // Commands without a response body answer with a plain success, see `EncryptedCommandEmptyResponse` in the frontend
+    if TypeId::of::<C::Response>() == TypeId::of::<()>() {
+        return json!({"success": true});
+    }
+    match commands_armor::encrypt(&key, &response) {
+        Ok(encrypted_with_iv) => encrypted_with_iv,
+        Err(e) => CommandError::from(e).into(),
+    }
+}
+
+/// Answers a command with [Access::Unarmored]. The response is sent in plain text
+pub(crate) fn unarmored<C: Command>(_: C, response: Result<C::Response, CommandError>) -> Value {
+    debug_assert!(matches!(C::ACCESS, Access::Unarmored));
+    match response {
+        Ok(x) => json!({"success": true, "data": x}),
+        Err(e) => e.into(),
+    }
+}
+
+/// Who an event is armored for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EventTarget {
+    /// The core frontend, armored with the root token of each window
+    Root,
+    /// The facade of a single plugin, armored with the plugin's key. See [commands_armor::RootTokens::emit_to_plugin]
+    Plugin,
+}
+
+/// An event the backend emits. Implemented by the marker types of [events!]
+pub(crate) trait Event {
+    const NAME: &'static str;
+    const TARGET: EventTarget;
+    type Payload: Serialize + JsonSchema;
+}
+
+/// A command as described by [super::bindings]
+pub(crate) struct CommandContract {
+    pub(crate) name: &'static str,
+    pub(crate) docs: &'static str,
+    pub(crate) access: Access,
+    pub(crate) request: Schema,
+    pub(crate) response: Schema,
+    /// What the command sends on its channel, if it takes one
+    pub(crate) channel: Option<Schema>,
+}
+
+/// An event as described by [super::bindings]
+pub(crate) struct EventContract {
+    pub(crate) name: &'static str,
+    pub(crate) docs: &'static str,
+    pub(crate) target: EventTarget,
+    pub(crate) payload: Schema,
+}
+
+/// Declares the marker type of each command and lists them for [command_contracts]
+macro_rules! commands {
+    ($(
+        $(#[doc = $doc:literal])*
+        $marker:ident($name:literal, $access:ident $(, channel: $channel:ty)?): $request:ty => $response:ty;
+    )*) => {
+        $(
+            $(#[doc = $doc])*
+            pub(crate) struct $marker;
+
+            impl Command for $marker {
+                const NAME: &'static str = $name;
+                const ACCESS: Access = Access::$access;
+                type Request = $request;
+                type Response = $response;
+            }
+        )*
+
+        /// Describes all commands. Their types end up in the definitions of `generator`
+        pub(crate) fn command_contracts(generator: &mut SchemaGenerator) -> Vec<CommandContract> {
+            vec![$(
+                CommandContract {
+                    name: $name,
+                    docs: concat!($($doc, "\n",)*),
+                    access: Access::$access,
+                    request: generator.subschema_for::<$request>(),
+                    response: generator.subschema_for::<$response>(),
+                    channel: commands!(@channel generator $($channel)?),
+                },
+            )*]
+        }
+    };
+    (@channel $generator:ident $channel:ty) => {
+        Some($generator.subschema_for::<$channel>())
+    };
+    (@channel $generator:ident) => {
+        None
+    };
+}
+
+/// Declares the marker type of each event and lists them for [event_contracts]
+macro_rules! events {
+    ($(
+        $(#[doc = $doc:literal])*
+        $marker:ident($name:literal, $target:ident): $payload:ty;
+    )*) => {
+        $(
+            $(#[doc = $doc])*
+            pub(crate) struct $marker;
+
+            impl Event for $marker {
+                const NAME: &'static str = $name;
+                const TARGET: EventTarget = EventTarget::$target;
+                type Payload = $payload;
+            }
+        )*
+
+        /// Describes all events. Their types end up in the definitions of `generator`
+        pub(crate) fn event_contracts(generator: &mut SchemaGenerator) -> Vec<EventContract> {
+            vec![$(
+                EventContract {
+                    name: $name,
+                    docs: concat!($($doc, "\n",)*),
+                    target: EventTarget::$target,
+                    payload: generator.subschema_for::<$payload>(),
+                },
+            )*]
+        }
+    };
+}
+
+commands! {
+    /// Hands out the root token of the calling window, once per page load. See [super::get_root_token_once]
+    GetRootTokenOnce("get_root_token_once", Unarmored): Empty => String;
+    /// All plugins that were loaded, by ID
+    FetchAllPlugins("fetch_all_plugins", Root): Empty => HashMap<String, PluginState>;
+    /// All plugins that were found during the last reconcile, but could not be loaded
+    FetchBrokenPlugins("fetch_broken_plugins", Root): Empty => Vec<BrokenPlugin>;
+    GetPlugin("get_plugin", Root): PluginIdRequest => PluginState;
+    GetImportPathForPlugin("get_import_path_for_plugin", Root): PluginIdRequest => ImportPath;
+    /// Opens the folder of a plugin, or the user plugin folder if no plugin is given
+    OpenPluginsDir("open_plugins_dir", Root): OpenPluginsDirRequest => ();
+    OpenSettings("open_settings", Root): Empty => ();
+    /// Opens a URL in the user's browser. URLs outside the plugin's granted domains need the user's confirmation
+    OpenUrl("open_url", Caller): OpenUrlRequest => ();
+    AnswerUrlPrompt("answer_url_prompt", Root): AnswerUrlPromptRequest => ();
+    ForgetUrlDecision("forget_url_decision", Root): ForgetUrlDecisionRequest => ();
+    /// Looks for an update of EDPF. `null` if there is none
+    CheckUpdateEdpf("check_update_edpf", Root): CheckUpdateRequest => Option<UpdateInfo>;
+    /// Installs the update found by the last `check_update_edpf`, reporting the progress on the channel
+    CommitUpdateEdpf("commit_update_edpf", Root, channel: UpdateProgress): Empty => ();
+    StartPlugin("start_plugin", Root): PluginIdRequest => ();
+    /// Clears the failure history of a plugin, lifts its quarantine and starts it right away
+    RetryPlugin("retry_plugin", Root): PluginIdRequest => ();
+    FinalizeStartPlugin("finalize_start_plugin", Root): PluginIdRequest => ();
+    StartPluginFailed("start_plugin_failed", Root): StartPluginFailedRequest => ();
+    StopPlugin("stop_plugin", Root): PluginIdRequest => ();
+    FinalizeStopPlugin("finalize_stop_plugin", Root): PluginIdRequest => ();
+    /// Writes a setting and returns the value as stored
+    WriteSetting("write_setting", Caller): WriteSettingRequest => SettingValue;
+    ReadSetting("read_setting", Caller): ReadSettingRequest => SettingValue;
+    /// The files in the plugin's storage folder. Needs [Permission::FileStorage]
+    ListPluginFiles("list_plugin_files", Caller): PluginIdRequest => Vec<PluginFile>;
+    /// Reads a file from the plugin's storage folder as UTF-8 text. Needs [Permission::FileStorage]
+    ReadPluginFile("read_plugin_file", Caller): PluginFileRequest => String;
+    /// Creates or replaces a file in the plugin's storage folder. Needs [Permission::FileStorage]
+    WritePluginFile("write_plugin_file", Caller): WritePluginFileRequest => ();
+    DeletePluginFile("delete_plugin_file", Caller): PluginFileRequest => ();
+    /// Opens the live journal stream of a plugin. Frames are sent on the channel until the page is unloaded
+    OpenJournalStream("open_journal_stream", Caller, channel: JournalFrame): PluginIdRequest => ();
+    AckJournalFrames("ack_journal_frames", Caller): AckJournalFramesRequest => ();
+    /// Sends all events of the active journals as frames on the channel. Returns once all frames were acknowledged
+    RereadActiveJournal("reread_active_journal", Caller, channel: JournalFrame): PluginIdRequest => ();
+    /// The latest entries of the audit log, newest first
+    FetchAuditLog("fetch_audit_log", Root): FetchAuditLogRequest => Vec<AuditEntry>;
+    /// Replaces the layout of the main window if one is given, then returns the layout in effect
+    SyncMainLayout("sync_main_layout", Root): SyncMainLayoutRequest => PluginsUiConfig;
+    TrustPluginPublisher("trust_plugin_publisher", Root): PluginIdRequest => ();
+    UntrustPluginPublisher("untrust_plugin_publisher", Root): PluginIdRequest => ();
+    SetTrustPolicy("set_trust_policy", Root): SetTrustPolicyRequest => TrustStore;
+    SyncTransitionTimeouts("sync_transition_timeouts", Root): SyncTransitionTimeoutsRequest => TransitionTimeouts;
+    SyncCommandLimits("sync_command_limits", Root): SyncCommandLimitsRequest => CommandLimits;
+    FetchDevPlugins("fetch_dev_plugins", Root): Empty => DevPluginLinks;
+    LinkDevPlugin("link_dev_plugin", Root): LinkDevPluginRequest => ();
+    UnlinkDevPlugin("unlink_dev_plugin", Root): PluginIdRequest => ();
+    SetPluginDir("set_plugin_dir", Root): SetPluginDirRequest => SetPluginDirResponse;
+    FetchPluginRoots("fetch_plugin_roots", Root): Empty => Vec<PluginRoot>;
+    SetPluginRoots("set_plugin_roots", Root): SetPluginRootsRequest => ();
+    SendSidecarMessage("send_sidecar_message", Caller): SendSidecarMessageRequest => ();
+    PublishBusMessage("publish_bus_message", Caller): PublishBusMessageRequest => ();
+    FetchRetainedBusMessages("fetch_retained_bus_messages", Caller): PluginIdRequest => Vec<BusMessage>;
+    FetchPluginPermissions("fetch_plugin_permissions", Root): PluginIdRequest => PluginPermissionsResponse;
+    /// Replaces the permissions granted to a plugin. Permissions it doesn't request are dropped
+    GrantPluginPermissions("grant_plugin_permissions", Root): GrantPluginPermissionsRequest => ();
+    /// Plugins that request permissions the user was not asked about yet. See `core/plugins/permissions_requested`
+    FetchPermissionRequests("fetch_permission_requests", Root): Empty => Vec<PermissionRequest>;
+    /// Runs several read-only commands in one round trip, all against the same state. Answers with one outcome per operation, in order
+    RunBatch("run_batch", Caller): BatchRequest => Vec<BatchOutcome>;
+}
+
+events! {
+    /// A plugin was added or changed its state, or a broken plugin was found or fixed
+    PluginUpdated("core/plugins/update", Root): PluginUpdate;
+    /// The frontend has to unload the plugin, so it can be started again with a new frontend hash
+    PluginRestart("core/plugins/restart", Root): PluginUpdate;
+    /// A plugin did not leave a transitional state in time, see [TransitionTimeouts]
+    TransitionExpired("core/plugins/transition_expired", Root): TransitionExpiry;
+    /// A plugin failed too often in a row and is no longer started on its own
+    PluginQuarantined("core/plugins/quarantined", Root): Quarantine;
+    /// A new plugin was discovered that requests permissions. It runs with none of them until the user answers with `grant_plugin_permissions`
+    PermissionsRequested("core/plugins/permissions_requested", Root): PermissionRequest;
+    SettingsUpdated("settings_update", Root): SettingValue;
+    /// A setting the plugin may read changed
+    PluginSettingsUpdated("core/plugins/settings_update", Plugin): SettingValue;
+    /// A message of the plugin's sidecar or WASM backend
+    SidecarMessageReceived("core/plugins/sidecar_message", Plugin): SidecarMessage;
+    BusMessageReceived("core/plugins/bus_message", Plugin): BusMessage;
+    /// Asks the user whether a plugin may open a URL. Only sent to the window the plugin runs in
+    UrlPromptOpened("core/open_url/prompt", Root): UrlPrompt;
+}
+
+/// The request of commands that take no arguments
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(crate) struct Empty {}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PluginIdRequest {
+    pub(crate) plugin_id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OpenPluginsDirRequest {
+    pub(crate) plugin_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct OpenUrlRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) url: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AnswerUrlPromptRequest {
+    pub(crate) prompt_id: String,
+    pub(crate) allow: bool,
+    /// If set, the decision applies to all further URLs of the plugin on that host
+    pub(crate) remember: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ForgetUrlDecisionRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) host: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub(crate) struct CheckUpdateRequest {
+    pub(crate) channel: ReleaseChannel,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct UpdateInfo {
+    pub(crate) new_version: String,
+    pub(crate) current_version: String,
+}
+
+/// Sent on the channel of `commit_update_edpf`
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "type")]
+pub(crate) enum UpdateProgress {
+    Started { content_len: Option<u64> },
+    Progress { chunk_len: usize },
+    Finished,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportPath {
+    /// The hash of the plugin's frontend. Changes whenever the frontend changes
+    pub(crate) hash: String,
+    pub(crate) import: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartPluginFailedRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) reasons: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WriteSettingRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) key: String,
+    pub(crate) value: Value,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReadSettingRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) key: String,
+}
+
+/// A setting and its stored value. `None` if the setting is not set
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SettingValue {
+    pub(crate) key: String,
+    pub(crate) value: Option<Value>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AckJournalFramesRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) channel_id: u32,
+    /// How many frames of the stream the facade handled so far
+    pub(crate) received: u64,
+}
+
+/// What a frame of a journal stream decrypts to, see [super::journal_stream]
+pub(crate) type JournalFrame = FrameBody<'static, LogEventWithContext>;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FetchAuditLogRequest {
+    /// If set, only entries the plugin performed or is concerned by are returned
+    pub(crate) plugin_id: Option<String>,
+    pub(crate) limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SyncMainLayoutRequest {
+    pub(crate) layout: Option<PluginsUiConfig>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetTrustPolicyRequest {
+    pub(crate) minimum_level: Option<PluginVerification>,
+    pub(crate) trust_on_first_use: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SyncTransitionTimeoutsRequest {
+    pub(crate) timeouts: Option<TransitionTimeouts>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SyncCommandLimitsRequest {
+    /// If set, the limits of that plugin are synced instead of the global ones
+    pub(crate) plugin_id: Option<String>,
+    pub(crate) limits: Option<CommandLimits>,
+    /// Drops the plugin's own limits, so the global ones apply
+    #[serde(default)]
+    pub(crate) inherit: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LinkDevPluginRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) plugin_dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetPluginDirRequest {
+    pub(crate) plugin_dir: PathBuf,
+    /// Moves the plugins of the current directory over. This is best-effort
+    #[serde(default)]
+    pub(crate) move_plugins: bool,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetPluginDirResponse {
+    pub(crate) plugin_dir: PathBuf,
+    /// Plugins that could not be moved
+    pub(crate) not_moved: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SetPluginRootsRequest {
+    /// Ordered from highest to lowest precedence
+    pub(crate) shared: Vec<PathBuf>,
+    /// Ordered from highest to lowest precedence
+    pub(crate) system: Vec<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SendSidecarMessageRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) payload: Value,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PublishBusMessageRequest {
+    pub(crate) plugin_id: String,
+    /// The topic name without the plugin ID
+    pub(crate) topic: String,
+    pub(crate) payload: Value,
+    #[serde(default)]
+    pub(crate) retain: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PluginFileRequest {
+    pub(crate) plugin_id: String,
+    /// Relative to the storage folder, with `/` as separator
+    pub(crate) path: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WritePluginFileRequest {
+    pub(crate) plugin_id: String,
+    /// Relative to the storage folder, with `/` as separator
+    pub(crate) path: String,
+    pub(crate) contents: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PluginPermissionsResponse {
+    pub(crate) requested: Vec<Permission>,
+    pub(crate) granted: Vec<Permission>,
+    /// Remembered answers to `open_url` prompts, by host
+    pub(crate) url_decisions: BTreeMap<String, UrlDecision>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GrantPluginPermissionsRequest {
+    pub(crate) plugin_id: String,
+    pub(crate) permissions: Vec<Permission>,
+}
+
+/// How many operations a single `run_batch` may contain
+pub(crate) const MAX_BATCH_OPERATIONS: usize = 256;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(crate) struct BatchRequest {
+    pub(crate) operations: Vec<BatchOperation>,
+}
+
+/// A single operation of `run_batch`: the name of a read-only command and the request it takes
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "command", content = "request", rename_all = "snake_case")]
+pub(crate) enum BatchOperation {
+    FetchAllPlugins(Empty),
+    FetchBrokenPlugins(Empty),
+    GetPlugin(PluginIdRequest),
+    GetImportPathForPlugin(PluginIdRequest),
+    ReadSetting(ReadSettingRequest),
+    FetchRetainedBusMessages(PluginIdRequest),
+    FetchPluginPermissions(PluginIdRequest),
+    FetchDevPlugins(Empty),
+    FetchPluginRoots(Empty),
+}
+
+impl BatchOperation {
+    /// The keys the command accepts on its own. A plugin's key can't sneak root-only commands into a batch
+    pub(crate) fn access(&self) -> Access {
+        match self {
+            BatchOperation::FetchAllPlugins(_) => FetchAllPlugins::ACCESS,
+            BatchOperation::FetchBrokenPlugins(_) => FetchBrokenPlugins::ACCESS,
+            BatchOperation::GetPlugin(_) => GetPlugin::ACCESS,
+            BatchOperation::GetImportPathForPlugin(_) => GetImportPathForPlugin::ACCESS,
+            BatchOperation::ReadSetting(_) => ReadSetting::ACCESS,
+            BatchOperation::FetchRetainedBusMessages(_) => FetchRetainedBusMessages::ACCESS,
+            BatchOperation::FetchPluginPermissions(_) => FetchPluginPermissions::ACCESS,
+            BatchOperation::FetchDevPlugins(_) => FetchDevPlugins::ACCESS,
+            BatchOperation::FetchPluginRoots(_) => FetchPluginRoots::ACCESS,
+        }
+    }
+}
+
+/// The result of a single operation of `run_batch`. `ok` holds what the command would have answered with on its own
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum BatchOutcome {
+    Ok(Value),
+    Error(CommandError),
+}
+
+impl<T: Serialize> From<Result<T, CommandError>> for BatchOutcome {
+    fn from(value: Result<T, CommandError>) -> Self {
+        match value.map(serde_json::to_value) {
+            Ok(Ok(x)) => BatchOutcome::Ok(x),
+            Ok(Err(_)) => BatchOutcome::Error(CommandError::InternalFailedConversion),
+            Err(e) => BatchOutcome::Error(e),
+        }
+    }
+}
+
+/// The payload of `core/plugins/update` and `core/plugins/restart`
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub(crate) enum PluginUpdate {
+    #[serde(rename_all = "camelCase")]
+    State {
+        id: String,
+        plugin_state: Box<PluginState>,
+    },
+    /// `broken` is `None` once the plugin is no longer broken
+    Broken {
+        id: String,
+        broken: Option<BrokenPlugin>,
+    },
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TransitionExpiry {
+    pub(crate) id: String,
+    /// The state the plugin did not leave in time
+    pub(crate) expired_state: PluginCurrentState,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Quarantine {
+    pub(crate) id: String,
+    pub(crate) failures: Vec<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PermissionRequest {
+    pub(crate) plugin_id: String,
+    /// The name from the manifest
+    pub(crate) name: String,
+    pub(crate) requested: Vec<Permission>,
+    /// e.g. the permissions of an upgraded `v1alpha` manifest were filled in by EDPF
+    pub(crate) manifest_warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SidecarMessage {
+    pub(crate) plugin_id: String,
+    pub(crate) payload: Value,
+}
@@ -0,0 +1,125 @@
+//! Dev plugins are plugins linked from an arbitrary folder on disk, instead of living in the plugin directory.
+//!
+//! This is meant for plugin authors: they link the output folder of their build once, and every rebuild is picked up and hot-restarted.
+//! The links are stored in `store.json` under `dev_plugins`, mapping the plugin ID to the plugin folder (the one containing the `manifest.json`).
+//! As the linked folders can be anywhere on disk, each of them gets its own `notify` watcher.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use notify::{RecommendedWatcher, Watcher};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_store::StoreBuilder;
+use tracing::{error, info, warn};
+
+use crate::portable::store_path;
+
+use super::{frontend_hash::FrontendHashCache, package_signature, ReconcileTrigger};
+
+/// Maps the ID of a dev plugin to the folder containing its `manifest.json`
+#[derive(Debug, Serialize, Deserialize, Default, Clone, JsonSchema)]
+pub(crate) struct DevPluginLinks(pub(crate) BTreeMap<String, PathBuf>);
+
+impl DevPluginLinks {
+    pub(crate) fn load<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<Self> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        Ok(match store.get("dev_plugins") {
+            Some(v) => serde_json::from_value(v)?,
+            None => Self::default(),
+        })
+    }
+
+    pub(crate) fn commit<R: Runtime>(&self, app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        store.set("dev_plugins", serde_json::to_value(self)?);
+        Ok(())
+    }
+}
+
+/// Managed by Tauri. Holds one watcher per linked dev plugin folder
+pub(crate) struct DevPluginWatchers(Mutex<HashMap<PathBuf, RecommendedWatcher>>);
+
+impl DevPluginWatchers {
+    pub(crate) fn new() -> Self {
+        Self(Mutex::new(HashMap::new()))
+    }
+
+    /// Starts watching newly linked folders and stops watching folders that are no longer linked.
+    ///
+    /// Changes to the `manifest.json` or anything below `frontend/` invalidate the cached digests and trigger a reconcile,
+    /// which restarts the plugin if its frontend hash changed. Changes to the other files of the package listing (see [package_signature::affects_listing])
+    /// trigger a reconcile as well, as they affect the verification of the plugin.
+    pub(crate) fn sync(
+        &self,
+        links: &DevPluginLinks,
+        hash_cache: &Arc<FrontendHashCache>,
+        trigger: &ReconcileTrigger,
+    ) {
+        let mut watchers = self.0.lock().unwrap();
+        watchers.retain(|plugin_dir, _| links.0.values().any(|x| x == plugin_dir));
+
+        for plugin_dir in links.0.values() {
+            if watchers.contains_key(plugin_dir) || !plugin_dir.is_dir() {
+                continue;
+            }
+
+            let hash_cache = hash_cache.clone();
+            let trigger = trigger.clone();
+            let moved_plugin_dir = plugin_dir.clone();
+            let watcher = RecommendedWatcher::new(
+                move |res: Result<notify::Event, notify::Error>| match res {
+                    Ok(ev) => {
+                        if !matches!(
+                            ev.kind,
+                            notify::EventKind::Create(_)
+                                | notify::EventKind::Modify(_)
+                                | notify::EventKind::Remove(_)
+                        ) {
+                            return;
+                        }
+                        hash_cache.invalidate(&ev.paths);
+                        let relevant = ev
+                            .paths
+                            .iter()
+                            .any(|p| package_signature::affects_listing(&moved_plugin_dir, p));
+                        if relevant {
+                            trigger.trigger();
+                        }
+                    }
+                    Err(_) => {
+                        warn!("rx error while using dev plugin listener. ignoring")
+                    }
+                },
+                Default::default(),
+            )
+            .and_then(|mut x| {
+                x.watch(plugin_dir, notify::RecursiveMode::Recursive)?;
+                Ok(x)
+            });
+
+            match watcher {
+                Ok(x) => {
+                    info!("watching dev plugin at {}", plugin_dir.display());
+                    watchers.insert(plugin_dir.clone(), x);
+                }
+                Err(e) => error!(
+                    "failed to watch dev plugin at {}: {e}",
+                    plugin_dir.display()
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Plugins bundled with EDPF.
+//!
+//! `build.rs` discovers every folder in `assets/plugins` that has a `manifest.json`, validates the manifest and generates [EMBEDDED_PLUGINS].
+//! The frontend hash is computed at build time as well, so the Frontend reloads an embedded plugin after an upgrade instead of serving a cached version.
+//!
+//! With the `embed-assets` feature, the frontend files are included in the binary and served by the [super::frontend_server] from memory.
+//! Otherwise, they are read from the resource dir.
+
+/// An embedded plugin as generated by `build.rs`
+pub(crate) struct EmbeddedPlugin {
+    pub(crate) id: &'static str,
+    /// Contents of the `manifest.json`
+    pub(crate) manifest: &'static str,
+    /// [None] if the frontend was not built when EDPF was compiled. It is then hashed at runtime
+    pub(crate) frontend_hash: Option<&'static str>,
+    /// Frontend files by their path relative to the `frontend` folder. Empty unless the `embed-assets` feature is enabled
+    pub(crate) files: &'static [(&'static str, &'static [u8])],
+}
+
+include!(concat!(env!("OUT_DIR"), "/embedded_plugins.rs"));
+
+impl EmbeddedPlugin {
+    pub(crate) fn get(id: &str) -> Option<&'static EmbeddedPlugin> {
+        EMBEDDED_PLUGINS.iter().find(|x| x.id == id)
+    }
+
+    /// Returns whether the frontend is served from the binary
+    pub(crate) fn is_in_binary(&self) -> bool {
+        !self.files.is_empty()
+    }
+
+    /// Returns the contents of a frontend file. `path` is relative to the `frontend` folder and uses `/` as separator
+    pub(crate) fn file(&self, path: &str) -> Option<&'static [u8]> {
+        self.files
+            .iter()
+            .find(|(x, _)| *x == path)
+            .map(|(_, bytes)| *bytes)
+    }
+}
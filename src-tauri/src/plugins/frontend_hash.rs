@@ -0,0 +1,151 @@
+//! Incremental hashing of plugin frontend folders.
+//!
+//! The frontend hash tells the Frontend whether a plugin has to be reloaded. Hashing every file of every plugin on each reconcile gets expensive with large bundles,
+//! so the digest of each file is cached. A digest is only recomputed if the size or modification time of the file changed, or if `notify` reported a change for it.
+//! The package listing that is checked against a plugin's signature (see [super::package_signature]) is built from the same cached digests.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use tracing::{error, instrument};
+
+use super::{
+    package_signature::{self, collect_files, SignatureCheck},
+    PluginState,
+};
+
+/// Maps the folder of a user plugin to what was computed for it before the reconcile, see [FrontendHashCache::digest_plugin_dir]
+pub(crate) type FrontendHashes = HashMap<PathBuf, PluginDigest>;
+
+pub(crate) struct PluginDigest {
+    /// [None] if the frontend could not be hashed
+    pub(crate) frontend_hash: Option<String>,
+    /// [None] if the manifest could not be read. The reconcile reports the plugin as broken then
+    pub(crate) signature: Option<SignatureCheck>,
+}
+
+struct CachedDigest {
+    size: u64,
+    modified: Option<SystemTime>,
+    digest: [u8; 32],
+}
+
+/// Managed by Tauri. Caches the digests of single files below the plugins' `frontend` folders
+pub(crate) struct FrontendHashCache {
+    files: Mutex<HashMap<PathBuf, CachedDigest>>,
+}
+
+impl FrontendHashCache {
+    pub(crate) fn new() -> Self {
+        Self {
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drops the cached digests of the given paths. If a path is a folder, everything below it is dropped as well
+    pub(crate) fn invalidate(&self, paths: &[PathBuf]) {
+        self.files
+            .lock()
+            .unwrap()
+            .retain(|path, _| !paths.iter().any(|x| path.starts_with(x)));
+    }
+
+    /// Returns the folders of all plugins in the plugin root `root`, i.e. all folders containing a `manifest.json`
+    pub(crate) fn plugin_dirs_in(root: &Path) -> Vec<PathBuf> {
+        let manifests = match glob::glob(root.join("*/manifest.json").to_str().unwrap()) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("failed to get user plugin manifests: {e}");
+                return vec![];
+            }
+        };
+
+        manifests
+            .flatten()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect()
+    }
+
+    /// Hashes the frontend of the plugin in `plugin_dir` and checks its signature. This reads from disk, so it should not be run on the async runtime directly
+    pub(crate) fn digest_plugin_dir(&self, plugin_dir: &Path) -> PluginDigest {
+        let signature = PluginState::get_manifest(&plugin_dir.join("manifest.json"))
+            .ok()
+            .map(|(manifest, _)| package_signature::check_package(plugin_dir, &manifest, self));
+        PluginDigest {
+            frontend_hash: self.hash_plugin_dir(plugin_dir),
+            signature,
+        }
+    }
+
+    /// Hashes the `frontend` folder of the plugin in `plugin_dir`. Errors are logged and result in [None]
+    pub(crate) fn hash_plugin_dir(&self, plugin_dir: &Path) -> Option<String> {
+        self.hash_frontend_dir(&plugin_dir.join("frontend"))
+            .map_err(|e| error!("failed to hash frontend of {}: {e}", plugin_dir.display()))
+            .ok()
+    }
+
+    /// Hashes all files below `frontend_dir`, reusing cached digests where possible. Symlinks are not followed.
+    ///
+    /// The hash covers the relative path and the contents of each file, so renaming a file changes the hash as well.
+    #[instrument(skip(self))]
+    pub(crate) fn hash_frontend_dir(&self, frontend_dir: &Path) -> anyhow::Result<String> {
+        if !frontend_dir.is_dir() {
+            anyhow::bail!("{} is not a directory", frontend_dir.display());
+        }
+        let mut files = vec![];
+        collect_files(frontend_dir, &mut files)?;
+
+        let mut entries = Vec::with_capacity(files.len());
+        for path in files {
+            let digest = self.file_digest(&path)?;
+            let relative = path
+                .strip_prefix(frontend_dir)?
+                .components()
+                .map(|x| x.as_os_str().to_string_lossy())
+                .join("/");
+            entries.push((relative, digest));
+        }
+
+        let mut hasher = Sha256::new();
+        for (relative, digest) in entries.into_iter().sorted() {
+            hasher.update(relative.as_bytes());
+            hasher.update([0]);
+            hasher.update(digest);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Returns the SHA-256 digest of a single file. It is only read if its size or modification time changed since it was last hashed
+    pub(crate) fn file_digest(&self, path: &Path) -> anyhow::Result<[u8; 32]> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified().ok();
+
+        let cached = self
+            .files
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|x| x.size == metadata.len() && x.modified == modified && modified.is_some())
+            .map(|x| x.digest);
+        if let Some(x) = cached {
+            return Ok(x);
+        }
+        let digest: [u8; 32] = Sha256::digest(fs::read(path)?).into();
+        self.files.lock().unwrap().insert(
+            path.to_path_buf(),
+            CachedDigest {
+                size: metadata.len(),
+                modified,
+                digest,
+            },
+        );
+        Ok(digest)
+    }
+}
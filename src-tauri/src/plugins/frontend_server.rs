@@ -5,7 +5,10 @@ use std::{
     sync::Arc,
 };
 
-use super::{PluginState, PluginsState};
+use super::{
+    embedded_plugins::EmbeddedPlugin, plugin_manifest::PluginManifest, PluginState,
+    PluginStateSource, PluginsState,
+};
 use anyhow::anyhow;
 use axum::{
     body::Body,
@@ -19,7 +22,11 @@ use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager, Wry};
 use tokio::{net::TcpListener, sync::RwLock};
 use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// The HTTP Server tries to bind to this port first, so URLs to the manifest JSON Schemas stay stable between runs.
+/// If the port is taken, any free port is used instead.
+const PREFERRED_PORT: u16 = 47830;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub(crate) struct HttpServerState {
@@ -34,6 +41,15 @@ impl HttpServerState {
             port, plugin.id, plugin.frontend_hash
         )
     }
+
+    /// Returns the URL at which the JSON Schema of the given manifest version is served
+    pub(crate) fn manifest_schema_url(&self, version: &str) -> String {
+        format!(
+            "http://localhost:{}/schemas/manifest/{}.json",
+            self.address.port(),
+            version
+        )
+    }
 }
 
 type InjectableState = Arc<RwLock<PluginsState>>;
@@ -42,10 +58,16 @@ type InjectableState = Arc<RwLock<PluginsState>>;
 pub(crate) async fn spawn_server_blocking(app_handle: &AppHandle<Wry>) -> anyhow::Result<()> {
     let cors = CorsLayer::very_permissive().allow_credentials(false);
 
-    // Port 0 -> we pick any free one
-    let listener = TcpListener::bind("127.0.0.1:0")
-        .await
-        .expect("Failed to spawn Server due to failing to spawn a TCP Listener");
+    let listener = match TcpListener::bind(("127.0.0.1", PREFERRED_PORT)).await {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Could not bind to preferred port {PREFERRED_PORT}: {e}. Schema URLs will not be stable");
+            // Port 0 -> we pick any free one
+            TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("Failed to spawn Server due to failing to spawn a TCP Listener")
+        }
+    };
     let addr = listener.local_addr().unwrap();
     {
         if !app_handle.manage(HttpServerState { address: addr }) {
@@ -56,6 +78,12 @@ pub(crate) async fn spawn_server_blocking(app_handle: &AppHandle<Wry>) -> anyhow
     }
 
     info!("Preparing HTTP Server to run on {}", addr);
+    info!(
+        "Plugin manifest JSON Schema is served at {}",
+        app_handle
+            .state::<HttpServerState>()
+            .manifest_schema_url("v1")
+    );
 
     let state_ref: InjectableState = app_handle
         .state::<Arc<RwLock<PluginsState>>>()
@@ -63,6 +91,8 @@ pub(crate) async fn spawn_server_blocking(app_handle: &AppHandle<Wry>) -> anyhow
         .clone();
 
     let router = Router::new()
+        .route("/schemas/manifest.json", get(serve_manifest_schema_all))
+        .route("/schemas/manifest/{version}", get(serve_manifest_schema))
         .route("/{plugin}/{hash}/{*path}", get(serve_asset))
         .route("/", get(debug_mapping))
         .layer(cors)
@@ -88,22 +118,59 @@ async fn debug_mapping(State(state): State<InjectableState>) -> impl IntoRespons
     Json(iter).into_response()
 }
 
+/// Serves the JSON Schema accepting any supported manifest version
+async fn serve_manifest_schema_all() -> impl IntoResponse {
+    match PluginManifest::json_schema(None) {
+        Some(schema) => Json(schema).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Serves the JSON Schema of a single manifest version. `version` is expected to be suffixed with `.json`, e.g. `v1.json`
+async fn serve_manifest_schema(Path(version): Path<String>) -> impl IntoResponse {
+    let version = version.strip_suffix(".json").unwrap_or(&version);
+    match PluginManifest::json_schema(Some(version)) {
+        Some(schema) => Json(schema).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 /// Serves the assets. Note that a hash is used in the path. We do not use that hash when serving files. This is merely used for cache busting / ES Module resolution.
 async fn serve_asset(
     State(state): State<InjectableState>,
     Path((plugin, _, tail)): Path<(String, String, String)>,
 ) -> impl IntoResponse {
-    let root = {
+    let (root, source) = {
         if let Some(plugin_state) = state.read().await.plugin_states.get(&plugin) {
-            plugin_state.frontend_path()
+            (plugin_state.frontend_path(), plugin_state.source.clone())
         } else {
             return StatusCode::NOT_FOUND.into_response();
         }
     };
     let path_to_resource = safe_join(&root, StdPath::new(&tail));
-    let bytes = match tokio::fs::read(&path_to_resource).await {
-        Ok(bytes) => bytes,
-        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    // With the `embed-assets` feature, embedded plugins are served from the binary
+    let embedded = match source {
+        PluginStateSource::Embedded => EmbeddedPlugin::get(&plugin).filter(|x| x.is_in_binary()),
+        _ => None,
+    };
+    let bytes = match embedded {
+        Some(embedded) => {
+            let relative = path_to_resource
+                .strip_prefix(&root)
+                .unwrap_or(&path_to_resource)
+                .components()
+                .map(|x| x.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            match embedded.file(&relative) {
+                Some(bytes) => bytes.to_vec(),
+                None => return StatusCode::NOT_FOUND.into_response(),
+            }
+        }
+        None => match tokio::fs::read(&path_to_resource).await {
+            Ok(bytes) => bytes,
+            Err(_) => return StatusCode::NOT_FOUND.into_response(),
+        },
     };
 
     let ct = mime_guess::from_path(&path_to_resource).first();
@@ -1,14 +1,34 @@
 //! This Module concerns itself with the *persistent* State outside of the Manifest that is user-configurable
 //!
-//! E.g. if a plugin is enabled, if the plugin has specific permissions defined (unused for now)
+//! E.g. if a plugin is enabled, which permissions the user granted to the plugin
 //! also if the Plugin is new / unknown
 
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Runtime};
 use tauri_plugin_store::StoreBuilder;
 use tracing::error;
 
-use super::internal_plugin_ids;
+use crate::portable::store_path;
+
+use super::{
+    internal_plugin_ids,
+    permissions::Permission,
+    rate_limits::{CommandClass, RateLimit},
+    url_policy::UrlDecision,
+};
+
+/// Failures older than this are forgotten
+const FAILURE_WINDOW: TimeDelta = TimeDelta::minutes(10);
+/// A plugin that failed this many times within [FAILURE_WINDOW] is quarantined
+const QUARANTINE_THRESHOLD: usize = 5;
+/// Delay before retrying a plugin after its first failure. Doubles with every further failure, up to [BACKOFF_MAX]
+const BACKOFF_BASE: TimeDelta = TimeDelta::seconds(5);
+const BACKOFF_MAX: TimeDelta = TimeDelta::minutes(5);
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 /// This contains the entire **generic** Configurable State for a plugin.  
 /// Settings read/set from the Plugins themselves are managed separately
@@ -22,6 +42,21 @@ pub(crate) struct GenericPluginSettings {
     pub(crate) update_strategy: PluginSettingsUpdateStrategy,
     /// If set, versions marked as pre-releases will also be considered for updates.
     pub(crate) consider_prereleases: bool,
+    /// Points in time at which this plugin failed to start, or crashed right after starting. Only failures within [FAILURE_WINDOW] are kept.
+    #[serde(default)]
+    pub(crate) failures: Vec<DateTime<Utc>>,
+    /// Set if the plugin was disabled automatically because it kept failing. Cleared when the user retries the plugin.
+    #[serde(default)]
+    pub(crate) quarantined: bool,
+    /// Permissions the user granted to this plugin. See [super::permissions]
+    #[serde(default)]
+    pub(crate) granted_permissions: Vec<Permission>,
+    /// Replaces the global [CommandLimits] for this plugin, if set
+    #[serde(default)]
+    pub(crate) command_limits: Option<CommandLimits>,
+    /// What the user chose to remember when this plugin opened a URL outside its granted domains, by host. See [super::url_policy]
+    #[serde(default)]
+    pub(crate) url_decisions: BTreeMap<String, UrlDecision>,
 }
 
 impl GenericPluginSettings {
@@ -29,7 +64,7 @@ impl GenericPluginSettings {
         app_handle: &AppHandle<R>,
         maybe_new_layout: Option<PluginsUiConfig>,
     ) -> anyhow::Result<PluginsUiConfig> {
-        let store = match StoreBuilder::new(app_handle, "store.json").build() {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
             Ok(x) => x,
             Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
         };
@@ -51,11 +86,44 @@ impl GenericPluginSettings {
         Ok(response)
     }
 
+    /// Adds a failure at `now` to the history. Returns true if this failure got the plugin quarantined, in which case it is also disabled.
+    pub(crate) fn record_failure(&mut self, now: DateTime<Utc>) -> bool {
+        self.failures.retain(|x| now - *x < FAILURE_WINDOW);
+        self.failures.push(now);
+
+        if self.failures.len() >= QUARANTINE_THRESHOLD && !self.quarantined {
+            self.enabled = false;
+            self.quarantined = true;
+            return true;
+        }
+        false
+    }
+
+    /// Returns the earliest point in time at which the plugin should be started again, or None if it has no recent failures.
+    pub(crate) fn next_attempt_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let recent_failures = self
+            .failures
+            .iter()
+            .filter(|x| now - **x < FAILURE_WINDOW)
+            .collect::<Vec<_>>();
+        let last_failure = recent_failures.iter().max()?;
+        let exponent = (recent_failures.len() as u32 - 1).min(16);
+        let delay = (BACKOFF_BASE * 2i32.pow(exponent)).min(BACKOFF_MAX);
+        Some(**last_failure + delay)
+    }
+
+    /// Clears the failure history, lifts the quarantine and enables the plugin again
+    pub(crate) fn reset_failures(&mut self) {
+        self.failures.clear();
+        self.quarantined = false;
+        self.enabled = true;
+    }
+
     pub(crate) fn get_by_id<R: Runtime>(
         app_handle: &AppHandle<R>,
         plugin_id: &str,
     ) -> anyhow::Result<Option<Self>> {
-        let store = match StoreBuilder::new(app_handle, "store.json").build() {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
             Ok(x) => x,
             Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
         };
@@ -74,7 +142,7 @@ impl GenericPluginSettings {
         app_handle: &AppHandle<R>,
         ids_to_check: &[String],
     ) -> anyhow::Result<Vec<String>> {
-        let store = match StoreBuilder::new(app_handle, "store.json").build() {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
             Ok(x) => x,
             Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
         };
@@ -112,7 +180,7 @@ impl GenericPluginSettings {
         app_handle: &AppHandle<R>,
         plugin_id: &str,
     ) -> anyhow::Result<()> {
-        let store = match StoreBuilder::new(app_handle, "store.json").build() {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
             Ok(x) => x,
             Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
         };
@@ -125,6 +193,147 @@ impl GenericPluginSettings {
     }
 }
 
+/// Deadlines for the transitional plugin states, in seconds. Stored in `store.json` under `transition_timeouts`.
+///
+/// Once a deadline passes without the Frontend acknowledging the transition, the Backend resolves it on its own.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub(crate) struct TransitionTimeouts {
+    /// How long the Frontend has to load a plugin before it is considered failed
+    pub(crate) starting_secs: u64,
+    /// How long the Frontend has to unload a plugin before it is forced to be disabled
+    pub(crate) disabling_secs: u64,
+    /// How long the Frontend has to unload a plugin during a restart before the restart is considered failed
+    pub(crate) restarting_secs: u64,
+}
+
+impl Default for TransitionTimeouts {
+    fn default() -> Self {
+        Self {
+            starting_secs: 30,
+            disabling_secs: 10,
+            restarting_secs: 10,
+        }
+    }
+}
+
+impl TransitionTimeouts {
+    pub(crate) fn load<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<Self> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        Ok(match store.get("transition_timeouts") {
+            Some(v) => serde_json::from_value(v)?,
+            None => Self::default(),
+        })
+    }
+
+    /// Optionally replaces the stored timeouts, then returns the timeouts in effect
+    pub(crate) fn sync<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        maybe_new_timeouts: Option<Self>,
+    ) -> anyhow::Result<Self> {
+        if let Some(new_timeouts) = maybe_new_timeouts {
+            let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+                Ok(x) => x,
+                Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+            };
+            store.set("transition_timeouts", serde_json::to_value(new_timeouts)?);
+        }
+        Self::load(app_handle)
+    }
+}
+
+/// Limits on what plugins may do, see [super::rate_limits]. Stored in `store.json` under `command_limits`.
+/// A plugin may have its own limits, see [GenericPluginSettings::command_limits]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub(crate) struct CommandLimits {
+    /// Command classes missing here use [CommandClass::default_limit]
+    pub(crate) rate_limits: BTreeMap<CommandClass, RateLimit>,
+    /// How large the settings store of a plugin may grow, in bytes
+    pub(crate) settings_quota_bytes: u64,
+    /// How large all files in the storage folder of a plugin may grow together, in bytes
+    pub(crate) files_quota_bytes: u64,
+}
+
+impl Default for CommandLimits {
+    fn default() -> Self {
+        Self {
+            rate_limits: BTreeMap::new(),
+            settings_quota_bytes: 1024 * 1024,
+            files_quota_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl CommandLimits {
+    pub(crate) fn rate_limit(&self, class: CommandClass) -> RateLimit {
+        self.rate_limits
+            .get(&class)
+            .copied()
+            .unwrap_or_else(|| class.default_limit())
+    }
+
+    pub(crate) fn load<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<Self> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        Ok(match store.get("command_limits") {
+            Some(v) => serde_json::from_value(v)?,
+            None => Self::default(),
+        })
+    }
+
+    /// The limits in effect for `plugin_id`: its own, if it has any, or the global ones
+    pub(crate) fn for_plugin<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        plugin_id: &str,
+    ) -> anyhow::Result<Self> {
+        match GenericPluginSettings::get_by_id(app_handle, plugin_id)?
+            .and_then(|x| x.command_limits)
+        {
+            Some(x) => Ok(x),
+            None => Self::load(app_handle),
+        }
+    }
+
+    /// Optionally replaces the stored global limits, then returns the global limits in effect
+    pub(crate) fn sync<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        maybe_new_limits: Option<Self>,
+    ) -> anyhow::Result<Self> {
+        if let Some(new_limits) = maybe_new_limits {
+            let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+                Ok(x) => x,
+                Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+            };
+            store.set("command_limits", serde_json::to_value(new_limits)?);
+        }
+        Self::load(app_handle)
+    }
+
+    /// Optionally replaces the limits of `plugin_id`, then returns the limits in effect for it. `inherit` drops its own limits, so the global ones apply
+    pub(crate) fn sync_for_plugin<R: Runtime>(
+        app_handle: &AppHandle<R>,
+        plugin_id: &str,
+        maybe_new_limits: Option<Self>,
+        inherit: bool,
+    ) -> anyhow::Result<Self> {
+        if maybe_new_limits.is_some() || inherit {
+            let mut settings =
+                GenericPluginSettings::get_by_id(app_handle, plugin_id)?.unwrap_or_default();
+            settings.command_limits = maybe_new_limits;
+            settings.commit(app_handle, plugin_id)?;
+        }
+        Self::for_plugin(app_handle, plugin_id)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub(crate) enum PluginSettingsUpdateStrategy {
     /// The Plugin is upgraded without the User needing to interfere. If the Plugin is started, it will be restarted
@@ -135,12 +344,12 @@ pub(crate) enum PluginSettingsUpdateStrategy {
     #[default]
     Manual,
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct PluginsUiConfig {
     root: PluginUiConfigNode,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "type")]
 pub(crate) enum PluginUiConfigNode {
     VerticalLayout {
@@ -158,7 +367,7 @@ pub(crate) enum PluginUiConfigNode {
         meta: PluginUiConfigNodeMetadata,
     },
 }
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub(crate) struct PluginUiConfigNodeMetadata {
     min_width: Option<String>,
     max_width: Option<String>,
@@ -182,3 +391,86 @@ impl Default for PluginsUiConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + TimeDelta::seconds(seconds)
+    }
+
+    fn enabled() -> GenericPluginSettings {
+        GenericPluginSettings {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_with_each_failure() {
+        let mut settings = enabled();
+        assert_eq!(settings.next_attempt_at(at(0)), None);
+        let mut delay = BACKOFF_BASE;
+        for i in 0..4 {
+            settings.record_failure(at(i));
+            assert_eq!(settings.next_attempt_at(at(i)), Some(at(i) + delay));
+            delay = delay * 2;
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let mut settings = enabled();
+        // 5s * 2^6 would be past the cap
+        for i in 0..7 {
+            settings.record_failure(at(i));
+        }
+        assert_eq!(settings.next_attempt_at(at(6)), Some(at(6) + BACKOFF_MAX));
+        // the exponent is clamped, so even a long history does not overflow
+        settings.failures = (0..100).map(at).collect();
+        assert_eq!(settings.next_attempt_at(at(99)), Some(at(99) + BACKOFF_MAX));
+    }
+
+    #[test]
+    fn failures_expire_after_the_window() {
+        let mut settings = enabled();
+        settings.record_failure(at(0));
+        settings.record_failure(at(1));
+        let window = FAILURE_WINDOW.num_seconds();
+        // only the failure at 1 is still in the window, so the delay starts over
+        assert_eq!(
+            settings.next_attempt_at(at(window)),
+            Some(at(1) + BACKOFF_BASE)
+        );
+        assert_eq!(settings.next_attempt_at(at(window + 1)), None);
+
+        // expired failures are dropped from the history and do not count towards the quarantine
+        for i in 0..QUARANTINE_THRESHOLD as i64 - 2 {
+            settings.record_failure(at(window + 1 + i));
+        }
+        assert_eq!(settings.failures.len(), QUARANTINE_THRESHOLD - 2);
+        assert!(!settings.quarantined);
+    }
+
+    #[test]
+    fn quarantine_happens_exactly_at_the_threshold() {
+        let mut settings = enabled();
+        for i in 0..QUARANTINE_THRESHOLD as i64 - 1 {
+            assert!(!settings.record_failure(at(i)));
+            assert!(settings.enabled);
+        }
+        assert!(settings.record_failure(at(QUARANTINE_THRESHOLD as i64)));
+        assert!(settings.quarantined);
+        assert!(!settings.enabled);
+        // only the failure that caused the quarantine reports it
+        assert!(!settings.record_failure(at(QUARANTINE_THRESHOLD as i64 + 1)));
+
+        settings.reset_failures();
+        assert!(settings.enabled && !settings.quarantined);
+        assert_eq!(
+            settings.next_attempt_at(at(QUARANTINE_THRESHOLD as i64 + 1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,496 @@
+//! Streams journal events to plugin facades over [Channel]s.
+//!
+//! Frames are sent as raw bytes, so they are neither JSON-encoded nor base64-encoded again on their way to the webview:
+//!
+//! | Bytes | Content |
+//! | ----- | ------- |
+//! | 1     | Flags, see [FLAG_DEFLATE] |
+//! | 12    | AES-GCM IV |
+//! | rest  | The [FrameBody] as JSON, deflated if [FLAG_DEFLATE] is set, then encrypted with the key that opened the stream |
+//!
+//! The facade acknowledges each frame once it handled it (see `ack_journal_frames`). At most [MAX_UNACKED_FRAMES] frames are unacknowledged,
+//! further frames wait in the stream's queue.
+//! - The live stream of a plugin (see [JournalStreams::push_live]) never blocks the journal readers. If its queue grows beyond [MAX_QUEUED_BYTES],
+//!   the queue is dropped and the next frame reports how many events were lost.
+//! - A reread (see [JournalStream::send]) waits for acknowledgements instead.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use flate2::{write::DeflateEncoder, Compression};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::ipc::{Channel, Response};
+use tokio::sync::Notify;
+use tracing::warn;
+
+use super::{
+    commands_armor::{self, DearmorError},
+    contracts::CommandError,
+};
+
+/// Set if the frame body is compressed with raw deflate (`deflate-raw` for the `DecompressionStream` of the webview)
+const FLAG_DEFLATE: u8 = 1;
+/// Frame bodies larger than this are compressed
+const COMPRESS_THRESHOLD: usize = 16 * 1024;
+/// How many frames may be sent to a facade without being acknowledged
+const MAX_UNACKED_FRAMES: u64 = 8;
+/// How many bytes of frames a live stream may queue before it drops them
+const MAX_QUEUED_BYTES: usize = 16 * 1024 * 1024;
+/// How long a reread waits for the facade to acknowledge a frame
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub(crate) enum StreamError {
+    Armor(DearmorError),
+    /// The webview no longer accepts frames on this channel
+    Closed,
+    /// The facade did not acknowledge frames within [ACK_TIMEOUT]
+    Stalled,
+}
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Armor(e) => e.fmt(f),
+            StreamError::Closed => write!(f, "STREAM_CLOSED"),
+            StreamError::Stalled => write!(f, "STREAM_STALLED"),
+        }
+    }
+}
+
+impl From<DearmorError> for StreamError {
+    fn from(value: DearmorError) -> Self {
+        StreamError::Armor(value)
+    }
+}
+
+impl From<StreamError> for CommandError {
+    fn from(value: StreamError) -> Self {
+        match value {
+            StreamError::Armor(e) => e.into(),
+            StreamError::Closed => CommandError::StreamClosed,
+            StreamError::Stalled => CommandError::StreamStalled,
+        }
+    }
+}
+
+/// What a frame decrypts to
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[schemars(rename = "JournalFrame")]
+pub(crate) struct FrameBody<'a, T> {
+    events: &'a [T],
+    /// How many events were dropped before this frame, because the facade fell behind
+    dropped: u64,
+}
+
+/// Serializes, compresses and encrypts a frame. See the module docs for the layout
+fn encode_frame<T: Serialize>(
+    key: &[u8; 16],
+    events: &[T],
+    dropped: u64,
+) -> Result<Vec<u8>, DearmorError> {
+    let body = serde_json::to_vec(&FrameBody { events, dropped })
+        .map_err(|_| DearmorError::FailedParsePayloadStructure)?;
+    let (flags, body) = match body.len() > COMPRESS_THRESHOLD {
+        true => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+            match encoder.write_all(&body).and_then(|_| encoder.finish()) {
+                Ok(compressed) => (FLAG_DEFLATE, compressed),
+                Err(e) => {
+                    warn!("failed to compress journal frame, sending it uncompressed: {e}");
+                    (0, body)
+                }
+            }
+        }
+        false => (0, body),
+    };
+    let (iv, encrypted) = commands_armor::encrypt_bytes(key, &body)?;
+
+    let mut frame = Vec::with_capacity(1 + iv.len() + encrypted.len());
+    frame.push(flags);
+    frame.extend_from_slice(&iv);
+    frame.extend_from_slice(&encrypted);
+    Ok(frame)
+}
+
+struct QueuedFrame {
+    bytes: Vec<u8>,
+    events: usize,
+}
+
+#[derive(Default)]
+struct Flow {
+    sent: u64,
+    acked: u64,
+    queue: VecDeque<QueuedFrame>,
+    queued_bytes: usize,
+    /// Events dropped since the last frame was queued
+    dropped: u64,
+}
+
+/// A channel to the facade of a plugin
+pub(crate) struct JournalStream {
+    plugin_id: String,
+    window: String,
+    /// Live streams receive new journal events, all others are used for a single reread
+    live: bool,
+    key: [u8; 16],
+    channel: Channel<Response>,
+    flow: Mutex<Flow>,
+    acked: Notify,
+}
+
+impl JournalStream {
+    pub(crate) fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
+
+    pub(crate) fn channel_id(&self) -> u32 {
+        self.channel.id()
+    }
+
+    /// Sends queued frames until [MAX_UNACKED_FRAMES] are unacknowledged
+    fn drain(&self, flow: &mut Flow) -> Result<(), StreamError> {
+        while flow.sent - flow.acked < MAX_UNACKED_FRAMES {
+            let Some(frame) = flow.queue.pop_front() else {
+                break;
+            };
+            flow.queued_bytes -= frame.bytes.len();
+            flow.sent += 1;
+            self.channel
+                .send(Response::new(frame.bytes))
+                .map_err(|_| StreamError::Closed)?;
+        }
+        Ok(())
+    }
+
+    /// Queues a frame with `events`. Drops the queue if the facade fell too far behind
+    fn push<T: Serialize>(&self, events: &[T]) -> Result<(), StreamError> {
+        let mut flow = self.flow.lock().unwrap();
+        let bytes = encode_frame(&self.key, events, flow.dropped)?;
+        flow.dropped = 0;
+        flow.queued_bytes += bytes.len();
+        flow.queue.push_back(QueuedFrame {
+            bytes,
+            events: events.len(),
+        });
+        if flow.queued_bytes > MAX_QUEUED_BYTES {
+            let dropped: usize = flow.queue.drain(..).map(|x| x.events).sum();
+            warn!(
+                "journal stream of {} fell behind, dropping {dropped} events",
+                self.plugin_id
+            );
+            flow.queued_bytes = 0;
+            flow.dropped += dropped as u64;
+        }
+        self.drain(&mut flow)
+    }
+
+    /// Sends a frame with `events`, waiting until the facade acknowledged enough of the previous frames
+    pub(crate) async fn send<T: Serialize>(&self, events: &[T]) -> Result<(), StreamError> {
+        loop {
+            // registered before checking, so an acknowledgement in between isn't missed
+            let acked = self.acked.notified();
+            {
+                let flow = self.flow.lock().unwrap();
+                if flow.sent - flow.acked < MAX_UNACKED_FRAMES {
+                    drop(flow);
+                    return self.push(events);
+                }
+            }
+            tokio::time::timeout(ACK_TIMEOUT, acked)
+                .await
+                .map_err(|_| StreamError::Stalled)?;
+        }
+    }
+
+    /// Waits until the facade acknowledged all frames
+    pub(crate) async fn flush(&self) -> Result<(), StreamError> {
+        loop {
+            let acked = self.acked.notified();
+            {
+                let flow = self.flow.lock().unwrap();
+                if flow.queue.is_empty() && flow.sent == flow.acked {
+                    return Ok(());
+                }
+            }
+            tokio::time::timeout(ACK_TIMEOUT, acked)
+                .await
+                .map_err(|_| StreamError::Stalled)?;
+        }
+    }
+
+    /// Called when the facade handled the first `received` frames of this stream
+    pub(crate) fn ack(&self, received: u64) -> Result<(), StreamError> {
+        let mut flow = self.flow.lock().unwrap();
+        flow.acked = flow.acked.max(received.min(flow.sent));
+        let drained = self.drain(&mut flow);
+        self.acked.notify_waiters();
+        drained
+    }
+}
+
+/// Managed by Tauri. Holds the open journal streams, keyed by the ID of their channel
+pub(crate) struct JournalStreams {
+    streams: Mutex<HashMap<u32, Arc<JournalStream>>>,
+}
+
+impl JournalStreams {
+    pub(crate) fn new() -> Self {
+        Self {
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers a channel of the facade of `plugin_id` in `window`. Its frames are encrypted with `key`.
+    /// A new live stream replaces the plugin's previous one in that window
+    pub(crate) fn open(
+        &self,
+        window: &str,
+        plugin_id: &str,
+        key: [u8; 16],
+        channel: Channel<Response>,
+        live: bool,
+    ) -> Arc<JournalStream> {
+        let stream = Arc::new(JournalStream {
+            plugin_id: plugin_id.to_string(),
+            window: window.to_string(),
+            live,
+            key,
+            channel,
+            flow: Mutex::new(Flow::default()),
+            acked: Notify::new(),
+        });
+        let mut streams = self.streams.lock().unwrap();
+        if live {
+            streams.retain(|_, x| !(x.live && x.plugin_id == plugin_id && x.window == window));
+        }
+        streams.insert(stream.channel.id(), stream.clone());
+        stream
+    }
+
+    pub(crate) fn get(&self, channel_id: u32) -> Option<Arc<JournalStream>> {
+        self.streams.lock().unwrap().get(&channel_id).cloned()
+    }
+
+    pub(crate) fn close(&self, channel_id: u32) {
+        self.streams.lock().unwrap().remove(&channel_id);
+    }
+
+    /// Closes all streams of a window whose page is unloaded. Its channels are gone with the page
+    pub(crate) fn forget_window(&self, window: &str) {
+        self.streams
+            .lock()
+            .unwrap()
+            .retain(|_, x| x.window != window);
+    }
+
+    /// Queues `events` on the live streams of `plugin_id`. Streams whose webview no longer accepts frames are closed
+    pub(crate) fn push_live<T: Serialize>(
+        &self,
+        plugin_id: &str,
+        events: &[T],
+    ) -> Result<(), DearmorError> {
+        let live: Vec<_> = self
+            .streams
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, x)| x.live && x.plugin_id == plugin_id)
+            .map(|(id, x)| (*id, x.clone()))
+            .collect();
+        for (id, stream) in live {
+            match stream.push(events) {
+                Ok(()) => {}
+                Err(StreamError::Armor(e)) => return Err(e),
+                Err(e) => {
+                    warn!("closing journal stream of {plugin_id}: {e}");
+                    self.close(id);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    use aes_gcm::{aead::Aead, Aes128Gcm, KeyInit, Nonce};
+    use flate2::read::DeflateDecoder;
+    use tauri::ipc::InvokeResponseBody;
+
+    use super::*;
+
+    const KEY: [u8; 16] = [7; 16];
+
+    type Frames = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    /// A channel that records the frames it receives, and fails once `open` is cleared
+    fn channel() -> (Channel<Response>, Frames, Arc<AtomicBool>) {
+        let frames = Arc::new(Mutex::new(vec![]));
+        let open = Arc::new(AtomicBool::new(true));
+        let channel = Channel::new({
+            let frames = frames.clone();
+            let open = open.clone();
+            move |body| {
+                if !open.load(Ordering::SeqCst) {
+                    return Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe).into());
+                }
+                let InvokeResponseBody::Raw(bytes) = body else {
+                    panic!("frames must be sent as raw bytes");
+                };
+                frames.lock().unwrap().push(bytes);
+                Ok(())
+            }
+        });
+        (channel, frames, open)
+    }
+
+    /// Decodes a frame the way the facade does
+    fn decode(frame: &[u8]) -> serde_json::Value {
+        let (flags, rest) = frame.split_first().unwrap();
+        let (iv, encrypted) = rest.split_at(12);
+        let body = Aes128Gcm::new_from_slice(&KEY)
+            .unwrap()
+            .decrypt(Nonce::from_slice(iv), encrypted)
+            .unwrap();
+        let body = match *flags {
+            FLAG_DEFLATE => {
+                let mut inflated = vec![];
+                DeflateDecoder::new(&body[..])
+                    .read_to_end(&mut inflated)
+                    .unwrap();
+                inflated
+            }
+            0 => body,
+            x => panic!("unknown flags {x}"),
+        };
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn small_frames_are_encrypted_without_compression() {
+        let frame = encode_frame(&KEY, &["a", "b"], 3).unwrap();
+        assert_eq!(frame[0], 0);
+        assert_eq!(
+            decode(&frame),
+            serde_json::json!({"events": ["a", "b"], "dropped": 3})
+        );
+        // a fresh IV for every frame
+        assert_ne!(frame, encode_frame(&KEY, &["a", "b"], 3).unwrap());
+    }
+
+    #[test]
+    fn large_frames_are_compressed() {
+        let events = vec!["x".repeat(64); COMPRESS_THRESHOLD / 64 + 1];
+        let frame = encode_frame(&KEY, &events, 0).unwrap();
+        assert_eq!(frame[0], FLAG_DEFLATE);
+        assert!(frame.len() < COMPRESS_THRESHOLD);
+        assert_eq!(
+            decode(&frame),
+            serde_json::json!({"events": events, "dropped": 0})
+        );
+    }
+
+    #[test]
+    fn at_most_eight_frames_are_unacknowledged() {
+        let streams = JournalStreams::new();
+        let (channel, frames, _) = channel();
+        let stream = streams.open("main", "plugin", KEY, channel, true);
+        for i in 0..10 {
+            streams.push_live("plugin", &[i]).unwrap();
+        }
+        assert_eq!(frames.lock().unwrap().len(), MAX_UNACKED_FRAMES as usize);
+        assert_eq!(stream.flow.lock().unwrap().queue.len(), 2);
+
+        // acknowledging frees the window for the queued frames, in order
+        stream.ack(1).unwrap();
+        assert_eq!(frames.lock().unwrap().len(), 9);
+        stream.ack(3).unwrap();
+        let frames = frames.lock().unwrap();
+        assert_eq!(frames.len(), 10);
+        assert!(stream.flow.lock().unwrap().queue.is_empty());
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(decode(frame)["events"], serde_json::json!([i]));
+        }
+    }
+
+    #[test]
+    fn acks_are_clamped_to_sent_frames() {
+        let streams = JournalStreams::new();
+        let (channel, frames, _) = channel();
+        let stream = streams.open("main", "plugin", KEY, channel, true);
+        streams.push_live("plugin", &[0, 1]).unwrap();
+        streams.push_live("plugin", &[2]).unwrap();
+
+        // acknowledging frames that were never sent must not open the window further
+        stream.ack(100).unwrap();
+        assert_eq!(stream.flow.lock().unwrap().acked, 2);
+        // neither do stale acknowledgements shrink it again
+        stream.ack(1).unwrap();
+        assert_eq!(stream.flow.lock().unwrap().acked, 2);
+
+        for i in 0..10 {
+            streams.push_live("plugin", &[i]).unwrap();
+        }
+        assert_eq!(
+            frames.lock().unwrap().len(),
+            2 + MAX_UNACKED_FRAMES as usize
+        );
+    }
+
+    #[test]
+    fn acks_fail_once_the_channel_is_closed() {
+        let streams = JournalStreams::new();
+        let (channel, _, open) = channel();
+        let stream = streams.open("main", "plugin", KEY, channel, false);
+        for i in 0..=MAX_UNACKED_FRAMES {
+            stream.push(&[i]).unwrap();
+        }
+        open.store(false, Ordering::SeqCst);
+        assert!(matches!(stream.ack(1), Err(StreamError::Closed)));
+    }
+
+    #[test]
+    fn closed_live_streams_are_dropped() {
+        let streams = JournalStreams::new();
+        let (closed, _, open) = channel();
+        let closed = streams.open("main", "plugin", KEY, closed, true);
+        let (healthy, frames, _) = channel();
+        let healthy = streams.open("overlay", "plugin", KEY, healthy, true);
+        open.store(false, Ordering::SeqCst);
+
+        streams.push_live("plugin", &[1]).unwrap();
+        assert!(streams.get(closed.channel_id()).is_none());
+        assert!(streams.get(healthy.channel_id()).is_some());
+        assert_eq!(frames.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn live_streams_are_replaced_per_window() {
+        let streams = JournalStreams::new();
+        let first = streams.open("main", "plugin", KEY, channel().0, true);
+        let reread = streams.open("main", "plugin", KEY, channel().0, false);
+        let other = streams.open("main", "other", KEY, channel().0, true);
+        let second = streams.open("main", "plugin", KEY, channel().0, true);
+        assert!(streams.get(first.channel_id()).is_none());
+        for stream in [&reread, &other, &second] {
+            assert!(streams.get(stream.channel_id()).is_some());
+        }
+
+        streams.forget_window("main");
+        assert!(streams.get(second.channel_id()).is_none());
+    }
+}
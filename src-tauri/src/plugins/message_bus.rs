@@ -0,0 +1,226 @@
+//! Publish/subscribe messaging between plugins.
+//!
+//! Topics are namespaced by the plugin that owns them: a plugin with the ID `my-plugin` publishing on `status` publishes on `my-plugin/status`.
+//! Both sides are declared in the manifest (see [PluginMessageBus]): a plugin may only publish on topics it lists in `publishes`,
+//! and only receives messages of topics it lists in `subscribes`. `other-plugin/*` subscribes to all topics of `other-plugin`.
+//! Each topic also has to be granted by the user (see [super::permissions::granted_message_bus]).
+//!
+//! Messages are delivered to each running subscriber as a `core/plugins/bus_message` event, armored with the subscriber's key.
+//! If a message is published with `retain`, it is kept as the topic's last message, so plugins that start later can catch up via `fetch_retained_bus_messages`.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Runtime};
+
+use super::{
+    contracts::{BusMessageReceived, CommandError},
+    permissions::granted_message_bus,
+    PluginCurrentState, PluginsState,
+};
+
+/// The message bus section of a plugin manifest
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema, Default)]
+pub(crate) struct PluginMessageBus {
+    /// Topic names this plugin publishes on, without the plugin ID. Only lowercase letters, digits, dashes, underscores and dots are allowed
+    #[serde(default)]
+    pub(crate) publishes: Vec<String>,
+    /// Full topic names this plugin subscribes to, e.g. `other-plugin/status` or `other-plugin/*`
+    #[serde(default)]
+    pub(crate) subscribes: Vec<String>,
+}
+
+impl PluginMessageBus {
+    /// Returns whether a plugin with this config receives messages published on `topic` (a full topic name)
+    pub(crate) fn subscribes_to(&self, topic: &str) -> bool {
+        self.subscribes.iter().any(|x| match x.strip_suffix("/*") {
+            Some(owner) => topic
+                .strip_prefix(owner)
+                .is_some_and(|rest| rest.starts_with('/')),
+            None => x == topic,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BusMessage {
+    /// Full topic name, i.e. `$publisher/$name`
+    pub(crate) topic: String,
+    pub(crate) publisher: String,
+    pub(crate) payload: Value,
+    pub(crate) published_at: DateTime<Utc>,
+}
+
+/// Returns the full topic name `publisher` publishes on, or the reason it may not publish on `name`.
+/// `config` is the publisher's granted message bus config
+pub(crate) fn resolve_topic(
+    publisher: &str,
+    config: Option<&PluginMessageBus>,
+    name: &str,
+) -> Result<String, CommandError> {
+    if name.is_empty()
+        || !name.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_' || c == '.'
+        })
+    {
+        return Err(CommandError::TopicInvalid);
+    }
+    let declared = config.is_some_and(|x| x.publishes.iter().any(|x| x == name));
+    match declared {
+        true => Ok(format!("{publisher}/{name}")),
+        false => Err(CommandError::TopicNotDeclared),
+    }
+}
+
+/// Managed by Tauri. Holds the retained message of each topic
+pub(crate) struct MessageBus {
+    retained: Mutex<BTreeMap<String, BusMessage>>,
+}
+
+impl MessageBus {
+    pub(crate) fn new() -> Self {
+        Self {
+            retained: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Delivers `message` to all running plugins that subscribe to its topic. If `retain` is set, it replaces the topic's retained message
+    pub(crate) fn publish<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        state: &PluginsState,
+        message: BusMessage,
+        retain: bool,
+    ) -> anyhow::Result<()> {
+        if retain {
+            self.retained
+                .lock()
+                .unwrap()
+                .insert(message.topic.clone(), message.clone());
+        }
+
+        let recipients = state
+            .plugin_states
+            .values()
+            .filter(|x| x.current_state == PluginCurrentState::Running {})
+            .filter(|x| {
+                granted_message_bus(app_handle, x).is_some_and(|x| x.subscribes_to(&message.topic))
+            });
+        for recipient in recipients {
+            state
+                .emit_to_plugin::<BusMessageReceived, _>(
+                    app_handle,
+                    &["main"],
+                    &recipient.id,
+                    &message,
+                )
+                .map_err(|e| anyhow::anyhow!("failed to encrypt bus message: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the retained messages of all topics a plugin with the (granted) message bus `config` subscribes to
+    pub(crate) fn retained_for(&self, config: &PluginMessageBus) -> Vec<BusMessage> {
+        self.retained
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|x| config.subscribes_to(&x.topic))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bus(publishes: &[&str], subscribes: &[&str]) -> PluginMessageBus {
+        PluginMessageBus {
+            publishes: publishes.iter().map(|x| x.to_string()).collect(),
+            subscribes: subscribes.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    fn message(topic: &str) -> BusMessage {
+        BusMessage {
+            topic: topic.to_string(),
+            publisher: topic.split('/').next().unwrap().to_string(),
+            payload: Value::Null,
+            published_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn topics_are_namespaced_by_their_publisher() {
+        let config = bus(&["status", "fsd.jump"], &[]);
+        assert_eq!(
+            resolve_topic("my-plugin", Some(&config), "status").unwrap(),
+            "my-plugin/status"
+        );
+        assert_eq!(
+            resolve_topic("my-plugin", Some(&config), "fsd.jump").unwrap(),
+            "my-plugin/fsd.jump"
+        );
+    }
+
+    #[test]
+    fn plugins_cannot_publish_on_foreign_topics() {
+        let config = bus(&["status", "other-plugin/status"], &[]);
+        // a `/` would leave the publisher's namespace, even if it is declared
+        for name in ["other-plugin/status", "../status", "", "Status", "a b"] {
+            assert!(
+                matches!(
+                    resolve_topic("my-plugin", Some(&config), name),
+                    Err(CommandError::TopicInvalid)
+                ),
+                "{name} must be rejected"
+            );
+        }
+        assert!(matches!(
+            resolve_topic("my-plugin", Some(&config), "other"),
+            Err(CommandError::TopicNotDeclared)
+        ));
+        // without a granted config, nothing is declared
+        assert!(matches!(
+            resolve_topic("my-plugin", None, "status"),
+            Err(CommandError::TopicNotDeclared)
+        ));
+    }
+
+    #[test]
+    fn wildcards_only_match_the_owners_topics() {
+        let config = bus(&[], &["other/*", "third/status"]);
+        assert!(config.subscribes_to("other/status"));
+        assert!(config.subscribes_to("other/fsd.jump"));
+        assert!(config.subscribes_to("third/status"));
+
+        assert!(!config.subscribes_to("other-plugin/status"));
+        assert!(!config.subscribes_to("otherx/status"));
+        assert!(!config.subscribes_to("other"));
+        assert!(!config.subscribes_to("third/status2"));
+        assert!(!config.subscribes_to("third/other"));
+    }
+
+    #[test]
+    fn retained_messages_follow_subscriptions() {
+        let bus_state = MessageBus::new();
+        {
+            let mut retained = bus_state.retained.lock().unwrap();
+            for topic in ["other/a", "other/b", "other-plugin/a", "third/a"] {
+                retained.insert(topic.to_string(), message(topic));
+            }
+        }
+        let topics = bus_state
+            .retained_for(&bus(&[], &["other/*", "third/b"]))
+            .into_iter()
+            .map(|x| x.topic)
+            .collect::<Vec<_>>();
+        assert_eq!(topics, vec!["other/a", "other/b"]);
+        assert!(bus_state.retained_for(&bus(&[], &[])).is_empty());
+    }
+}
@@ -3,62 +3,107 @@ use std::{
     fmt::Debug,
     fs::File,
     path::{Path, PathBuf},
-    str::FromStr,
     sync::{Arc, OnceLock},
     time::Duration,
 };
 
 use anyhow::anyhow;
 use base64::{prelude::BASE64_STANDARD_NO_PAD, Engine};
-use chrono::{TimeDelta, Utc};
-use dirs::data_local_dir;
-use generic_plugin_settings::GenericPluginSettings;
-use get_dir_hash::Options;
+use chrono::{DateTime, TimeDelta, Utc};
+use contracts::{
+    CommandError, Event, GetRootTokenOnce, PermissionRequest, PermissionsRequested,
+    PluginQuarantined, PluginSettingsUpdated, PluginUpdate, PluginUpdated, Quarantine,
+    SettingValue, SettingsUpdated, TransitionExpired, TransitionExpiry,
+};
+use dev_plugins::{DevPluginLinks, DevPluginWatchers};
+use embedded_plugins::{EmbeddedPlugin, EMBEDDED_PLUGINS};
+use frontend_hash::{FrontendHashCache, FrontendHashes};
+use generic_plugin_settings::{GenericPluginSettings, TransitionTimeouts};
 use itertools::Itertools;
+use journal_stream::JournalStreams;
+use message_bus::MessageBus;
 use notify::{RecommendedWatcher, Watcher};
+use package_signature::{PluginVerification, TrustStore};
+use permissions::Permission;
 use plugin_manifest::PluginManifest;
-use rand::RngCore;
-use reconciler_utils::ReconcileAction;
+use plugin_roots::{search_roots, PluginRoot, PluginRootKind};
+use reconciler_utils::{EventEmit, ReconcileAction};
 use schemars::JsonSchema;
-use serde::Serialize;
-use serde_json::{json, Value};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use sidecar::{SidecarSpec, SidecarSupervisor};
 use tauri::{path::BaseDirectory, AppHandle, Manager, Runtime, Wry};
 use tauri_plugin_store::StoreExt;
 use tokio::sync::RwLock;
-use tracing::{error, info, instrument, warn};
+use tracing::{error, info, info_span, instrument, warn};
+use wasm_backend::{WasmBackendSpec, WasmBackends};
+
+use crate::{
+    event_watchdog,
+    portable::{default_plugin_dir, store_path},
+};
 
+pub(crate) mod audit_log;
+pub(crate) mod bindings;
 pub(crate) mod commands;
 pub(crate) mod commands_armor;
+pub(crate) mod contracts;
+pub(crate) mod dev_plugins;
+pub(crate) mod embedded_plugins;
+pub(crate) mod frontend_hash;
 pub(crate) mod frontend_server;
 pub(crate) mod generic_plugin_settings;
+pub(crate) mod journal_stream;
+pub(crate) mod message_bus;
+pub(crate) mod package_signature;
+pub(crate) mod permissions;
+pub(crate) mod plugin_files;
 pub(crate) mod plugin_manifest;
+pub(crate) mod plugin_roots;
 pub(crate) mod plugin_settings;
+pub(crate) mod rate_limits;
 mod reconciler_utils;
+pub(crate) mod sidecar;
+pub(crate) mod url_policy;
+pub(crate) mod wasm_backend;
 
-/// Lazy-init'd list of all internal plugins. There might be better ways to do it, but for now this is hand-adjusted.
-/// If we add a plugin here we **MUST** also ensure that it is present in the plugins folder at
-/// `assets/plugins/$pluginID`
+/// Lazy-init'd list of all internal plugins. This is generated by `build.rs` from the folders in `assets/plugins`, see [embedded_plugins]
 pub(crate) fn internal_plugin_ids() -> &'static [&'static str] {
     static MEM: OnceLock<Vec<&str>> = OnceLock::new();
-    MEM.get_or_init(|| vec!["core"]).as_slice()
+    MEM.get_or_init(|| EMBEDDED_PLUGINS.iter().map(|x| x.id).collect())
+        .as_slice()
+}
+
+/// If a plugin reports a failure within this period after it reached [PluginCurrentState::Running], it is treated as a failed start
+const CRASH_GRACE_PERIOD: TimeDelta = TimeDelta::seconds(30);
+
+/// Managed by Tauri once the reconciler is running. Commands use this to request a reconcile, e.g. after the Trust Store changed.
+#[derive(Clone)]
+pub(crate) struct ReconcileTrigger(std::sync::mpsc::Sender<bool>);
+
+impl ReconcileTrigger {
+    pub(crate) fn trigger(&self) {
+        if let Err(e) = self.0.send(true) {
+            warn!("failed to trigger plugin reconcile: {e}")
+        }
+    }
 }
 
 /// This function never finishes. It spawns a reconciler, then polls in loop for changes. Expected to be run in a thread / task.
 #[instrument(skip(app_state))]
 pub(super) async fn spawn_reconciler_blocking(app_state: &AppHandle<Wry>) -> () {
     let (tx, rx) = std::sync::mpsc::channel();
-
-    let user_plugin_dir = app_state
-        .store("store.json")
-        .map_err(|x| anyhow!("couldn't get store: {x}"))
-        .unwrap()
-        .get("plugin_dir")
-        .and_then(|x| {
-            let x = x.to_string();
-            PathBuf::from_str(&x).ok()
-        })
-        .unwrap_or(data_local_dir().unwrap().join("edpf-plugins"));
-    let moved_user_plugin_dir = user_plugin_dir.clone();
+    app_state.manage(ReconcileTrigger(tx.clone()));
+    let hash_cache = Arc::new(FrontendHashCache::new());
+    app_state.manage(hash_cache.clone());
+    app_state.manage(DevPluginWatchers::new());
+    app_state.manage(SidecarSupervisor::new());
+    app_state.manage(WasmBackends::new());
+    app_state.manage(MessageBus::new());
+
+    // The plugin roots can be changed at runtime, see [sync_root_watches]
+    let watched_roots: Arc<std::sync::RwLock<Vec<PathBuf>>> = Default::default();
+    let moved_watched_roots = watched_roots.clone();
 
     // Note that we only watch for changes in User plugins.
     // We make the assumption that bundled plugins do not change over time.
@@ -69,7 +114,12 @@ pub(super) async fn spawn_reconciler_blocking(app_state: &AppHandle<Wry>) -> ()
                     notify::EventKind::Create(_)
                     | notify::EventKind::Modify(_)
                     | notify::EventKind::Remove(_) => {
-                        matches_relevant_files(&ev.paths, &moved_user_plugin_dir)
+                        hash_cache.invalidate(&ev.paths);
+                        moved_watched_roots
+                            .read()
+                            .unwrap()
+                            .iter()
+                            .any(|base| matches_relevant_files(&ev.paths, base))
                     }
                     _ => false,
                 };
@@ -87,126 +137,303 @@ pub(super) async fn spawn_reconciler_blocking(app_state: &AppHandle<Wry>) -> ()
     )
     .unwrap();
     {
+        let frontend_hashes = hash_user_frontends(app_state).await;
         let state = app_state.state::<Arc<RwLock<PluginsState>>>();
         info!("Running Plugin reconciler…");
         let mut state: tokio::sync::RwLockWriteGuard<'_, PluginsState> = state.write().await;
-        if let Err(e) = state.reconcile(app_state).await {
+        if let Err(e) = state.reconcile(app_state, &frontend_hashes).await {
             error!("plugin state reconcile failed: {e}")
         }
     }
 
-    // Check that the Plugin Dir exists. If it doesn't, create it.
-    if !user_plugin_dir.exists() {
-        info!(
-            "User Plugin Directory doesn't exist yet. Attempting to create directory at {}",
-            user_plugin_dir.display()
-        );
-        std::fs::create_dir_all(&user_plugin_dir).unwrap();
-    }
-    watcher
-        .watch(&user_plugin_dir, notify::RecursiveMode::Recursive)
-        .unwrap();
+    sync_root_watches(app_state, &mut watcher, &watched_roots);
 
     let mut last_reconciled = Utc::now();
+    // Set if a trigger was debounced. We then only wait briefly, so the last change of e.g. a rebuild is not lost
+    let mut pending = false;
 
     loop {
         {
-            let trigger_reconcile = rx.recv_timeout(Duration::from_secs(30)).unwrap_or(true);
+            let timeout = match pending {
+                true => Duration::from_secs(1),
+                false => Duration::from_secs(30),
+            };
+            let trigger_reconcile = rx.recv_timeout(timeout).unwrap_or(true);
             if !trigger_reconcile || Utc::now() - last_reconciled < TimeDelta::seconds(1) {
                 // We debounce events here
+                pending |= trigger_reconcile;
                 continue;
             }
+            pending = false;
             last_reconciled = Utc::now();
+            sync_root_watches(app_state, &mut watcher, &watched_roots);
+            let frontend_hashes = hash_user_frontends(app_state).await;
             let state = app_state.state::<Arc<RwLock<PluginsState>>>();
             info!("Running Plugin reconciler…");
             let mut state: tokio::sync::RwLockWriteGuard<'_, PluginsState> = state.write().await;
-            if let Err(e) = state.reconcile(app_state).await {
+            if let Err(e) = state.reconcile(app_state, &frontend_hashes).await {
                 error!("plugin state reconcile failed: {e}")
             }
         }
     }
 }
 
-/// Returns true if any of the paths matches:
-/// 1. $base/*/manifest.json
-/// 2. $base/*/frontend/**
-fn matches_relevant_files(paths: &[PathBuf], base: &Path) -> bool {
-    paths.iter().any(|p| {
-        // Must start with the base directory
-        if !p.starts_with(base) {
-            return false;
+/// Makes sure the watcher targets exactly the configured plugin roots (see [search_roots]).
+///
+/// The user plugin dir is created if it does not exist yet. Other roots are considered read-only: if they do not exist, they are retried on the next call.
+/// The watched roots are shared with the watcher callback, so it filters events against the right bases.
+fn sync_root_watches<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    watcher: &mut RecommendedWatcher,
+    watched_roots: &std::sync::RwLock<Vec<PathBuf>>,
+) {
+    let roots = match search_roots(app_handle) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("failed to get plugin roots: {e}");
+            return;
         }
+    };
+    let mut watched_roots = watched_roots.write().unwrap();
 
-        // Get the path components *after* the base
-        let mut comps = match p.strip_prefix(base) {
-            Ok(x) => x,
-            Err(_) => return false,
+    watched_roots.retain(|watched| {
+        if roots.iter().any(|x| &x.path == watched) {
+            return true;
+        }
+        info!("No longer watching plugin root {}", watched.display());
+        if let Err(e) = watcher.unwatch(watched) {
+            warn!("failed to stop watching {}: {e}", watched.display());
         }
-        .components()
-        .peekable();
+        false
+    });
 
-        // Must have at least one component after base
-        match comps.next() {
-            Some(_) => {}
-            None => return false,
-        };
+    for root in roots {
+        if watched_roots.contains(&root.path) {
+            continue;
+        }
+        if !root.path.exists() {
+            if root.kind != PluginRootKind::User {
+                warn!("Plugin root {} does not exist", root.path.display());
+                continue;
+            }
+            // Check that the Plugin Dir exists. If it doesn't, create it.
+            info!(
+                "User Plugin Directory doesn't exist yet. Attempting to create directory at {}",
+                root.path.display()
+            );
+            if let Err(e) = std::fs::create_dir_all(&root.path) {
+                error!("failed to create user plugin dir: {e}");
+                continue;
+            }
+        }
+        match watcher.watch(&root.path, notify::RecursiveMode::Recursive) {
+            Ok(_) => {
+                info!(
+                    "Watching {:?} plugin root {}",
+                    root.kind,
+                    root.path.display()
+                );
+                watched_roots.push(root.path)
+            }
+            Err(e) => error!("failed to watch plugin root {}: {e}", root.path.display()),
+        }
+    }
+}
+
+/// Reads the user plugin directory from the store, falling back to the default location
+pub(crate) fn user_plugin_dir<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<PathBuf> {
+    Ok(app_handle
+        .store(store_path("store.json"))
+        .map_err(|x| anyhow!("couldn't get store: {x}"))?
+        .get("plugin_dir")
+        .and_then(|x| x.as_str().map(PathBuf::from))
+        .unwrap_or_else(default_plugin_dir))
+}
 
-        // Match on $path/*/manifest.json
-        if comps.clone().count() == 1 {
-            if let Some(last) = comps.peek() {
-                if last.as_os_str() == "manifest.json" {
-                    return true;
+/// Persists a new user plugin directory. It is picked up by the next reconcile, which also re-targets the watcher.
+pub(crate) fn set_user_plugin_dir<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin_dir: &Path,
+) -> anyhow::Result<()> {
+    let plugin_dir = plugin_dir
+        .to_str()
+        .ok_or(anyhow!("plugin dir is not valid UTF-8"))?;
+    app_handle
+        .store(store_path("store.json"))
+        .map_err(|x| anyhow!("couldn't get store: {x}"))?
+        .set("plugin_dir", plugin_dir);
+    Ok(())
+}
+
+/// Moves every plugin folder (a folder containing a `manifest.json`) from `from` into `to`.
+///
+/// This is best-effort: folders that already exist in `to` or cannot be moved are left in place. Their names are returned.
+pub(crate) fn move_user_plugins(from: &Path, to: &Path) -> anyhow::Result<Vec<String>> {
+    let mut not_moved = vec![];
+    for manifest in glob::glob(from.join("*/manifest.json").to_str().unwrap())?.flatten() {
+        let Some(source) = manifest.parent() else {
+            continue;
+        };
+        let name = source.file_name().unwrap().to_string_lossy().to_string();
+        let target = to.join(&name);
+        if target.exists() {
+            warn!(
+                "not moving plugin {name}, as {} already exists",
+                target.display()
+            );
+            not_moved.push(name);
+            continue;
+        }
+        let result = match std::fs::rename(source, &target) {
+            Ok(_) => Ok(()),
+            // Renaming fails across file systems, in which case we copy instead
+            Err(_) => match copy_dir_recursive(source, &target) {
+                Ok(_) => {
+                    if let Err(e) = std::fs::remove_dir_all(source) {
+                        warn!(
+                            "copied plugin {name}, but failed to remove {}: {e}",
+                            source.display()
+                        );
+                    }
+                    Ok(())
                 }
-            }
+                Err(e) => {
+                    // The target did not exist before, so we only remove our partial copy
+                    _ = std::fs::remove_dir_all(&target);
+                    Err(e)
+                }
+            },
+        };
+        if let Err(e) = result {
+            error!("failed to move plugin {name} to {}: {e}", target.display());
+            not_moved.push(name);
         }
+    }
+    Ok(not_moved)
+}
 
-        // Match on $path/*/frontend/**
-        if let Some(first_after) = comps.peek() {
-            if first_after.as_os_str() == "frontend" {
-                // Anything under frontend/, including nested directories, matches
-                return true;
-            }
+fn copy_dir_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_recursive(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
         }
+    }
+    Ok(())
+}
 
-        false
+/// Returns why `plugin_id` cannot be used as the ID of a user or dev plugin, if that is the case
+pub(crate) fn plugin_id_problem(plugin_id: &str) -> Option<BrokenPluginReason> {
+    if !plugin_id
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        Some(BrokenPluginReason::InvalidPluginId {
+            message: "only lowercase letters, digits and dashes are allowed".into(),
+        })
+    } else if internal_plugin_ids().contains(&plugin_id) {
+        Some(BrokenPluginReason::DuplicateId {
+            conflicting_dir: None,
+        })
+    } else {
+        None
+    }
+}
+
+/// Hashes the frontends and checks the signatures of all plugins in every plugin root, and of all dev plugins.
+/// This is the expensive part of a reconcile, so it runs on a blocking thread before the [PluginsState] lock is taken.
+async fn hash_user_frontends(app_handle: &AppHandle<Wry>) -> FrontendHashes {
+    let roots = match search_roots(app_handle) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("failed to get plugin roots: {e}");
+            return FrontendHashes::new();
+        }
+    };
+    let dev_plugin_dirs = DevPluginLinks::load(app_handle)
+        .map(|x| x.0.into_iter().collect_vec())
+        .unwrap_or_else(|e| {
+            error!("failed to load dev plugin links: {e}");
+            vec![]
+        });
+    let hash_cache = app_handle.state::<Arc<FrontendHashCache>>().inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        // User plugins are identified by their folder name, same as in the reconcile
+        let plugin_dirs = roots
+            .iter()
+            .flat_map(|x| FrontendHashCache::plugin_dirs_in(&x.path))
+            .filter_map(|x| Some((x.file_name()?.to_string_lossy().to_string(), x)))
+            .chain(dev_plugin_dirs);
+        let mut hashes = FrontendHashes::new();
+        for (plugin_id, plugin_dir) in plugin_dirs {
+            let _entered = info_span!("hash_plugin_dir", plugin_id = %plugin_id).entered();
+            let digest = hash_cache.digest_plugin_dir(&plugin_dir);
+            hashes.insert(plugin_dir, digest);
+        }
+        hashes
+    })
+    .await
+    .unwrap_or_else(|e| {
+        error!("failed to hash plugin frontends: {e}");
+        FrontendHashes::new()
+    })
+}
+
+/// Returns true if any of the paths is part of the package listing of a plugin in `base` (see [package_signature::affects_listing]), e.g.
+/// 1. $base/*/manifest.json
+/// 2. $base/*/frontend/**
+/// 3. the executables declared in $base/*/manifest.json
+fn matches_relevant_files(paths: &[PathBuf], base: &Path) -> bool {
+    paths.iter().any(|p| {
+        // The first component after the base is the plugin folder
+        let plugin_dir = match p
+            .strip_prefix(base)
+            .ok()
+            .and_then(|x| x.components().next())
+        {
+            Some(x) => base.join(x),
+            None => return false,
+        };
+        package_signature::affects_listing(&plugin_dir, p)
     })
 }
 
 #[derive(Serialize, Clone)]
 pub(crate) struct PluginsState {
     plugin_states: HashMap<String, PluginState>,
-    /// This is the "admin" token, if you want. It's an AES-128 GCM Cipher. We pass this cipher to the main and settings windows **before** the contexts are tainted by importing plugins.
+    /// Plugins that were found during the last reconcile but could not be loaded. See [BrokenPlugin]
+    broken_plugins: Vec<BrokenPlugin>,
+    /// Incremented every time a [ReconcileAction] is applied to a plugin. Used to detect if a plugin is still in the same transition once its deadline expires.
+    #[serde(skip_serializing)]
+    transition_generations: HashMap<String, u64>,
+    /// When a plugin last reached [PluginCurrentState::Running]. Used to tell a crash right after starting apart from a plugin that fails much later.
+    #[serde(skip_serializing)]
+    running_since: HashMap<String, DateTime<Utc>>,
+    /// These are the "admin" tokens, if you want. Each is an AES-128 GCM Cipher. We pass them to the main and settings windows **before** the contexts are tainted by importing plugins.
     ///
-    /// Commands and Events are encrypted with this Cipher. This way, Plugins cannot just invoke window.__TAURI or import Tauri and invoke commands at will and must go through the Facade provided
+    /// Commands and Events are encrypted with these Ciphers. This way, Plugins cannot just invoke window.__TAURI or import Tauri and invoke commands at will and must go through the Facade provided
     /// to them by EDPF.
     ///
-    /// The only way to get this Token is by calling the [get_root_token_once] command. As the name implies, this can be only called once **PER WINDOW LIFECYCLE**. Subsequent requests are rejected.
-    /// The backend listens for Webview Reloads and resets the lock if a Reload is completed. This is tracked in [PluginsState::allow_request_root_key_main] and [PluginsState::allow_request_root_key_settings] respectively.
+    /// The only way to get a Token is by calling the [get_root_token_once] command. As the name implies, this can be only called once **PER WINDOW LIFECYCLE**. Subsequent requests are rejected.
+    /// The backend listens for Webview Reloads and replaces the window's token when the page starts loading (see [PluginsState::rearm_root_token]), so a token never outlives its page.
     ///
     /// Because the main window is called before any plugins, it can acquire it first. If a Plugin somehow manages to call [get_root_token_once], that call is rejected.
+    ///
+    /// Plugin facades don't get the root token, but a key derived from it (see [commands_armor::plugin_key]).
     #[serde(skip_serializing)]
-    root_token: [u8; 16],
-    /// intialized to false. When we receive the signal that the main window is ready, this is set to `true`. The main window may then invoke [get_root_token_once] to get the [PluginsState::root_token], which it needs to decrypt events and invoke commands.
-    #[serde(skip_serializing)]
-    pub(super) allow_request_root_key_main: bool,
-    /// see [PluginsState::allow_request_root_key_main] - the difference is that this concerns the settings Window
-    #[serde(skip_serializing)]
-    pub(super) allow_request_root_key_settings: bool,
+    root_tokens: commands_armor::RootTokens,
 }
 
 impl Debug for PluginsState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("PluginsState")
             .field("plugin_states", &self.plugin_states)
-            .field(
-                "allow_request_root_key_main",
-                &self.allow_request_root_key_main,
-            )
-            .field(
-                "allow_request_root_key_settings",
-                &self.allow_request_root_key_settings,
-            )
-            .finish()
+            .field("broken_plugins", &self.broken_plugins)
+            .finish_non_exhaustive()
     }
 }
 
@@ -218,7 +445,7 @@ impl Debug for PluginsState {
 /// They can fetch the root token and store it internally, outside of the plugin's reach.
 ///
 /// If a rogue plugin would try to request this token afterwards, it gets rejected, as loading this token is only allowed once per Webview Load.
-/// if the window gets unloaded and reloaded, a Tauri-internal event is emitted. After the reload, we can assume that the context is untainted again, which is why we set back [PluginsState::allow_request_root_key_main] and [PluginsState::allow_request_root_key_settings].
+/// if the window gets unloaded and reloaded, a Tauri-internal event is emitted. After the reload, we can assume that the context is untainted again, which is why the window gets a new token it may request (see [PluginsState::rearm_root_token]).
 ///
 #[tauri::command]
 pub(crate) async fn get_root_token_once<R: Runtime>(
@@ -229,45 +456,25 @@ pub(crate) async fn get_root_token_once<R: Runtime>(
 
     let mut data = state.write().await;
 
-    let is_main = match window.label() {
-        "main" => true,
-        "settings" => false,
-        _ => {
-            return Ok(json!({"success": false, "reason": "CALLED_FROM_INVALID_WINDOW"}));
-        }
-    };
-
-    let can_request = match is_main {
-        true => data.allow_request_root_key_main,
-        false => data.allow_request_root_key_settings,
+    let response = match window.label() {
+        "main" | "settings" => match data.root_tokens.take(window.label()) {
+            Some(x) => Ok(BASE64_STANDARD_NO_PAD.encode(x)),
+            None => Err(CommandError::TokenAlreadyRequested),
+        },
+        _ => Err(CommandError::CalledFromInvalidWindow),
     };
-
-    if !can_request {
-        // During dev we might reload the window. To not cause any annoyances and having the fully restart the app, we ignore the case that
-        // the root token was already requested.
-        return Ok(json!({"success": false, "reason": "TOKEN_ALREADY_REQUESTED"}));
-    }
-
-    match is_main {
-        true => data.allow_request_root_key_main = false,
-        false => data.allow_request_root_key_settings = false,
-    }
-
-    let encoded_token = BASE64_STANDARD_NO_PAD.encode(&data.root_token);
-    Ok(json!({"success": true, "data":  encoded_token}))
+    Ok(contracts::unarmored(GetRootTokenOnce, response))
 }
 
 impl PluginsState {
     /// this just creates an empty hashmap. Use reconcile function to sync the states
     pub(crate) fn new() -> Self {
-        let mut root_token = [0u8; 16];
-        rand::rng().fill_bytes(&mut root_token);
-
         Self {
             plugin_states: HashMap::new(),
-            root_token,
-            allow_request_root_key_main: false,
-            allow_request_root_key_settings: false,
+            broken_plugins: vec![],
+            transition_generations: HashMap::new(),
+            running_since: HashMap::new(),
+            root_tokens: commands_armor::RootTokens::default(),
         }
     }
 
@@ -275,6 +482,164 @@ impl PluginsState {
         self.plugin_states.get(id).cloned()
     }
 
+    /// Decrypts the payload of a privileged command. Only the root tokens are accepted
+    pub(crate) fn decrypt<T: DeserializeOwned>(
+        &self,
+        iv: &str,
+        payload: &str,
+    ) -> Result<(commands_armor::Caller, T), commands_armor::DearmorError> {
+        self.root_tokens.decrypt(iv, payload)
+    }
+
+    /// Decrypts the payload of a command plugins may invoke, accepting the root tokens and the key of each known plugin.
+    /// See [commands_armor::RootTokens::decrypt_as_caller]
+    pub(crate) fn decrypt_as_caller<T: DeserializeOwned>(
+        &self,
+        iv: &str,
+        payload: &str,
+    ) -> Result<(commands_armor::Caller, T), commands_armor::DearmorError> {
+        self.root_tokens.decrypt_as_caller(
+            self.plugin_states.keys().map(String::as_str),
+            iv,
+            payload,
+        )
+    }
+
+    /// Emits `payload` to all windows, armored with each window's root token
+    pub(crate) fn emit_armored<E: Event, R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        payload: &E::Payload,
+    ) -> Result<(), commands_armor::DearmorError> {
+        self.root_tokens.emit(app_handle, E::NAME, payload)
+    }
+
+    /// Emits `payload` to a single window, armored with its root token
+    pub(crate) fn emit_armored_to<E: Event, R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        window: &str,
+        payload: &E::Payload,
+    ) -> Result<(), commands_armor::DearmorError> {
+        self.root_tokens
+            .emit_to_window(app_handle, window, E::NAME, payload)
+    }
+
+    /// Emits `payload` to the facade of a plugin, armored with its key. See [commands_armor::RootTokens::emit_to_plugin]
+    pub(crate) fn emit_to_plugin<E: Event, R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        windows: &[&str],
+        plugin_id: &str,
+        payload: &E::Payload,
+    ) -> Result<(), commands_armor::DearmorError> {
+        self.root_tokens
+            .emit_to_plugin(app_handle, windows, plugin_id, E::NAME, payload)
+    }
+
+    /// Notifies about a changed setting: the core frontend gets `settings_update`, every plugin that may read the setting gets `core/plugins/settings_update`.
+    /// Plugin settings run in both windows, so both get the event
+    pub(crate) fn emit_settings_update<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        key: &plugin_settings::ParsedKey,
+        payload: &SettingValue,
+    ) -> Result<(), commands_armor::DearmorError> {
+        self.emit_armored::<SettingsUpdated, _>(app_handle, payload)?;
+        for plugin in self.plugin_states.values() {
+            let may_read = key.is_readable_by(&plugin.id)
+                && (key.plugin_id() == plugin.id
+                    || permissions::is_granted(
+                        app_handle,
+                        plugin,
+                        &Permission::SettingsRead {
+                            plugin_id: key.plugin_id().to_string(),
+                        },
+                    ));
+            if may_read {
+                self.emit_to_plugin::<PluginSettingsUpdated, _>(
+                    app_handle,
+                    &["main", "settings"],
+                    &plugin.id,
+                    payload,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hands a batch of journal events to the live journal streams, the sidecar and the WASM backend of each running plugin, reduced to the event classes it was granted (see [Permission::JournalEvents])
+    pub(crate) fn emit_journal_events<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        events: &[event_watchdog::LogEventWithContext],
+    ) -> Result<(), commands_armor::DearmorError> {
+        let classes: Vec<Option<String>> = events
+            .iter()
+            .map(|x| {
+                serde_json::from_str::<Value>(&x.event)
+                    .ok()
+                    .and_then(|x| x.get("event")?.as_str().map(str::to_string))
+            })
+            .collect();
+        for plugin in self
+            .plugin_states
+            .values()
+            .filter(|x| x.current_state == PluginCurrentState::Running {})
+        {
+            let granted = permissions::granted(app_handle, plugin);
+            let filtered: Vec<_> = events
+                .iter()
+                .zip(&classes)
+                .filter(|(_, class)| {
+                    class.as_ref().is_some_and(|class| {
+                        granted.iter().any(|x| {
+                            x.covers(&Permission::JournalEvents {
+                                class: class.clone(),
+                            })
+                        })
+                    })
+                })
+                .map(|(x, _)| x)
+                .collect();
+            if filtered.is_empty() {
+                continue;
+            }
+            if let Some(sidecars) = app_handle.try_state::<SidecarSupervisor>() {
+                if let Err(CommandError::SidecarBusy) =
+                    sidecars.notify(&plugin.id, "journalEvents", &filtered)
+                {
+                    warn!(
+                        "dropping journal events for the sidecar of {}: its queue is full",
+                        plugin.id
+                    );
+                }
+            }
+            if let Some(backends) = app_handle.try_state::<WasmBackends>() {
+                backends.send_journal_events(&plugin.id, &filtered);
+            }
+            // A broken stream of one plugin must not keep the events from the others
+            if let Err(e) = app_handle
+                .state::<JournalStreams>()
+                .push_live(&plugin.id, &filtered)
+            {
+                error!("failed to push journal events to {}: {e}", plugin.id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Called when a page starts loading in `window`. Rotates the window's root token, as the old one may have leaked to the unloaded page,
+    /// and lets the new page request the new one via [get_root_token_once]
+    pub(crate) fn rearm_root_token(&mut self, window: &str) {
+        self.root_tokens.rearm(window);
+    }
+
+    /// Called when a window is closed, so its root token is no longer accepted
+    pub(crate) fn forget_root_token(&mut self, window: &str) {
+        self.root_tokens.remove(window);
+    }
+
     /// Indicates a Plugin wants to stop (e.g. when user presses to Stop button)
     /// Once ack'd, front and backend start unloading resources.
     /// The stop is finished when the [PluginsState::finalize_stop] is invoked.
@@ -283,32 +648,51 @@ impl PluginsState {
         id: String,
         app_handle: &AppHandle<R>,
     ) -> anyhow::Result<()> {
-        let maybe_event = ReconcileAction::Stop { plugin_id: id }.apply(self)?;
+        let maybe_event = ReconcileAction::Stop {
+            plugin_id: id.clone(),
+        }
+        .apply(self)?;
+        self.schedule_transition_deadline(app_handle, &id);
+        self.sync_backends(app_handle);
         if let Some(x) = maybe_event {
             tokio::time::sleep(Duration::from_millis(20)).await;
-            x.emit(app_handle)?;
+            x.emit(app_handle, self)?;
         }
         Ok(())
     }
 
-    /// This method indicates the final stop in stopping a plugin
+    /// This method indicates the final stop in stopping a plugin.
+    ///
+    /// If the plugin is [PluginCurrentState::Restarting], this acknowledges the unload and the plugin is started again.
     pub(crate) async fn finalize_stop<R: Runtime>(
         &mut self,
         id: String,
         app_handle: &AppHandle<R>,
     ) -> anyhow::Result<()> {
-        let maybe_event = ReconcileAction::SyncInPlace {
-            plugin_id: id,
-            patch: Box::new(|x| x.current_state = PluginCurrentState::Disabled {}),
-        }
-        .apply(self)?;
+        let maybe_event = self.finalize_stop_action(&id).apply(self)?;
+        // Only the restart continues with a transition
+        self.schedule_transition_deadline(app_handle, &id);
+        self.sync_backends(app_handle);
         if let Some(x) = maybe_event {
             tokio::time::sleep(Duration::from_millis(20)).await;
-            x.emit(app_handle)?;
+            x.emit(app_handle, self)?;
         }
         Ok(())
     }
 
+    /// A plugin that is [PluginCurrentState::Restarting] is started again once it was unloaded, any other plugin is now [PluginCurrentState::Disabled]
+    fn finalize_stop_action(&self, id: &str) -> ReconcileAction {
+        match self.plugin_states.get(id).map(|x| &x.current_state) {
+            Some(PluginCurrentState::Restarting {}) => ReconcileAction::Start {
+                plugin_id: id.to_string(),
+            },
+            _ => ReconcileAction::SyncInPlace {
+                plugin_id: id.to_string(),
+                patch: Box::new(|x| x.current_state = PluginCurrentState::Disabled {}),
+            },
+        }
+    }
+
     /// Indicates a Plugin wants to start (e.g. when user presses to Start button)
     /// Once ack'd, front and backend start loading resources.
     /// The start is finished when the [PluginsState::finalize_start] is invoked.
@@ -318,51 +702,289 @@ impl PluginsState {
         id: String,
         app_handle: &AppHandle<R>,
     ) -> anyhow::Result<()> {
-        let maybe_event = ReconcileAction::Start { plugin_id: id }.apply(self)?;
+        let maybe_event = ReconcileAction::Start {
+            plugin_id: id.clone(),
+        }
+        .apply(self)?;
+        self.schedule_transition_deadline(app_handle, &id);
         if let Some(x) = maybe_event {
             tokio::time::sleep(Duration::from_millis(20)).await;
-            x.emit(app_handle)?;
+            x.emit(app_handle, self)?;
         }
         Ok(())
     }
 
+    /// All user plugins that request permissions, but were never shown to the user. See [GenericPluginSettings::already_known]
+    ///
+    /// Embedded plugins are left out, as they are granted what they request.
+    pub(crate) fn permission_requests<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+    ) -> Vec<PermissionRequest> {
+        self.plugin_states
+            .values()
+            .filter(|x| x.source != PluginStateSource::Embedded)
+            .filter(
+                |x| match GenericPluginSettings::get_by_id(app_handle, &x.id) {
+                    Ok(settings) => !settings.is_some_and(|x| x.already_known),
+                    Err(e) => {
+                        error!("failed to get generic plugin settings by ID: {e}");
+                        false
+                    }
+                },
+            )
+            .filter_map(|x| {
+                let requested = permissions::requested(&x.manifest);
+                (!requested.is_empty()).then(|| PermissionRequest {
+                    plugin_id: x.id.clone(),
+                    name: x.manifest.name().to_string(),
+                    requested,
+                    manifest_warnings: x.manifest_warnings.clone(),
+                })
+            })
+            .sorted_by(|a, b| a.plugin_id.cmp(&b.plugin_id))
+            .collect()
+    }
+
+    /// Hands the sidecars and WASM backends of all [PluginCurrentState::Running] plugins to the [SidecarSupervisor] and [WasmBackends], which stop all others.
+    /// Call this after every change to a plugin's current state.
+    fn sync_backends<R: Runtime>(&self, app_handle: &AppHandle<R>) {
+        let running = self
+            .plugin_states
+            .values()
+            .filter(|x| x.current_state == PluginCurrentState::Running {})
+            .collect_vec();
+        if let Some(supervisor) = app_handle.try_state::<SidecarSupervisor>() {
+            let desired = running
+                .iter()
+                .filter(|x| permissions::is_granted(app_handle, x, &Permission::Sidecar))
+                .filter_map(|x| {
+                    let sidecar = x.manifest.sidecar()?;
+                    SidecarSpec::resolve(&x.plugin_dir, sidecar)
+                        .map(|spec| (x.id.clone(), spec))
+                        .map_err(|e| error!("not starting sidecar of {}: {e}", x.id))
+                        .ok()
+                })
+                .collect();
+            supervisor.sync(app_handle, desired);
+        }
+        if let Some(backends) = app_handle.try_state::<WasmBackends>() {
+            let desired = running
+                .iter()
+                .filter_map(|x| {
+                    let mut backend = x.manifest.backend()?.clone();
+                    backend
+                        .capabilities
+                        .retain(|c| permissions::is_granted(app_handle, x, &c.permission()));
+                    WasmBackendSpec::resolve(&x.plugin_dir, &backend)
+                        .map(|spec| (x.id.clone(), spec))
+                        .map_err(|e| error!("not starting WASM backend of {}: {e}", x.id))
+                        .ok()
+                })
+                .collect();
+            backends.sync(app_handle, desired);
+        }
+    }
+
+    fn generation(&self, id: &str) -> u64 {
+        self.transition_generations
+            .get(id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Spawns a watchdog for the transition the plugin is currently in ([PluginCurrentState::Starting], [PluginCurrentState::Disabling] or [PluginCurrentState::Restarting]).
+    /// Does nothing if the plugin is not in a transition.
+    ///
+    /// If no other action was applied to the plugin once the deadline (see [TransitionTimeouts]) has passed, the transition is considered stuck:
+    /// - Starting and Restarting move to [PluginCurrentState::FailedToStart]
+    /// - Disabling is forced to [PluginCurrentState::Disabled]
+    ///
+    /// Next to the regular state update, the expiry is emitted as a `core/plugins/transition_expired` event.
+    fn schedule_transition_deadline<R: Runtime>(&self, app_handle: &AppHandle<R>, plugin_id: &str) {
+        let current_state = match self.plugin_states.get(plugin_id) {
+            Some(x) => x.current_state.clone(),
+            None => return,
+        };
+        let timeouts = TransitionTimeouts::load(app_handle).unwrap_or_else(|e| {
+            error!("failed to load transition timeouts, using defaults: {e}");
+            TransitionTimeouts::default()
+        });
+        let (timeout, expired_state) = match current_state {
+            PluginCurrentState::Starting { .. } => (
+                timeouts.starting_secs,
+                PluginCurrentState::FailedToStart {
+                    reasons: vec!["Timed out while starting".into()],
+                },
+            ),
+            PluginCurrentState::Restarting {} => (
+                timeouts.restarting_secs,
+                PluginCurrentState::FailedToStart {
+                    reasons: vec![
+                        "Timed out waiting for the plugin to unload during a restart".into(),
+                    ],
+                },
+            ),
+            PluginCurrentState::Disabling {} => {
+                (timeouts.disabling_secs, PluginCurrentState::Disabled {})
+            }
+            PluginCurrentState::Disabled {}
+            | PluginCurrentState::FailedToStart { .. }
+            | PluginCurrentState::Running {} => return,
+        };
+        let timeout = Duration::from_secs(timeout);
+
+        let generation = self.generation(plugin_id);
+        let plugin_id = plugin_id.to_string();
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            let state = app_handle.state::<Arc<RwLock<PluginsState>>>();
+            let mut data = state.write().await;
+            if data.generation(&plugin_id) != generation {
+                // the plugin moved on in the meantime
+                return;
+            }
+            warn!("plugin {plugin_id} did not leave {current_state:?} within {timeout:?}");
+            let failed = matches!(expired_state, PluginCurrentState::FailedToStart { .. });
+            let maybe_event = ReconcileAction::SyncInPlace {
+                plugin_id: plugin_id.clone(),
+                patch: Box::new(move |x| x.current_state = expired_state.clone()),
+            }
+            .apply(&mut data);
+            match maybe_event {
+                Ok(Some(x)) => {
+                    let expiry = EventEmit::new::<TransitionExpired>(&TransitionExpiry {
+                        id: plugin_id.clone(),
+                        expired_state: current_state,
+                    });
+                    if let Err(e) = x
+                        .emit(&app_handle, &data)
+                        .and_then(|_| expiry.emit(&app_handle, &data))
+                    {
+                        error!("failed to emit expired transition: {e}")
+                    }
+                    if failed {
+                        data.record_failure(&app_handle, &plugin_id);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => error!("failed to expire transition: {e}"),
+            }
+        });
+    }
+
+    /// Marks a plugin as [PluginCurrentState::FailedToStart] and records the failure (see [PluginsState::record_failure]).
+    ///
+    /// This is accepted while the plugin is [PluginCurrentState::Starting], or if it crashed within [CRASH_GRACE_PERIOD] after reaching [PluginCurrentState::Running].
+    /// Failures reported later than that are ignored.
     pub(crate) async fn start_failed<R: Runtime>(
         &mut self,
         id: String,
         reasons: Vec<String>,
         app_handle: &AppHandle<R>,
     ) -> anyhow::Result<()> {
+        let crashed_after_start = self
+            .running_since
+            .get(&id)
+            .is_some_and(|since| Utc::now() - *since < CRASH_GRACE_PERIOD);
+        let failed = match self.plugin_states.get(&id).map(|x| &x.current_state) {
+            Some(PluginCurrentState::Starting { .. }) => true,
+            Some(PluginCurrentState::Running {}) => crashed_after_start,
+            _ => false,
+        };
+        if !failed {
+            warn!("ignoring failure report for plugin {id}, as it is neither starting nor just started");
+            return Ok(());
+        }
+
+        self.running_since.remove(&id);
         let maybe_event = ReconcileAction::SyncInPlace {
-            plugin_id: id,
+            plugin_id: id.clone(),
             patch: Box::new(move |x| {
-                if matches!(&x.current_state, PluginCurrentState::Starting { .. }) {
-                    x.current_state = PluginCurrentState::FailedToStart {
-                        reasons: reasons.clone(),
-                    }
+                x.current_state = PluginCurrentState::FailedToStart {
+                    reasons: reasons.clone(),
                 }
             }),
         }
         .apply(self)?;
+        self.sync_backends(app_handle);
         if let Some(x) = maybe_event {
             tokio::time::sleep(Duration::from_millis(20)).await;
-            x.emit(app_handle)?;
+            x.emit(app_handle, self)?;
         }
+        self.record_failure(app_handle, &id);
         Ok(())
     }
 
+    /// Adds a failure to the plugin's history in [GenericPluginSettings].
+    ///
+    /// If the plugin failed too often, it gets quarantined: it is disabled, a `core/plugins/quarantined` event is emitted and a reconcile is triggered to stop it.
+    /// Otherwise, a reconcile is scheduled for when the backoff expires, so the plugin gets retried.
+    fn record_failure<R: Runtime>(&self, app_handle: &AppHandle<R>, plugin_id: &str) {
+        let mut settings = match GenericPluginSettings::get_by_id(app_handle, plugin_id) {
+            Ok(Some(x)) => x,
+            // Embedded plugins might not have a config yet, but they are active by default
+            Ok(None) => GenericPluginSettings {
+                enabled: true,
+                ..Default::default()
+            },
+            Err(e) => {
+                error!("failed to load settings of plugin {plugin_id} to record a failure: {e}");
+                return;
+            }
+        };
+
+        let now = Utc::now();
+        let quarantined = settings.record_failure(now);
+        if let Err(e) = settings.commit(app_handle, plugin_id) {
+            error!("failed to persist failure of plugin {plugin_id}: {e}");
+            return;
+        }
+
+        if quarantined {
+            warn!(
+                "plugin {plugin_id} failed {} times in a row, quarantining it",
+                settings.failures.len()
+            );
+            let event = EventEmit::new::<PluginQuarantined>(&Quarantine {
+                id: plugin_id.to_string(),
+                failures: settings.failures.clone(),
+            });
+            if let Err(e) = event.emit(app_handle, self) {
+                error!("failed to emit quarantine of plugin {plugin_id}: {e}")
+            }
+            if let Some(trigger) = app_handle.try_state::<ReconcileTrigger>() {
+                trigger.trigger();
+            }
+        } else if let Some(retry_at) = settings.next_attempt_at(now) {
+            let delay = (retry_at - now).to_std().unwrap_or_default();
+            info!("plugin {plugin_id} failed, retrying in {delay:?}");
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(delay).await;
+                if let Some(trigger) = app_handle.try_state::<ReconcileTrigger>() {
+                    trigger.trigger();
+                }
+            });
+        }
+    }
+
     pub(crate) async fn finalize_start<R: Runtime>(
         &mut self,
         id: String,
         app_handle: &AppHandle<R>,
     ) -> anyhow::Result<()> {
+        self.running_since.insert(id.clone(), Utc::now());
         let maybe_event = ReconcileAction::SyncInPlace {
             plugin_id: id,
             patch: Box::new(|x| x.current_state = PluginCurrentState::Running {}),
         }
         .apply(self)?;
+        self.sync_backends(app_handle);
         if let Some(x) = maybe_event {
             tokio::time::sleep(Duration::from_millis(20)).await;
-            x.emit(app_handle)?;
+            x.emit(app_handle, self)?;
         }
         Ok(())
     }
@@ -372,44 +994,103 @@ impl PluginsState {
     /// - fetch all user-provided and embedded plugins
     /// - look at the config to figure out which plugins are active
     /// - calls [PluginState::reconcile] for each plugin and notifies it if it should be started or not
-    #[instrument(skip(self, app_handle))]
-    async fn reconcile(&mut self, app_handle: &AppHandle<Wry>) -> anyhow::Result<()> {
-        let user_plugin_dir = app_handle
-            .store("store.json")
-            .map_err(|x| anyhow!("couldn't get store: {x}"))?
-            .get("plugin_dir")
-            .and_then(|x| {
-                let x = x.to_string();
-                PathBuf::from_str(&x).ok()
-            })
-            .unwrap_or(data_local_dir().unwrap().join("edpf-plugins"));
+    ///
+    /// The frontend hashes and signature checks of user plugins are expected to be computed beforehand (see [hash_user_frontends]), so the lock is not held while reading their files.
+    /// Plugins missing from `frontend_hashes` (e.g. because they were added in the meantime) are hashed and checked on the spot.
+    #[instrument(skip(self, app_handle, frontend_hashes))]
+    async fn reconcile(
+        &mut self,
+        app_handle: &AppHandle<Wry>,
+        frontend_hashes: &FrontendHashes,
+    ) -> anyhow::Result<()> {
+        let roots = search_roots(app_handle)?;
 
         let internal_plugins = internal_plugin_ids();
 
         enum PluginUnit {
-            UserDefined { path: PathBuf, plugin_id: String },
-            Embedded { plugin_id: String },
-        }
-
-        let mut plugin_ids_and_paths =
-            glob::glob(user_plugin_dir.join("*/manifest.json").to_str().unwrap())
-                .map_err(|x| anyhow!("failed to get user plugin manifests: {x}"))?
-                .flatten()
-                .filter_map(|path| {
-                    if let Some(p) = path.parent() {
-                        let plugin_id = p.file_name()?.to_str()?.to_string();
-
-                        if plugin_id
-                            .chars()
-                            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
-                            && !internal_plugins.contains(&plugin_id.as_str())
-                        {
-                            return Some(PluginUnit::UserDefined { path, plugin_id });
-                        }
+            UserDefined {
+                path: PathBuf,
+                plugin_id: String,
+                root: PluginRoot,
+            },
+            Dev {
+                path: PathBuf,
+                plugin_id: String,
+            },
+            Embedded {
+                plugin_id: String,
+            },
+        }
+
+        // Plugins we found, but which cannot be turned into a PluginState. These are surfaced to the UI instead of being silently ignored
+        let mut broken_plugins = vec![];
+
+        let dev_plugin_links = DevPluginLinks::load(app_handle)?;
+        if let Some(trigger) = app_handle.try_state::<ReconcileTrigger>() {
+            app_handle.state::<DevPluginWatchers>().sync(
+                &dev_plugin_links,
+                &app_handle.state::<Arc<FrontendHashCache>>(),
+                &trigger,
+            );
+        }
+
+        // Dev plugins come first, so they take precedence over an installed copy of the same plugin
+        let mut plugin_ids_and_paths = dev_plugin_links
+            .0
+            .into_iter()
+            .filter_map(
+                |(plugin_id, plugin_dir)| match plugin_id_problem(&plugin_id) {
+                    Some(problem) => {
+                        error!(
+                            "ignoring dev plugin at {}: {}",
+                            plugin_dir.display(),
+                            problem
+                        );
+                        broken_plugins.push(BrokenPlugin {
+                            id: plugin_id,
+                            plugin_dir,
+                            problem,
+                        });
+                        None
                     }
-                    None
-                })
-                .collect_vec();
+                    None => Some(PluginUnit::Dev {
+                        path: plugin_dir.join("manifest.json"),
+                        plugin_id,
+                    }),
+                },
+            )
+            .collect_vec();
+
+        // Then all roots, ordered by precedence. See [plugin_roots]
+        for root in &roots {
+            plugin_ids_and_paths.extend(
+                glob::glob(root.path.join("*/manifest.json").to_str().unwrap())
+                    .map_err(|x| anyhow!("failed to get user plugin manifests: {x}"))?
+                    .flatten()
+                    .filter_map(|path| {
+                        let plugin_dir = path.parent()?.to_path_buf();
+                        let plugin_id = plugin_dir.file_name()?.to_string_lossy().to_string();
+
+                        let problem = match plugin_id_problem(&plugin_id) {
+                            Some(x) => x,
+                            None => {
+                                return Some(PluginUnit::UserDefined {
+                                    path,
+                                    plugin_id,
+                                    root: root.clone(),
+                                })
+                            }
+                        };
+                        error!("ignoring plugin at {}: {}", plugin_dir.display(), problem);
+                        broken_plugins.push(BrokenPlugin {
+                            id: plugin_id,
+                            plugin_dir,
+                            problem,
+                        });
+                        None
+                    }),
+            );
+        }
         plugin_ids_and_paths.extend(internal_plugins.iter().map(|x| PluginUnit::Embedded {
             plugin_id: x.to_string(),
         }));
@@ -417,62 +1098,84 @@ impl PluginsState {
         let all_known_plugin_ids = plugin_ids_and_paths
             .iter()
             .map(|x| match x {
-                PluginUnit::UserDefined { path: _, plugin_id } => plugin_id.clone(),
-                PluginUnit::Embedded { plugin_id } => plugin_id.clone(),
+                PluginUnit::UserDefined { plugin_id, .. }
+                | PluginUnit::Dev { plugin_id, .. }
+                | PluginUnit::Embedded { plugin_id } => plugin_id.clone(),
             })
             .collect_vec();
         let active_plugin_ids: Vec<String> =
             GenericPluginSettings::get_active_ids(app_handle, &all_known_plugin_ids)?;
         let active_plugin_ids_set: HashSet<_> = active_plugin_ids.into_iter().collect();
+        let mut trust_store = TrustStore::load(app_handle)?;
+        let mut trust_store_modified = false;
 
         // This is what we know internally
         // We need this to stop plugins that were deleted between the last reconcile and now
         let known_user_plugin_ids: HashSet<_> = self
             .plugin_states
             .values()
-            .filter(|x| x.source == PluginStateSource::UserProvided)
+            .filter(|x| x.source != PluginStateSource::Embedded)
             .map(|x| x.id.clone())
             .collect();
 
-        let mut failed_path_bufs = vec![];
-        let mut discovered_user_plugins = HashMap::new();
+        let mut discovered_user_plugins: HashMap<String, PluginState> = HashMap::new();
 
         let mut actions_map = HashMap::new();
 
         // Here we define the **expected** state. We write this to discovered_user_plugins
         for entry in &plugin_ids_and_paths {
-            let (manifest, plugin_dir, plugin_id) = match entry {
-                PluginUnit::UserDefined { path, plugin_id } => {
-                    match PluginState::get_manifest(path) {
-                        Ok(x) => (x, path.parent().unwrap().to_path_buf(), plugin_id.clone()),
-                        Err(e) => {
-                            error!(
-                                "failed to get plugin manifest at {}: {}",
-                                path.display(),
-                                &e
-                            );
-                            failed_path_bufs.push((path, e));
-                            continue;
-                        }
-                    }
+            let span = match entry {
+                PluginUnit::UserDefined { plugin_id, .. }
+                | PluginUnit::Dev { plugin_id, .. }
+                | PluginUnit::Embedded { plugin_id } => {
+                    info_span!("reconcile_plugin", plugin_id = %plugin_id)
                 }
+            };
+            let _entered = span.enter();
+            let ((manifest, manifest_warnings), plugin_dir, plugin_id) = match entry {
+                PluginUnit::UserDefined {
+                    path, plugin_id, ..
+                }
+                | PluginUnit::Dev { path, plugin_id } => match PluginState::get_manifest(path) {
+                    Ok(x) => (x, path.parent().unwrap().to_path_buf(), plugin_id.clone()),
+                    Err(e) => {
+                        error!(
+                            "failed to get plugin manifest at {}: {}",
+                            path.display(),
+                            &e
+                        );
+                        broken_plugins.push(BrokenPlugin {
+                            id: plugin_id.clone(),
+                            plugin_dir: path.parent().unwrap().to_path_buf(),
+                            problem: e,
+                        });
+                        continue;
+                    }
+                },
                 PluginUnit::Embedded { plugin_id } => {
+                    let Some(embedded) = EmbeddedPlugin::get(plugin_id) else {
+                        error!("embedded plugin {plugin_id} is unknown");
+                        continue;
+                    };
                     let path = match app_handle.path().resolve(
-                        format!("assets/plugins/{}/manifest.json", plugin_id),
+                        format!("assets/plugins/{}", plugin_id),
                         BaseDirectory::Resource,
                     ) {
                         Ok(x) => x,
                         Err(x) => {
-                            error!("failed to resolve path to plugin manifest: {x}");
+                            error!("failed to resolve path to embedded plugin: {x}");
                             continue;
                         }
                     };
 
-                    let manifest = match PluginState::get_manifest(&path) {
-                        Ok(mut x) => {
+                    let manifest = match PluginState::parse_manifest(
+                        embedded.manifest.as_bytes(),
+                        &path.join("manifest.json"),
+                    ) {
+                        Ok((mut x, warnings)) => {
                             // Embedded Plugins inherit the Version from EDPF
                             x.inject_embedded_version(app_handle);
-                            x
+                            (x, warnings)
                         }
                         Err(e) => {
                             error!(
@@ -482,24 +1185,58 @@ impl PluginsState {
                             continue;
                         }
                     };
-                    (
-                        manifest,
-                        path.parent().unwrap().to_path_buf(),
-                        plugin_id.clone(),
-                    )
+                    (manifest, path, plugin_id.clone())
                 }
             };
 
+            let hash_cache = app_handle.state::<Arc<FrontendHashCache>>();
+            let digest = frontend_hashes.get(&plugin_dir);
             let frontend_hash = match entry {
-                PluginUnit::UserDefined { path, plugin_id: _ } => {
-                    PluginState::get_frontend_dir_hash(path)
+                PluginUnit::UserDefined { .. } | PluginUnit::Dev { .. } => match digest {
+                    Some(x) => x.frontend_hash.clone(),
+                    None => hash_cache.hash_plugin_dir(&plugin_dir),
+                },
+                // The hash is computed by build.rs, unless the frontend was not built back then
+                PluginUnit::Embedded { plugin_id } => {
+                    match EmbeddedPlugin::get(plugin_id).and_then(|x| x.frontend_hash) {
+                        Some(x) => Some(x.to_string()),
+                        None => hash_cache.hash_plugin_dir(&plugin_dir),
+                    }
+                }
+            };
+
+            let verification = match entry {
+                PluginUnit::UserDefined { .. } | PluginUnit::Dev { .. } => {
+                    let computed;
+                    let check = match digest.and_then(|x| x.signature.as_ref()) {
+                        Some(x) => x,
+                        None => {
+                            computed = package_signature::check_package(
+                                &plugin_dir,
+                                &manifest,
+                                &hash_cache,
+                            );
+                            &computed
+                        }
+                    };
+                    let (verification, modified) = trust_store.classify(&plugin_id, check);
+                    trust_store_modified |= modified;
+                    verification
                 }
-                PluginUnit::Embedded { plugin_id: _ } => Some("embedded".into()),
+                PluginUnit::Embedded { .. } => PluginVerification::Embedded,
             };
 
             let desired_state = PluginState {
                 current_state: match active_plugin_ids_set.contains(&plugin_id) {
                     true => match &frontend_hash {
+                        Some(_) if verification < trust_store.minimum_level => {
+                            PluginCurrentState::FailedToStart {
+                                reasons: vec![format!(
+                                    "Refusing to start: verification is {:?}, but at least {:?} is required",
+                                    verification, trust_store.minimum_level
+                                )],
+                            }
+                        }
                         Some(_frontend_hash) => PluginCurrentState::Running {},
                         None => PluginCurrentState::FailedToStart {
                             reasons: vec!["Failed to calculate hash for assets".into()],
@@ -510,27 +1247,63 @@ impl PluginsState {
                 frontend_hash: frontend_hash.unwrap_or("missing".into()),
                 plugin_dir: plugin_dir.clone(),
                 manifest,
+                manifest_warnings,
                 source: match entry {
                     PluginUnit::UserDefined { .. } => PluginStateSource::UserProvided,
+                    PluginUnit::Dev { .. } => PluginStateSource::Dev,
                     PluginUnit::Embedded { .. } => PluginStateSource::Embedded,
                 },
+                root: match entry {
+                    PluginUnit::UserDefined { root, .. } => Some(root.clone()),
+                    PluginUnit::Dev { .. } => Some(PluginRoot {
+                        kind: PluginRootKind::Dev,
+                        path: plugin_dir.clone(),
+                    }),
+                    PluginUnit::Embedded { .. } => None,
+                },
+                verification,
                 id: plugin_id.clone(),
             };
 
-            if let Some(x) =
-                discovered_user_plugins.insert(plugin_id.clone(), desired_state.clone())
-            {
-                error!(
-                    "Plugin conflict! The following manifests share the plugin ID '{}': {}, {}",
+            // The first plugin to claim an ID wins, as the plugins are ordered by the precedence of their root. Every other one is reported as shadowed
+            if let Some(x) = discovered_user_plugins.get(&plugin_id) {
+                warn!(
+                    "The plugin ID '{}' is claimed by multiple plugins. {} shadows {}",
                     plugin_id,
+                    x.manifest_path().display(),
                     plugin_dir.display(),
-                    x.manifest_path().display()
                 );
+                broken_plugins.push(BrokenPlugin {
+                    id: plugin_id.clone(),
+                    plugin_dir: plugin_dir.clone(),
+                    problem: BrokenPluginReason::Shadowed {
+                        shadowed_by: x.plugin_dir.clone(),
+                    },
+                });
                 continue;
             }
+            discovered_user_plugins.insert(plugin_id.clone(), desired_state.clone());
 
             let current_state_for_this_plugin = self.plugin_states.get(&plugin_id);
 
+            // A plugin that failed recently is only retried once its backoff expired. See [GenericPluginSettings::next_attempt_at]
+            if let (
+                Some(PluginState {
+                    current_state: PluginCurrentState::FailedToStart { .. },
+                    ..
+                }),
+                PluginCurrentState::Running {},
+            ) = (current_state_for_this_plugin, &desired_state.current_state)
+            {
+                let now = Utc::now();
+                let retry_at = GenericPluginSettings::get_by_id(app_handle, &plugin_id)?
+                    .and_then(|x| x.next_attempt_at(now));
+                if let Some(retry_at) = retry_at.filter(|x| *x > now) {
+                    info!("plugin {plugin_id} is backing off until {retry_at}");
+                    continue;
+                }
+            }
+
             if let Some(action) =
                 PluginState::get_reconcile_action(current_state_for_this_plugin, &desired_state)
             {
@@ -538,10 +1311,16 @@ impl PluginsState {
             }
         }
 
+        if trust_store_modified {
+            if let Err(e) = trust_store.commit(app_handle) {
+                error!("failed to persist trust store: {e}")
+            }
+        }
+
         let action_plan = serde_json::to_string(&actions_map).unwrap();
         info!(
-            "Planned reconcile actions: {action_plan}, dir: {}",
-            user_plugin_dir.display(),
+            "Planned reconcile actions: {action_plan}, roots: {}",
+            roots.iter().map(|x| x.path.display()).join(", "),
         );
 
         // We STOP any plugin that was deleted or broke for some reason
@@ -574,15 +1353,111 @@ impl PluginsState {
                 if let Some(e) = maybe_event {
                     emits.push(e.clone());
                 }
+                self.schedule_transition_deadline(app_handle, &id);
             }
         };
 
+        emits.extend(self.sync_broken_plugins(broken_plugins));
+        self.sync_backends(app_handle);
+
+        // Only plugins that showed up with this reconcile are announced. Windows that open later use `fetch_permission_requests`
+        emits.extend(
+            self.permission_requests(app_handle)
+                .into_iter()
+                .filter(|x| !known_user_plugin_ids.contains(&x.plugin_id))
+                .map(|x| EventEmit::new::<PermissionsRequested>(&x)),
+        );
+
         for e in emits {
-            e.emit(app_handle)?;
+            e.emit(app_handle, self)?;
         }
 
         Ok(())
     }
+
+    /// Replaces the list of broken plugins and returns an update event for every entry that changed.
+    ///
+    /// The event is the same `core/plugins/update` event used for working plugins, except that it carries a `broken` field instead of a `pluginState`.
+    /// If a plugin is no longer broken, `broken` is set to null.
+    fn sync_broken_plugins(&mut self, broken_plugins: Vec<BrokenPlugin>) -> Vec<EventEmit> {
+        let mut emits = vec![];
+        for broken in &broken_plugins {
+            if !self.broken_plugins.contains(broken) {
+                emits.push(EventEmit::new::<PluginUpdated>(&PluginUpdate::Broken {
+                    id: broken.id.clone(),
+                    broken: Some(broken.clone()),
+                }));
+            }
+        }
+        for previous in &self.broken_plugins {
+            if !broken_plugins
+                .iter()
+                .any(|x| x.plugin_dir == previous.plugin_dir)
+            {
+                emits.push(EventEmit::new::<PluginUpdated>(&PluginUpdate::Broken {
+                    id: previous.id.clone(),
+                    broken: None,
+                }));
+            }
+        }
+        self.broken_plugins = broken_plugins;
+        emits
+    }
+}
+
+/// A folder in the plugin directory that looks like a plugin, but could not be loaded.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+pub(crate) struct BrokenPlugin {
+    /// The plugin ID derived from the folder name
+    id: String,
+    plugin_dir: PathBuf,
+    problem: BrokenPluginReason,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "type")]
+pub(crate) enum BrokenPluginReason {
+    /// The manifest could not be opened or read
+    UnreadableManifest { message: String },
+    /// The manifest is not valid JSON, or does not match any known manifest version
+    InvalidManifest {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    /// The folder name cannot be used as a plugin ID
+    InvalidPluginId { message: String },
+    /// Another plugin already uses this ID. If `conflicting_dir` is missing, the ID is reserved by an embedded plugin
+    DuplicateId { conflicting_dir: Option<PathBuf> },
+    /// A plugin with the same ID was found in a root with higher precedence. See [plugin_roots]
+    Shadowed { shadowed_by: PathBuf },
+}
+
+impl std::fmt::Display for BrokenPluginReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrokenPluginReason::UnreadableManifest { message } => {
+                write!(f, "failed to read manifest: {message}")
+            }
+            BrokenPluginReason::InvalidManifest {
+                message,
+                line,
+                column,
+            } => write!(f, "failed to parse manifest at {line}:{column}: {message}"),
+            BrokenPluginReason::InvalidPluginId { message } => {
+                write!(f, "invalid plugin ID: {message}")
+            }
+            BrokenPluginReason::DuplicateId {
+                conflicting_dir: Some(dir),
+            } => write!(f, "plugin ID is already used by {}", dir.display()),
+            BrokenPluginReason::DuplicateId {
+                conflicting_dir: None,
+            } => write!(f, "plugin ID is reserved by an embedded plugin"),
+            BrokenPluginReason::Shadowed { shadowed_by } => {
+                write!(f, "shadowed by {}", shadowed_by.display())
+            }
+        }
+    }
 }
 
 /// Defines the current state of the plugin. Mainly used for reconciliation and for the Frontend to display all plugins / specific plugin
@@ -592,18 +1467,30 @@ pub(crate) struct PluginState {
     current_state: PluginCurrentState,
     plugin_dir: PathBuf,
     manifest: PluginManifest,
+    /// Deprecation warnings from loading the manifest, meant for the plugin author
+    manifest_warnings: Vec<String>,
     source: PluginStateSource,
     frontend_hash: String,
+    /// The result of checking the package signature against the Trust Store. See [package_signature]
+    verification: PluginVerification,
+    /// The root this plugin was loaded from. [None] for embedded plugins
+    root: Option<PluginRoot>,
 }
 
 #[derive(Debug, Serialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "type")]
 pub(crate) enum PluginCurrentState {
     Disabled {},
-    Starting { metadata: Vec<String> },
-    FailedToStart { reasons: Vec<String> },
+    Starting {
+        metadata: Vec<String>,
+    },
+    FailedToStart {
+        reasons: Vec<String>,
+    },
     Running {},
     Disabling {},
+    /// The plugin is being unloaded by the Frontend so it can be started again with a new hash. See [ReconcileAction::Restart]
+    Restarting {},
 }
 
 impl PluginState {
@@ -614,32 +1501,40 @@ impl PluginState {
         self.plugin_dir.join("frontend")
     }
 
+    /// Reads the manifest and upgrades it to the latest manifest version. Next to the manifest, any deprecation warnings are returned.
     #[instrument(ret)]
-    fn get_manifest(manifest_path: &PathBuf) -> Result<PluginManifest, String> {
-        let reader = File::open(manifest_path).map_err(|x| {
-            format!(
-                "failed to read manifest at {}: {x}",
-                manifest_path.display()
-            )
-        })?;
-        serde_json::from_reader(reader).map_err(|x| format!("failed to parse manifest: {}", x))
+    fn get_manifest(
+        manifest_path: &PathBuf,
+    ) -> Result<(PluginManifest, Vec<String>), BrokenPluginReason> {
+        let reader =
+            File::open(manifest_path).map_err(|x| BrokenPluginReason::UnreadableManifest {
+                message: x.to_string(),
+            })?;
+        Self::parse_manifest(reader, manifest_path)
     }
 
-    fn get_frontend_dir_hash(manifest_path: &Path) -> Option<String> {
-        let parent = match manifest_path.parent() {
-            Some(x) => x.join("frontend"),
-            None => return None,
-        };
-        get_dir_hash::get_dir_hash(
-            &parent,
-            &Options {
-                follow_symlinks: false,
-                include_metadata: false,
-                case_sensitive_paths: true,
-                ..Default::default()
-            },
-        )
-        .ok()
+    /// Like [PluginState::get_manifest], but reads from `reader`. `manifest_path` is only used for logging
+    fn parse_manifest(
+        reader: impl std::io::Read,
+        manifest_path: &Path,
+    ) -> Result<(PluginManifest, Vec<String>), BrokenPluginReason> {
+        let manifest: PluginManifest =
+            serde_json::from_reader(reader).map_err(|x| match x.classify() {
+                serde_json::error::Category::Io => BrokenPluginReason::UnreadableManifest {
+                    message: x.to_string(),
+                },
+                _ => BrokenPluginReason::InvalidManifest {
+                    message: x.to_string(),
+                    line: x.line(),
+                    column: x.column(),
+                },
+            })?;
+
+        let (manifest, warnings) = manifest.upgrade();
+        for warning in &warnings {
+            warn!("{}: {warning}", manifest_path.display());
+        }
+        Ok((manifest, warnings))
     }
 
     #[instrument(ret)]
@@ -663,52 +1558,79 @@ impl PluginState {
 
         let manifest_synced = current_plugin_state
             .manifest
-            .eq(&desired_plugin_state.manifest);
-        // only relevant if both desired and current state is in running
-        let frontend_dirs_synced =
-            current_plugin_state.frontend_hash == desired_plugin_state.frontend_hash;
+            .eq(&desired_plugin_state.manifest)
+            && current_plugin_state.manifest_warnings == desired_plugin_state.manifest_warnings;
+        let verification_synced =
+            current_plugin_state.verification == desired_plugin_state.verification;
+        // only relevant if both desired and current state is in running. The plugin dir changes if the plugin was moved or a dev plugin takes over the ID
+        let frontend_dirs_synced = current_plugin_state.frontend_hash
+            == desired_plugin_state.frontend_hash
+            && current_plugin_state.plugin_dir == desired_plugin_state.plugin_dir
+            && current_plugin_state.source == desired_plugin_state.source
+            && current_plugin_state.root == desired_plugin_state.root;
         let plugin_id = current_plugin_state.id.clone();
+        let sync_patch = || -> Box<dyn FnMut(&mut PluginState)> {
+            let new_hash = desired_plugin_state.frontend_hash.clone();
+            let new_manifest = desired_plugin_state.manifest.clone();
+            let new_manifest_warnings = desired_plugin_state.manifest_warnings.clone();
+            let new_verification = desired_plugin_state.verification;
+            let new_plugin_dir = desired_plugin_state.plugin_dir.clone();
+            let new_source = desired_plugin_state.source.clone();
+            let new_root = desired_plugin_state.root.clone();
+            Box::new(move |x| {
+                x.frontend_hash = new_hash.clone();
+                x.plugin_dir = new_plugin_dir.clone();
+                x.source = new_source.clone();
+                x.root = new_root.clone();
+                x.manifest = new_manifest.clone();
+                x.manifest_warnings = new_manifest_warnings.clone();
+                x.verification = new_verification;
+            })
+        };
 
         match (&current_plugin_state.current_state, &desired_plugin_state.current_state) {
-            (_, PluginCurrentState::Disabling { .. } | PluginCurrentState::FailedToStart { .. } | PluginCurrentState::Starting { .. }) => {
+            (_, PluginCurrentState::Disabling { .. } | PluginCurrentState::Starting { .. } | PluginCurrentState::Restarting { .. }) => {
                 error!("received a desired state that should not be possible due to reconciliation logic");
                 None
             }
-            (PluginCurrentState::Disabled {  }, PluginCurrentState::Disabled {  }) => match (manifest_synced, frontend_dirs_synced) {
-                (true, true) => None,
+            // The plugin must not run (e.g. missing assets or refused by the trust store). We move it into the failed state in place, the Frontend will tear down any UI
+            (current, desired @ PluginCurrentState::FailedToStart { .. }) => match (current == desired, manifest_synced, verification_synced, frontend_dirs_synced) {
+                (true, true, true, true) => None,
                 _ => {
-                    let new_hash = desired_plugin_state.frontend_hash.clone();
-                    let new_manifest = desired_plugin_state.manifest.clone();
+                    let new_state = desired.clone();
+                    let mut patch = sync_patch();
                     Some(ReconcileAction::SyncInPlace { plugin_id, patch: Box::new(move |x| {
-                        x.frontend_hash = new_hash.clone();
-                        x.manifest = new_manifest.clone();
+                        patch(x);
+                        x.current_state = new_state.clone();
                     })})
                 }
             },
+            (PluginCurrentState::Disabled {  }, PluginCurrentState::Disabled {  }) => match (manifest_synced, verification_synced, frontend_dirs_synced) {
+                (true, true, true) => None,
+                _ => Some(ReconcileAction::SyncInPlace { plugin_id, patch: sync_patch() }),
+            },
             // From Disabled / Failed to Enabled
             (PluginCurrentState::Disabled {  } | PluginCurrentState::Disabling {  } | PluginCurrentState::FailedToStart { .. }, | PluginCurrentState::Running {  }) => {
-                Some(ReconcileAction::Start { plugin_id })
+                match (manifest_synced, verification_synced, frontend_dirs_synced) {
+                    (true, true, true) => Some(ReconcileAction::Start { plugin_id }),
+                    // Restart applies the patch before starting
+                    _ => Some(ReconcileAction::Restart { plugin_id, patch: sync_patch() }),
+                }
             },
             // Already running, but might need a restart
             (PluginCurrentState::Running {  }, PluginCurrentState::Running {  }) => {
                 match (manifest_synced, frontend_dirs_synced) {
-                    (true, true) => None,
-                    _ => {
-                        let new_hash = desired_plugin_state.frontend_hash.clone();
-                        let new_manifest = desired_plugin_state.manifest.clone();
-                        Some(ReconcileAction::Restart { plugin_id, patch: Box::new(move |x| {
-                                x.frontend_hash = new_hash.clone();
-                                x.manifest = new_manifest.clone();
-                            })
-                        })
-                    }
+                    (true, true) if verification_synced => None,
+                    // only the verification changed. No need to restart
+                    (true, true) => Some(ReconcileAction::SyncInPlace { plugin_id, patch: sync_patch() }),
+                    _ => Some(ReconcileAction::Restart { plugin_id, patch: sync_patch() }),
                 }
             }
             // Noop - already converging towards that state
             (PluginCurrentState::Disabling {  }, PluginCurrentState::Disabled {  }) |
-            (PluginCurrentState::Starting { .. } ,  PluginCurrentState::Running {  }) => None,
+            (PluginCurrentState::Starting { .. } | PluginCurrentState::Restarting {  },  PluginCurrentState::Running {  }) => None,
             // Running or stuck trying to run while trying to be stopped
-            (PluginCurrentState::FailedToStart { .. } | PluginCurrentState::Running {  } | PluginCurrentState::Starting { .. }, PluginCurrentState::Disabled {  }) => {
+            (PluginCurrentState::FailedToStart { .. } | PluginCurrentState::Running {  } | PluginCurrentState::Starting { .. } | PluginCurrentState::Restarting {  }, PluginCurrentState::Disabled {  }) => {
                 Some(ReconcileAction::Stop { plugin_id })
             }
         }
@@ -720,13 +1642,265 @@ pub(crate) enum PluginStateSource {
     /// User-provided plugins are taken from the
     /// User-configured plugin directory.
     ///
-    /// The plugin dir defaults to [dirs::data_local_dir]/edpf-plugins
+    /// The plugin dir defaults to [dirs::data_local_dir]/edpf-plugins, or `plugins` in the portable data dir. See [crate::portable]
     ///
     /// In here, we expect folders that contain a `manifest.json`. Only folders containing such a file are considered plugins
     UserProvided,
+    /// A plugin linked from an arbitrary folder on disk for development. See [dev_plugins]
+    Dev,
     /// This is an "official" plugin that is bundled into edpf
     /// We use a virtual file system to load all relevant assets
     ///
     /// Other than that, embedded plugins are identical to User-provided ones
     Embedded,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{contracts::PluginRestart, *};
+
+    fn disabled() -> PluginCurrentState {
+        PluginCurrentState::Disabled {}
+    }
+    fn starting() -> PluginCurrentState {
+        PluginCurrentState::Starting { metadata: vec![] }
+    }
+    fn failed() -> PluginCurrentState {
+        PluginCurrentState::FailedToStart {
+            reasons: vec!["broken".into()],
+        }
+    }
+    fn running() -> PluginCurrentState {
+        PluginCurrentState::Running {}
+    }
+    fn restarting() -> PluginCurrentState {
+        PluginCurrentState::Restarting {}
+    }
+    fn disabling() -> PluginCurrentState {
+        PluginCurrentState::Disabling {}
+    }
+
+    fn plugin(id: &str, current_state: PluginCurrentState) -> PluginState {
+        PluginState {
+            id: id.into(),
+            current_state,
+            plugin_dir: PathBuf::from("plugins").join(id),
+            manifest: serde_json::from_value(json!({"type": "v1", "name": id})).unwrap(),
+            manifest_warnings: vec![],
+            source: PluginStateSource::UserProvided,
+            frontend_hash: "old".into(),
+            verification: PluginVerification::Unsigned,
+            root: None,
+        }
+    }
+
+    fn kind(action: &ReconcileAction) -> &'static str {
+        match action {
+            ReconcileAction::Adopt { .. } => "Adopt",
+            ReconcileAction::Start { .. } => "Start",
+            ReconcileAction::Stop { .. } => "Stop",
+            ReconcileAction::Drop { .. } => "Drop",
+            ReconcileAction::Restart { .. } => "Restart",
+            ReconcileAction::SyncInPlace { .. } => "SyncInPlace",
+        }
+    }
+
+    fn action_for(current: &PluginState, desired: &PluginState) -> Option<&'static str> {
+        PluginState::get_reconcile_action(Some(current), desired)
+            .as_ref()
+            .map(kind)
+    }
+
+    fn state_with(plugins: Vec<PluginState>) -> PluginsState {
+        let mut state = PluginsState::new();
+        for x in plugins {
+            state.plugin_states.insert(x.id.clone(), x);
+        }
+        state
+    }
+
+    #[test]
+    fn reconcile_action_table() {
+        #[rustfmt::skip]
+        let table = [
+            // (current, desired, action if nothing else changed)
+            (disabled(), disabled(), None),
+            (disabled(), running(), Some("Start")),
+            (disabled(), failed(), Some("SyncInPlace")),
+            (starting(), disabled(), Some("Stop")),
+            (starting(), running(), None),
+            (starting(), failed(), Some("SyncInPlace")),
+            (failed(), disabled(), Some("Stop")),
+            (failed(), running(), Some("Start")),
+            (failed(), failed(), None),
+            (running(), disabled(), Some("Stop")),
+            (running(), running(), None),
+            (running(), failed(), Some("SyncInPlace")),
+            (restarting(), disabled(), Some("Stop")),
+            (restarting(), running(), None),
+            (restarting(), failed(), Some("SyncInPlace")),
+            (disabling(), disabled(), None),
+            (disabling(), running(), Some("Start")),
+            (disabling(), failed(), Some("SyncInPlace")),
+        ];
+        for (current, desired, expected) in table {
+            assert_eq!(
+                action_for(&plugin("a", current.clone()), &plugin("a", desired.clone())),
+                expected,
+                "{current:?} -> {desired:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn transitional_states_are_never_desired() {
+        for desired in [starting(), restarting(), disabling()] {
+            for current in [disabled(), running(), failed()] {
+                assert_eq!(
+                    action_for(&plugin("a", current), &plugin("a", desired.clone())),
+                    None
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_plugins_are_adopted() {
+        for (desired, start) in [(running(), true), (disabled(), false), (failed(), false)] {
+            let action = PluginState::get_reconcile_action(None, &plugin("a", desired));
+            match action {
+                Some(ReconcileAction::Adopt { start: x, .. }) => assert_eq!(x, start),
+                x => panic!("expected Adopt, got {x:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn changed_plugins_are_restarted_or_synced() {
+        let mut changed = plugin("a", running());
+        changed.frontend_hash = "new".into();
+        #[rustfmt::skip]
+        let table = [
+            (running(), running(), Some("Restart")),
+            (disabled(), running(), Some("Restart")),
+            (failed(), running(), Some("Restart")),
+            (disabled(), disabled(), Some("SyncInPlace")),
+            (running(), failed(), Some("SyncInPlace")),
+            // already on the way, the next reconcile picks the change up
+            (starting(), running(), None),
+            (restarting(), running(), None),
+        ];
+        for (current, desired, expected) in table {
+            changed.current_state = desired.clone();
+            assert_eq!(
+                action_for(&plugin("a", current.clone()), &changed),
+                expected,
+                "{current:?} -> {desired:?}"
+            );
+        }
+
+        // a new verification alone does not need the frontend to reload
+        let mut verified = plugin("a", running());
+        verified.verification = PluginVerification::SignedTrusted;
+        assert_eq!(
+            action_for(&plugin("a", running()), &verified),
+            Some("SyncInPlace")
+        );
+    }
+
+    #[test]
+    fn restart_waits_for_the_unload_before_starting() {
+        let mut state = state_with(vec![plugin("a", running())]);
+        let event = ReconcileAction::Restart {
+            plugin_id: "a".into(),
+            patch: Box::new(|x| x.frontend_hash = "new".into()),
+        }
+        .apply(&mut state)
+        .unwrap()
+        .unwrap();
+        let restarted = state.get_cloned("a").unwrap();
+        assert_eq!(restarted.current_state, restarting());
+        assert_eq!(restarted.frontend_hash, "new");
+        // the frontend is told to unload the plugin, instead of just getting a state update
+        assert!(format!("{event:?}").contains(PluginRestart::NAME));
+
+        // `finalize_stop_plugin` acknowledges the unload, which hands over to a regular start
+        let action = state.finalize_stop_action("a");
+        assert_eq!(kind(&action), "Start");
+        action.apply(&mut state).unwrap();
+        let started = state.get_cloned("a").unwrap();
+        assert_eq!(started.current_state, starting());
+        assert_eq!(started.frontend_hash, "new");
+    }
+
+    #[test]
+    fn restart_of_a_stopped_plugin_starts_right_away() {
+        for current in [disabled(), disabling(), failed()] {
+            let mut state = state_with(vec![plugin("a", current)]);
+            ReconcileAction::Restart {
+                plugin_id: "a".into(),
+                patch: Box::new(|_| {}),
+            }
+            .apply(&mut state)
+            .unwrap();
+            assert_eq!(state.get_cloned("a").unwrap().current_state, starting());
+        }
+    }
+
+    #[test]
+    fn finalize_stop_disables_plugins_that_are_not_restarting() {
+        for current in [disabling(), running(), starting()] {
+            let mut state = state_with(vec![plugin("a", current)]);
+            state.finalize_stop_action("a").apply(&mut state).unwrap();
+            assert_eq!(state.get_cloned("a").unwrap().current_state, disabled());
+        }
+    }
+
+    #[test]
+    fn every_action_outdates_pending_transition_deadlines() {
+        let mut state = state_with(vec![plugin("a", disabled()), plugin("b", disabled())]);
+        assert_eq!(state.generation("a"), 0);
+
+        // A deadline remembers the generation it was scheduled in, see [PluginsState::schedule_transition_deadline]
+        ReconcileAction::Start {
+            plugin_id: "a".into(),
+        }
+        .apply(&mut state)
+        .unwrap();
+        let starting_deadline = state.generation("a");
+        assert_eq!(starting_deadline, 1);
+
+        ReconcileAction::SyncInPlace {
+            plugin_id: "a".into(),
+            patch: Box::new(|x| x.current_state = PluginCurrentState::Running {}),
+        }
+        .apply(&mut state)
+        .unwrap();
+        assert_ne!(state.generation("a"), starting_deadline);
+        // generations are per plugin
+        assert_eq!(state.generation("b"), 0);
+
+        // the hand-off of a restart is a new transition as well
+        ReconcileAction::Restart {
+            plugin_id: "a".into(),
+            patch: Box::new(|_| {}),
+        }
+        .apply(&mut state)
+        .unwrap();
+        let restarting_deadline = state.generation("a");
+        state.finalize_stop_action("a").apply(&mut state).unwrap();
+        assert_ne!(state.generation("a"), restarting_deadline);
+
+        // an adopted plugin that is started right away counts the start as well
+        let mut state = PluginsState::new();
+        ReconcileAction::Adopt {
+            plugin_state: Box::new(plugin("c", disabled())),
+            start: true,
+        }
+        .apply(&mut state)
+        .unwrap();
+        assert_eq!(state.generation("c"), 2);
+    }
+}
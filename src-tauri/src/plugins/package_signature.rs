@@ -0,0 +1,529 @@
+//! This module verifies the publisher signature of a plugin package and manages the Trust Store of publisher keys
+//!
+//! A signed plugin ships two things:
+//! - a `publisher_key` in its manifest. This is a minisign public key (the base64 line of a `minisign.pub`)
+//! - a [SIGNATURE_FILE_NAME] file in its plugin folder. This is a minisign signature over the package listing
+//!
+//! The package listing is built from the `manifest.json`, every file below `frontend/` and the executables declared in the manifest (see [listed_executables]), sorted by path.
+//! Each file contributes a line in the format of `sha256sum`, meaning `$sha256hex  $relative/path\n`.
+//! Paths always use forward slashes. A publisher can reproduce the listing with
+//! `find manifest.json frontend $executables -type f | LC_ALL=C sort | xargs sha256sum > listing && minisign -Sm listing`
+//! and then ship `listing.minisig` as [SIGNATURE_FILE_NAME].
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use minisign_verify::{PublicKey, Signature};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_store::StoreBuilder;
+use tracing::warn;
+
+use super::{
+    frontend_hash::FrontendHashCache, plugin_manifest::PluginManifest, sidecar::SidecarSpec,
+    wasm_backend::WasmBackendSpec, PluginState,
+};
+use crate::portable::store_path;
+
+/// Name of the file inside the plugin folder containing the minisign signature of the package listing
+pub(crate) const SIGNATURE_FILE_NAME: &str = "edpf-package.sig";
+
+/// Describes how far we can trust the contents of a plugin folder.
+///
+/// The variants are ordered from least to most trustworthy. This ordering is used when comparing against [TrustStore::minimum_level]
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, JsonSchema,
+)]
+pub(crate) enum PluginVerification {
+    /// A signature is present, but it does not match the contents of the plugin folder, or it could not be parsed at all.
+    /// This is also the case if a key is pinned for this plugin, but the plugin is unsigned or signed by a different key
+    Tampered,
+    /// The plugin does not ship a signature or does not declare a publisher key
+    Unsigned,
+    /// The signature is valid, but no key is pinned for this plugin
+    SignedUnknown,
+    /// The signature is valid and made by the publisher key pinned for this plugin
+    SignedTrusted,
+    /// Bundled with EDPF. These are covered by the signature of EDPF itself
+    Embedded,
+}
+
+/// The result of checking the signature, before the Trust Store is consulted
+#[derive(Debug)]
+pub(crate) enum SignatureCheck {
+    Unsigned,
+    Invalid { reason: String },
+    Valid { public_key: String },
+}
+
+/// Checks the signature of the plugin located in `plugin_dir` against the `publisher_key` declared in its `manifest`.
+/// The files are hashed through `hash_cache`, so unchanged files are not read again
+pub(crate) fn check_package(
+    plugin_dir: &Path,
+    manifest: &PluginManifest,
+    hash_cache: &FrontendHashCache,
+) -> SignatureCheck {
+    let signature_path = plugin_dir.join(SIGNATURE_FILE_NAME);
+    let (publisher_key, signature) = match (manifest.publisher_key(), signature_path.exists()) {
+        (None, false) => return SignatureCheck::Unsigned,
+        (Some(_), false) => {
+            return SignatureCheck::Invalid {
+                reason: "manifest declares a publisher key, but the signature is missing".into(),
+            }
+        }
+        (None, true) => {
+            return SignatureCheck::Invalid {
+                reason: "signature present, but the manifest declares no publisher key".into(),
+            }
+        }
+        (Some(key), true) => match Signature::from_file(&signature_path) {
+            Ok(sig) => (key.trim().to_string(), sig),
+            Err(e) => {
+                return SignatureCheck::Invalid {
+                    reason: format!("failed to parse signature: {e}"),
+                }
+            }
+        },
+    };
+
+    let public_key = match PublicKey::from_base64(&publisher_key) {
+        Ok(x) => x,
+        Err(e) => {
+            return SignatureCheck::Invalid {
+                reason: format!("failed to parse publisher key: {e}"),
+            }
+        }
+    };
+    let listing = match package_listing(plugin_dir, manifest, hash_cache) {
+        Ok(x) => x,
+        Err(e) => {
+            return SignatureCheck::Invalid {
+                reason: format!("failed to build package listing: {e}"),
+            }
+        }
+    };
+
+    match public_key.verify(listing.as_bytes(), &signature, false) {
+        Ok(_) => SignatureCheck::Valid {
+            public_key: publisher_key,
+        },
+        Err(e) => SignatureCheck::Invalid {
+            reason: format!("signature does not match package contents: {e}"),
+        },
+    }
+}
+
+/// Builds the listing that is signed by the publisher. See the module docs for the format.
+fn package_listing(
+    plugin_dir: &Path,
+    manifest: &PluginManifest,
+    hash_cache: &FrontendHashCache,
+) -> anyhow::Result<String> {
+    let mut files = vec![plugin_dir.join("manifest.json")];
+    collect_files(&plugin_dir.join("frontend"), &mut files)?;
+    files.extend(
+        listed_executables(plugin_dir, manifest)
+            .into_iter()
+            .filter(|x| x.is_file()),
+    );
+
+    let entries = files
+        .into_iter()
+        .unique()
+        .map(|path| {
+            let relative = path
+                .strip_prefix(plugin_dir)?
+                .components()
+                .map(|x| x.as_os_str().to_string_lossy())
+                .join("/");
+            let digest = hash_cache.file_digest(&path)?;
+            Ok((relative, hex(&digest)))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(entries
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()))
+        .map(|(path, digest)| format!("{digest}  {path}\n"))
+        .collect())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+/// Returns the files outside of `frontend/` that EDPF executes and that are therefore part of the package listing:
+/// the sidecar executable and the WASM backend module, as resolved by [SidecarSpec] and [WasmBackendSpec].
+/// Files that do not exist are skipped by the listing
+pub(crate) fn listed_executables(plugin_dir: &Path, manifest: &PluginManifest) -> Vec<PathBuf> {
+    let sidecar = manifest
+        .sidecar()
+        .and_then(|x| SidecarSpec::executable_path(plugin_dir, x).ok());
+    let backend = manifest
+        .backend()
+        .and_then(|x| WasmBackendSpec::module_path(plugin_dir, x).ok());
+    sidecar.into_iter().chain(backend).collect()
+}
+
+/// Returns whether a change to `path` can change the package listing of the plugin in `plugin_dir`, and therefore its verification
+pub(crate) fn affects_listing(plugin_dir: &Path, path: &Path) -> bool {
+    let manifest_path = plugin_dir.join("manifest.json");
+    if path == manifest_path || path.starts_with(plugin_dir.join("frontend")) {
+        return true;
+    }
+    match PluginState::get_manifest(&manifest_path) {
+        Ok((manifest, _)) => listed_executables(plugin_dir, &manifest)
+            .iter()
+            .any(|x| x == path),
+        Err(_) => false,
+    }
+}
+
+/// Recursively collects all files below `dir`. Symlinks are not followed, same as for the frontend hash
+pub(super) fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), out)?;
+        } else if file_type.is_file() {
+            out.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// A publisher key that is pinned to a plugin ID.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub(crate) struct PinnedKey {
+    /// The minisign public key, base64 encoded
+    pub(crate) public_key: String,
+    pub(crate) pinned_at: DateTime<Utc>,
+    /// If false, this key was pinned automatically because it was the first key seen for this plugin (trust-on-first-use)
+    pub(crate) explicit: bool,
+}
+
+/// The persisted Trust Store. It lives in `store.json` under the `trust_store` key.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(default)]
+pub(crate) struct TrustStore {
+    /// If set, the first valid publisher key seen for a plugin is pinned automatically.
+    pub(crate) trust_on_first_use: bool,
+    /// Plugins with a verification level below this are not started.
+    pub(crate) minimum_level: PluginVerification,
+    /// Maps a plugin ID to the publisher key that is allowed to sign it
+    pub(crate) pinned_keys: HashMap<String, PinnedKey>,
+}
+
+impl Default for TrustStore {
+    fn default() -> Self {
+        Self {
+            trust_on_first_use: true,
+            // We do not want to break all currently unsigned plugins. Tampered plugins are refused though
+            minimum_level: PluginVerification::Unsigned,
+            pinned_keys: HashMap::new(),
+        }
+    }
+}
+
+impl TrustStore {
+    pub(crate) fn load<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<Self> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        Ok(match store.get("trust_store") {
+            Some(v) => serde_json::from_value(v)?,
+            None => Self::default(),
+        })
+    }
+
+    pub(crate) fn commit<R: Runtime>(&self, app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        store.set("trust_store", serde_json::to_value(self)?);
+        Ok(())
+    }
+
+    /// Maps the result of a signature check to a verification level.
+    ///
+    /// Once a key is pinned for a plugin, anything but a valid signature by that key is [PluginVerification::Tampered], so dropping the signature or re-signing with another key does not get around the pin.
+    /// A publisher that really changed its key has to be untrusted by the user first.
+    ///
+    /// If trust-on-first-use is enabled, this will pin unknown keys. The returned bool indicates if the Trust Store was modified and needs to be committed.
+    pub(crate) fn classify(
+        &mut self,
+        plugin_id: &str,
+        check: &SignatureCheck,
+    ) -> (PluginVerification, bool) {
+        let pinned = self.pinned_keys.get(plugin_id);
+        let public_key = match check {
+            SignatureCheck::Unsigned if pinned.is_some() => {
+                warn!("plugin {plugin_id} is unsigned, but a publisher key is pinned for it");
+                return (PluginVerification::Tampered, false);
+            }
+            SignatureCheck::Unsigned => return (PluginVerification::Unsigned, false),
+            SignatureCheck::Invalid { reason } => {
+                warn!("plugin {plugin_id} failed signature verification: {reason}");
+                return (PluginVerification::Tampered, false);
+            }
+            SignatureCheck::Valid { public_key } => public_key,
+        };
+
+        match pinned {
+            Some(pinned) if &pinned.public_key == public_key => {
+                (PluginVerification::SignedTrusted, false)
+            }
+            Some(_) => {
+                warn!("plugin {plugin_id} is signed by a different key than the pinned one");
+                (PluginVerification::Tampered, false)
+            }
+            None if self.trust_on_first_use => {
+                self.pin(plugin_id, public_key, false);
+                (PluginVerification::SignedTrusted, true)
+            }
+            None => (PluginVerification::SignedUnknown, false),
+        }
+    }
+
+    pub(crate) fn pin(&mut self, plugin_id: &str, public_key: &str, explicit: bool) {
+        self.pinned_keys.insert(
+            plugin_id.to_string(),
+            PinnedKey {
+                public_key: public_key.to_string(),
+                pinned_at: Utc::now(),
+                explicit,
+            },
+        );
+    }
+
+    pub(crate) fn unpin(&mut self, plugin_id: &str) -> bool {
+        self.pinned_keys.remove(plugin_id).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const KEY_A: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    const KEY_B: &str = "RWTAPRW2qy9FjsBiMVY4XyC3PAzs/7TfXkHFCLvw1uT4WgLTkO8VOX+m";
+
+    fn valid(public_key: &str) -> SignatureCheck {
+        SignatureCheck::Valid {
+            public_key: public_key.into(),
+        }
+    }
+
+    fn store(trust_on_first_use: bool) -> TrustStore {
+        TrustStore {
+            trust_on_first_use,
+            ..Default::default()
+        }
+    }
+
+    fn manifest(publisher_key: Option<&str>) -> PluginManifest {
+        serde_json::from_value(json!({
+            "type": "v1",
+            "name": "test",
+            "publisher_key": publisher_key,
+        }))
+        .unwrap()
+    }
+
+    fn write(dir: &Path, path: &str, contents: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn first_key_is_pinned_on_first_use() {
+        let mut store = store(true);
+        assert_eq!(
+            store.classify("a", &valid(KEY_A)),
+            (PluginVerification::SignedTrusted, true)
+        );
+        assert!(!store.pinned_keys["a"].explicit);
+        // the pin is only written once
+        assert_eq!(
+            store.classify("a", &valid(KEY_A)),
+            (PluginVerification::SignedTrusted, false)
+        );
+    }
+
+    #[test]
+    fn unknown_key_is_not_pinned_without_trust_on_first_use() {
+        let mut store = store(false);
+        assert_eq!(
+            store.classify("a", &valid(KEY_A)),
+            (PluginVerification::SignedUnknown, false)
+        );
+        assert!(store.pinned_keys.is_empty());
+    }
+
+    #[test]
+    fn pinned_plugin_must_be_signed_by_the_pinned_key() {
+        for trust_on_first_use in [true, false] {
+            let mut store = store(trust_on_first_use);
+            store.pin("a", KEY_A, true);
+            assert_eq!(
+                store.classify("a", &valid(KEY_B)),
+                (PluginVerification::Tampered, false)
+            );
+            assert_eq!(
+                store.classify("a", &SignatureCheck::Unsigned),
+                (PluginVerification::Tampered, false)
+            );
+            // the pin is kept, so switching back to the pinned key works
+            assert_eq!(store.pinned_keys["a"].public_key, KEY_A);
+            assert_eq!(
+                store.classify("a", &valid(KEY_A)),
+                (PluginVerification::SignedTrusted, false)
+            );
+        }
+    }
+
+    #[test]
+    fn pins_are_per_plugin() {
+        let mut store = store(false);
+        store.pin("a", KEY_A, true);
+        assert_eq!(
+            store.classify("b", &SignatureCheck::Unsigned),
+            (PluginVerification::Unsigned, false)
+        );
+        assert_eq!(
+            store.classify("b", &valid(KEY_A)),
+            (PluginVerification::SignedUnknown, false)
+        );
+    }
+
+    #[test]
+    fn invalid_signature_is_tampered() {
+        let invalid = SignatureCheck::Invalid {
+            reason: "does not match".into(),
+        };
+        let mut pinned = store(true);
+        pinned.pin("a", KEY_A, true);
+        for mut store in [store(true), store(false), pinned] {
+            assert_eq!(
+                store.classify("a", &invalid),
+                (PluginVerification::Tampered, false)
+            );
+        }
+        // an invalid signature must never be pinned on first use
+        let mut store = store(true);
+        store.classify("a", &invalid);
+        assert!(store.pinned_keys.is_empty());
+    }
+
+    #[test]
+    fn broken_signatures_are_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "manifest.json", "{}");
+        assert!(matches!(
+            check_package(dir.path(), &manifest(None), &FrontendHashCache::new()),
+            SignatureCheck::Unsigned
+        ));
+        assert!(matches!(
+            check_package(
+                dir.path(),
+                &manifest(Some(KEY_A)),
+                &FrontendHashCache::new()
+            ),
+            SignatureCheck::Invalid { .. }
+        ));
+
+        write(dir.path(), SIGNATURE_FILE_NAME, "not a minisign signature");
+        assert!(matches!(
+            check_package(dir.path(), &manifest(None), &FrontendHashCache::new()),
+            SignatureCheck::Invalid { .. }
+        ));
+        assert!(matches!(
+            check_package(
+                dir.path(),
+                &manifest(Some(KEY_A)),
+                &FrontendHashCache::new()
+            ),
+            SignatureCheck::Invalid { .. }
+        ));
+    }
+
+    #[test]
+    fn package_listing_matches_sha256sum() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "manifest.json", "a");
+        write(dir.path(), "frontend/sub/index.js", "b");
+        write(dir.path(), "frontend/B.css", "c");
+        // neither the manifest nor in frontend/, so not covered by the signature
+        write(dir.path(), "README.md", "d");
+        write(dir.path(), SIGNATURE_FILE_NAME, "e");
+
+        // sorted by bytes, so uppercase comes first
+        assert_eq!(
+            package_listing(dir.path(), &manifest(None), &FrontendHashCache::new()).unwrap(),
+            "2e7d2c03a9507ae265ecf5b5356885a53393a2029d241394997265a1a25aefc6  frontend/B.css\n\
+             3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d  frontend/sub/index.js\n\
+             ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb  manifest.json\n"
+        );
+    }
+
+    #[test]
+    fn package_listing_is_stable() {
+        let files = [
+            ("manifest.json", "{}"),
+            ("frontend/index.js", "1"),
+            ("frontend/a/b/c.js", "2"),
+            ("frontend/a-b.js", "3"),
+            ("frontend/A.js", "4"),
+        ];
+        let forward = tempfile::tempdir().unwrap();
+        for (path, contents) in files {
+            write(forward.path(), path, contents);
+        }
+        let backward = tempfile::tempdir().unwrap();
+        for (path, contents) in files.iter().rev() {
+            write(backward.path(), path, contents);
+        }
+
+        let cache = FrontendHashCache::new();
+        let listing = package_listing(forward.path(), &manifest(None), &cache).unwrap();
+        // the listing does not depend on the order files were created in, or on the location of the plugin folder
+        assert_eq!(
+            listing,
+            package_listing(backward.path(), &manifest(None), &FrontendHashCache::new()).unwrap()
+        );
+        assert_eq!(
+            listing,
+            package_listing(forward.path(), &manifest(None), &cache).unwrap()
+        );
+        assert_eq!(listing.lines().count(), files.len());
+
+        // files outside of the listing do not change it, files inside do, even if their digest was cached
+        write(forward.path(), "data/cache.json", "x");
+        assert_eq!(
+            listing,
+            package_listing(forward.path(), &manifest(None), &cache).unwrap()
+        );
+        write(forward.path(), "frontend/a/b/c.js", "changed");
+        assert_ne!(
+            listing,
+            package_listing(forward.path(), &manifest(None), &cache).unwrap()
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Permissions plugins request in their manifest and the user grants.
+//!
+//! The requested permissions are derived from the manifest (see [requested]): the `permissions` section (see [PluginPermissions]),
+//! a declared sidecar, the capabilities of a declared WASM backend and the message bus topics. The user grants them in the settings window, which stores the grants in [GenericPluginSettings::granted_permissions].
+//! Embedded plugins are trusted and have all permissions.
+//!
+//! Armored commands check the grant with [check] and answer with a `PERMISSION_DENIED` error (see [denied]) if it is missing.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tracing::error;
+use url::Host;
+
+use super::{
+    contracts::CommandError, generic_plugin_settings::GenericPluginSettings,
+    message_bus::PluginMessageBus, plugin_manifest::PluginManifest,
+    wasm_backend::BackendCapability, PluginState, PluginStateSource,
+};
+
+/// The permissions section of a plugin manifest
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema, Default)]
+pub(crate) struct PluginPermissions {
+    /// Domains the plugin may open URLs on without asking the user. `*.example.com` matches `example.com` and all of its subdomains
+    #[serde(default)]
+    pub(crate) url_domains: Vec<String>,
+    /// Journal event classes (the `event` field, e.g. `FSDJump`) the plugin receives. `*` for all events
+    #[serde(default)]
+    pub(crate) journal_events: Vec<String>,
+    /// IDs of other plugins whose public settings this plugin may read. `*` for all plugins
+    #[serde(default)]
+    pub(crate) settings_read: Vec<String>,
+    /// Allows the plugin to store files in its own storage folder, see `writeFile` of the plugin context
+    #[serde(default)]
+    pub(crate) file_storage: bool,
+}
+
+/// A single permission. Used both for what a plugin requested and what it was granted
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub(crate) enum Permission {
+    #[serde(rename_all = "camelCase")]
+    UrlDomain {
+        domain: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    JournalEvents {
+        class: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    SettingsRead {
+        plugin_id: String,
+    },
+    Sidecar,
+    /// Reading and writing files in the plugin's storage folder, see [super::plugin_files]
+    FileStorage,
+    /// A capability of the plugin's WASM backend
+    #[serde(rename_all = "camelCase")]
+    Backend {
+        capability: BackendCapability,
+    },
+    /// `topic` is the topic name without the plugin ID
+    #[serde(rename_all = "camelCase")]
+    BusPublish {
+        topic: String,
+    },
+    /// `topic` is a full topic name or pattern, as in [PluginMessageBus::subscribes]
+    #[serde(rename_all = "camelCase")]
+    BusSubscribe {
+        topic: String,
+    },
+}
+
+impl Permission {
+    /// Returns whether this permission, if granted, allows `needed`
+    pub(crate) fn covers(&self, needed: &Permission) -> bool {
+        match (self, needed) {
+            (Permission::UrlDomain { domain }, Permission::UrlDomain { domain: host }) => {
+                let host = ascii_domain(host);
+                match domain.strip_prefix("*.") {
+                    Some(base) => {
+                        let base = ascii_domain(base);
+                        host == base || host.ends_with(&format!(".{base}"))
+                    }
+                    None => ascii_domain(domain) == host,
+                }
+            }
+            (Permission::JournalEvents { class }, Permission::JournalEvents { class: needed }) => {
+                class == "*" || class == needed
+            }
+            (
+                Permission::SettingsRead { plugin_id },
+                Permission::SettingsRead { plugin_id: needed },
+            ) => plugin_id == "*" || plugin_id == needed,
+            _ => self == needed,
+        }
+    }
+}
+
+/// Returns `domain` the way URLs are parsed (see [url_policy::host_of](super::url_policy::host_of)): lowercase, with internationalized labels in punycode
+fn ascii_domain(domain: &str) -> String {
+    match Host::parse(domain) {
+        Ok(x) => x.to_string(),
+        Err(_) => domain.to_ascii_lowercase(),
+    }
+}
+
+/// Returns all permissions the manifest requests
+pub(crate) fn requested(manifest: &PluginManifest) -> Vec<Permission> {
+    let mut permissions = vec![];
+    if let Some(x) = manifest.permissions() {
+        permissions.extend(x.url_domains.iter().map(|domain| Permission::UrlDomain {
+            domain: domain.clone(),
+        }));
+        permissions.extend(
+            x.journal_events
+                .iter()
+                .map(|class| Permission::JournalEvents {
+                    class: class.clone(),
+                }),
+        );
+        permissions.extend(
+            x.settings_read
+                .iter()
+                .map(|plugin_id| Permission::SettingsRead {
+                    plugin_id: plugin_id.clone(),
+                }),
+        );
+        if x.file_storage {
+            permissions.push(Permission::FileStorage);
+        }
+    }
+    if manifest.sidecar().is_some() {
+        permissions.push(Permission::Sidecar);
+    }
+    if let Some(x) = manifest.backend() {
+        permissions.extend(x.capabilities.iter().map(|x| x.permission()));
+    }
+    if let Some(x) = manifest.message_bus() {
+        permissions.extend(x.publishes.iter().map(|topic| Permission::BusPublish {
+            topic: topic.clone(),
+        }));
+        permissions.extend(x.subscribes.iter().map(|topic| Permission::BusSubscribe {
+            topic: topic.clone(),
+        }));
+    }
+    permissions
+}
+
+/// Returns the permissions the user granted to the plugin. Embedded plugins are granted everything they request
+pub(crate) fn granted<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin: &PluginState,
+) -> Vec<Permission> {
+    if plugin.source == PluginStateSource::Embedded {
+        return requested(&plugin.manifest);
+    }
+    match GenericPluginSettings::get_by_id(app_handle, &plugin.id) {
+        Ok(x) => x.map(|x| x.granted_permissions).unwrap_or_default(),
+        Err(e) => {
+            error!("failed to load permissions of {}: {e}", plugin.id);
+            vec![]
+        }
+    }
+}
+
+/// Returns whether `plugin` was granted a permission that allows `needed`
+pub(crate) fn is_granted<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin: &PluginState,
+    needed: &Permission,
+) -> bool {
+    granted(app_handle, plugin).iter().any(|x| x.covers(needed))
+}
+
+/// The error of a command that is missing a permission
+pub(crate) fn denied(needed: &Permission) -> CommandError {
+    CommandError::PermissionDenied {
+        permission: needed.clone(),
+    }
+}
+
+/// Checks the permission for an armored command. On failure, the error response is returned
+pub(crate) fn check<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin: &PluginState,
+    needed: &Permission,
+) -> Result<(), CommandError> {
+    match is_granted(app_handle, plugin, needed) {
+        true => Ok(()),
+        false => Err(denied(needed)),
+    }
+}
+
+/// Returns the message bus config of the plugin, reduced to the topics it was granted
+pub(crate) fn granted_message_bus<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin: &PluginState,
+) -> Option<PluginMessageBus> {
+    let config = plugin.manifest.message_bus()?;
+    let granted = granted(app_handle, plugin);
+    Some(PluginMessageBus {
+        publishes: config
+            .publishes
+            .iter()
+            .filter(|topic| {
+                granted.contains(&Permission::BusPublish {
+                    topic: topic.to_string(),
+                })
+            })
+            .cloned()
+            .collect(),
+        subscribes: config
+            .subscribes
+            .iter()
+            .filter(|topic| {
+                granted.contains(&Permission::BusSubscribe {
+                    topic: topic.to_string(),
+                })
+            })
+            .cloned()
+            .collect(),
+    })
+}
@@ -0,0 +1,213 @@
+//! This Module handles the file storage of plugins. Each plugin with [Permission::FileStorage] gets a folder of its own, see [crate::portable::plugin_files_dir].
+//! When invoking this module, access control should already be handled
+//!
+//! Plugins name their files by a relative path inside their folder, which is validated by [parse_path] so it cannot leave the folder.
+//! All files of a plugin together are limited in size (see [CommandLimits::files_quota_bytes]). Writes that would grow them beyond it fail with [QuotaExceeded].
+//!
+//! [Permission::FileStorage]: super::permissions::Permission::FileStorage
+//! [CommandLimits::files_quota_bytes]: super::generic_plugin_settings::CommandLimits::files_quota_bytes
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use tauri::{AppHandle, Runtime};
+
+use crate::portable::plugin_files_dir;
+
+use super::{contracts::CommandError, plugin_settings::QuotaExceeded};
+
+/// How many segments a path may have, e.g. `a/b/c.json` has 3
+const MAX_PATH_SEGMENTS: usize = 8;
+/// How long a single segment may be. Kept well below the limits of common file systems
+const MAX_SEGMENT_LEN: usize = 64;
+
+/// A file in the storage folder of a plugin
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PluginFile {
+    /// Relative to the storage folder, with `/` as separator
+    pub(crate) path: String,
+    pub(crate) size: u64,
+}
+
+/// The storage folder of a plugin. It is only created once the plugin writes a file
+pub(crate) fn storage_dir<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin_id: &str,
+) -> anyhow::Result<PathBuf> {
+    Ok(plugin_files_dir(app_handle)
+        .ok_or(anyhow::anyhow!("failed to resolve the plugin files dir"))?
+        .join(plugin_id))
+}
+
+/// Turns a path given by a plugin into one relative to its storage folder.
+///
+/// Segments are separated by `/` and may only contain ASCII letters, digits, `-`, `_` and `.`. Segments made of dots only are rejected, so `..` cannot leave the folder.
+pub(crate) fn parse_path(path: &str) -> Result<PathBuf, CommandError> {
+    let segments = path.split('/').collect::<Vec<_>>();
+    if segments.len() > MAX_PATH_SEGMENTS {
+        return Err(CommandError::FilePathInvalid);
+    }
+    let mut parsed = PathBuf::new();
+    for segment in segments {
+        let valid = !segment.is_empty()
+            && segment.len() <= MAX_SEGMENT_LEN
+            && segment
+                .chars()
+                .all(|x| x.is_ascii_alphanumeric() || matches!(x, '-' | '_' | '.'))
+            && !segment.chars().all(|x| x == '.');
+        if !valid {
+            return Err(CommandError::FilePathInvalid);
+        }
+        parsed.push(segment);
+    }
+    Ok(parsed)
+}
+
+/// Lists all files in the storage folder, sorted by path. A missing folder has no files
+pub(crate) fn list(dir: &Path) -> anyhow::Result<Vec<PluginFile>> {
+    let mut files = vec![];
+    list_into(dir, "", &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn list_into(dir: &Path, prefix: &str, files: &mut Vec<PluginFile>) -> anyhow::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(x) => x,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(|x| format!("{prefix}{x}")) else {
+            continue;
+        };
+        // Symlinks are never followed, the plugin could not have created them anyways
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            list_into(&entry.path(), &format!("{name}/"), files)?;
+        } else if metadata.is_file() {
+            files.push(PluginFile {
+                path: name,
+                size: metadata.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Reads a file from the storage folder. Returns [None] if it does not exist
+pub(crate) fn read(dir: &Path, path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    match fs::read(dir.join(path)) {
+        Ok(x) => Ok(Some(x)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes a file to the storage folder, replacing it if it exists
+pub(crate) fn write(dir: &Path, path: &Path, contents: &[u8], quota: u64) -> anyhow::Result<()> {
+    let target = dir.join(path);
+    let current_size: u64 = list(dir)?.iter().map(|x| x.size).sum();
+    let replaced_size = target.metadata().map(|x| x.len()).unwrap_or_default();
+    let new_size = current_size.saturating_sub(replaced_size) + contents.len() as u64;
+    // a folder that is already too large may still shrink
+    if new_size > quota && new_size > current_size {
+        return Err(QuotaExceeded.into());
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(target, contents)?;
+    Ok(())
+}
+
+/// Deletes a file from the storage folder. Returns `false` if it did not exist
+pub(crate) fn delete(dir: &Path, path: &Path) -> anyhow::Result<bool> {
+    match fs::remove_file(dir.join(path)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_stay_inside_the_folder() {
+        assert_eq!(parse_path("a.json").unwrap(), PathBuf::from("a.json"));
+        assert_eq!(
+            parse_path("cache/.hidden-1_2").unwrap(),
+            PathBuf::from("cache").join(".hidden-1_2")
+        );
+        for path in [
+            "",
+            "..",
+            "a/../../b",
+            "./a",
+            "/etc/passwd",
+            "a//b",
+            "a/",
+            "C:\\a",
+            "a\\..\\b",
+            "ä.json",
+            "a/b/c/d/e/f/g/h/i",
+        ] {
+            assert!(parse_path(path).is_err(), "{path} must be rejected");
+        }
+    }
+
+    #[test]
+    fn files_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("plugin");
+        assert_eq!(list(&root).unwrap(), vec![]);
+        assert_eq!(read(&root, Path::new("a")).unwrap(), None);
+
+        write(&root, &parse_path("b/c.txt").unwrap(), b"hello", 100).unwrap();
+        write(&root, &parse_path("a").unwrap(), b"", 100).unwrap();
+        assert_eq!(
+            list(&root).unwrap(),
+            vec![
+                PluginFile {
+                    path: "a".into(),
+                    size: 0
+                },
+                PluginFile {
+                    path: "b/c.txt".into(),
+                    size: 5
+                },
+            ]
+        );
+        assert_eq!(
+            read(&root, &parse_path("b/c.txt").unwrap()).unwrap(),
+            Some(b"hello".to_vec())
+        );
+
+        assert!(delete(&root, Path::new("a")).unwrap());
+        assert!(!delete(&root, Path::new("a")).unwrap());
+        assert_eq!(list(&root).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn writes_are_limited_by_quota() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, Path::new("a"), &[0; 6], 10).unwrap();
+        let e = write(root, Path::new("b"), &[0; 5], 10).unwrap_err();
+        assert!(e.is::<QuotaExceeded>());
+        // replacing a file only counts the difference
+        write(root, Path::new("a"), &[0; 10], 10).unwrap();
+        assert!(write(root, Path::new("a"), &[0; 11], 10).is_err());
+        // a folder over its quota (e.g. after the quota was lowered) may still shrink
+        write(root, Path::new("a"), &[0; 8], 4).unwrap();
+    }
+}
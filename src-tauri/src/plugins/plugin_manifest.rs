@@ -1,18 +1,71 @@
 //! This module defines what a Plugin Manifest looks like
 
-use schemars::JsonSchema;
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use tauri::Wry;
 
+use super::{
+    message_bus::PluginMessageBus, permissions::PluginPermissions, sidecar::PluginSidecar,
+    wasm_backend::PluginBackend,
+};
+
+/// All manifest versions EDPF understands, oldest first. The JSON Schema of each version is served by the [super::frontend_server]
+/// and written to `src-tauri/schemas` by [PluginManifest::export_json_schemas]
+pub(crate) const MANIFEST_VERSIONS: &[&str] = &["v1alpha", "v1"];
+
 /// Each Plugin must have a `manifest.json` which describes the plugin, it's requirements, updating strategies, and so on.
+///
+/// When a manifest is loaded, it is upgraded to the latest version using [PluginManifest::upgrade]. The rest of EDPF therefore only ever sees [PluginManifest::V1].
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "type")]
 pub(crate) enum PluginManifest {
     #[serde(rename = "v1alpha")]
     V1Alpha(PluginManifestV1Alpha),
+    #[serde(rename = "v1")]
+    V1(Box<PluginManifestV1>),
 }
+
+/// Version 1 is the stable manifest version. Changes to it must be backwards-compatible.
+///
+/// Point `$schema` to `http://localhost:$port/schemas/manifest/v1.json` to get validation and autocompletion in your editor.
+/// The port is only stable if EDPF could bind its preferred port. A copy of the schema is committed to `src-tauri/schemas/manifest/v1.json`, which can be referenced as a file instead.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+pub(crate) struct PluginManifestV1 {
+    /// What is this plugin's name?
+    /// This name shouldn't change over time as the internal ID and plugin-stored settings are tied to it
+    pub(crate) name: String,
+    /// A short description about what this Plugin is doing
+    pub(crate) description: Option<String>,
+    /// optionally, a URL to the Git Repository
+    pub(crate) repository_url: Option<String>,
+    /// optionally, a link where the user can get support. Can be a Discord Link, Github Issues, etc.
+    pub(crate) support_url: Option<String>,
+    /// Put a semantic version here (e.g. `0.0.1`)
+    pub(crate) version: Option<String>,
+    /// A list of versions. This is ignored from the local file and only the remote manifest is considered. Look at [PluginManifestV1::remote_manifest]
+    pub(crate) versions: Option<Vec<PluginVersionOption>>,
+    /// This contains the strategy the plugin should take during updating to find out if there is a new update
+    pub(crate) remote_manifest: Option<PluginRemoteManifestResolutionStrategy>,
+    /// optionally, a minisign public key (base64). If set, the plugin folder must contain a signature made with this key.
+    /// See [super::package_signature] for details
+    pub(crate) publisher_key: Option<String>,
+    /// optionally, a native executable EDPF runs alongside the plugin. See [super::sidecar] for details
+    pub(crate) sidecar: Option<PluginSidecar>,
+    /// optionally, a WASM module EDPF runs headless for the plugin. See [super::wasm_backend] for details
+    pub(crate) backend: Option<PluginBackend>,
+    /// optionally, which message bus topics this plugin publishes and subscribes to. See [super::message_bus] for details
+    pub(crate) message_bus: Option<PluginMessageBus>,
+    /// optionally, the permissions this plugin needs. The user is asked to grant them. See [super::permissions] for details
+    pub(crate) permissions: Option<PluginPermissions>,
+}
+
 /// Version 1alpha is the initial version that may introduce breaking changes.
-/// Once the MVP is finished, this can be promoted to v1
+///
+/// **Deprecated**: This version is automatically upgraded to [PluginManifestV1] when loaded. Plugin authors should switch to `v1`.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
 pub(crate) struct PluginManifestV1Alpha {
     /// What is this plugin's name?
@@ -31,6 +84,9 @@ pub(crate) struct PluginManifestV1Alpha {
     pub(crate) versions: Option<Vec<PluginVersionOption>>,
     /// This contains the strategy the plugin should take during updating to find out if there is a new update
     pub(crate) remote_manifest: Option<PluginRemoteManifestResolutionStrategy>,
+    /// optionally, a minisign public key (base64). If set, the plugin folder must contain a signature made with this key.
+    /// See [super::package_signature] for details
+    pub(crate) publisher_key: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
@@ -55,10 +111,137 @@ pub(crate) enum PluginRemoteManifestResolutionStrategy {
     UnofficialRegistry { address: String },
 }
 
+/// v1alpha plugins could not declare permissions, but received all journal events and could read all public settings.
+/// An upgraded manifest requests the same, so the plugin keeps working once the user granted it
+impl From<PluginManifestV1Alpha> for PluginManifestV1 {
+    fn from(value: PluginManifestV1Alpha) -> Self {
+        Self {
+            name: value.name,
+            description: value.description,
+            repository_url: value.repository_url,
+            support_url: value.support_url,
+            version: value.version,
+            versions: value.versions,
+            remote_manifest: value.remote_manifest,
+            publisher_key: value.publisher_key,
+            sidecar: None,
+            backend: None,
+            message_bus: None,
+            permissions: Some(PluginPermissions {
+                journal_events: vec!["*".into()],
+                settings_read: vec!["*".into()],
+                ..Default::default()
+            }),
+        }
+    }
+}
+
 impl PluginManifest {
+    /// Upgrades the manifest to the latest version. Returns the upgraded manifest and a list of deprecation warnings meant for the plugin author.
+    pub(crate) fn upgrade(self) -> (Self, Vec<String>) {
+        match self {
+            PluginManifest::V1Alpha(x) => (
+                PluginManifest::V1(Box::new(x.into())),
+                vec![
+                    "manifest type 'v1alpha' is deprecated and was upgraded automatically. Please change the type to 'v1'".into(),
+                    "the upgraded manifest requests all journal events and all public settings, which the user has to grant. Declare the permissions the plugin needs in the 'permissions' section instead".into(),
+                ],
+            ),
+            PluginManifest::V1(_) => (self, vec![]),
+        }
+    }
+
     pub(crate) fn inject_embedded_version(&mut self, app: &tauri::AppHandle<Wry>) {
+        let version = Some(app.package_info().version.to_string());
         match self {
-            PluginManifest::V1Alpha(x) => x.version = Some(app.package_info().version.to_string()),
+            PluginManifest::V1Alpha(x) => x.version = version,
+            PluginManifest::V1(x) => x.version = version,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            PluginManifest::V1Alpha(x) => &x.name,
+            PluginManifest::V1(x) => &x.name,
+        }
+    }
+
+    pub(crate) fn publisher_key(&self) -> Option<&str> {
+        match self {
+            PluginManifest::V1Alpha(x) => x.publisher_key.as_deref(),
+            PluginManifest::V1(x) => x.publisher_key.as_deref(),
+        }
+    }
+
+    pub(crate) fn sidecar(&self) -> Option<&PluginSidecar> {
+        match self {
+            PluginManifest::V1Alpha(_) => None,
+            PluginManifest::V1(x) => x.sidecar.as_ref(),
+        }
+    }
+
+    pub(crate) fn backend(&self) -> Option<&PluginBackend> {
+        match self {
+            PluginManifest::V1Alpha(_) => None,
+            PluginManifest::V1(x) => x.backend.as_ref(),
+        }
+    }
+
+    pub(crate) fn message_bus(&self) -> Option<&PluginMessageBus> {
+        match self {
+            PluginManifest::V1Alpha(_) => None,
+            PluginManifest::V1(x) => x.message_bus.as_ref(),
+        }
+    }
+
+    pub(crate) fn permissions(&self) -> Option<&PluginPermissions> {
+        match self {
+            PluginManifest::V1Alpha(_) => None,
+            PluginManifest::V1(x) => x.permissions.as_ref(),
+        }
+    }
+
+    /// Returns the JSON Schema for a single manifest version (see [MANIFEST_VERSIONS]), or for any version if `version` is [None].
+    pub(crate) fn json_schema(version: Option<&str>) -> Option<Value> {
+        let (mut schema, tag) = match version {
+            None => return Some(schema_for!(PluginManifest).to_value()),
+            Some("v1alpha") => (schema_for!(PluginManifestV1Alpha), "v1alpha"),
+            Some("v1") => (schema_for!(PluginManifestV1), "v1"),
+            Some(_) => return None,
+        };
+
+        // The version structs do not know about the tag. We add it here, so the schema matches a full manifest.json
+        let object = schema.ensure_object();
+        let properties = object.entry("properties").or_insert_with(|| json!({}));
+        if let Some(properties) = properties.as_object_mut() {
+            properties.insert("type".into(), json!({"const": tag}));
+            // Allows editors to pick up the schema from within the manifest
+            properties.insert("$schema".into(), json!({"type": "string"}));
+        }
+        let required = object.entry("required").or_insert_with(|| json!([]));
+        if let Some(required) = required.as_array_mut() {
+            required.push(json!("type"));
+        }
+        Some(schema.to_value())
+    }
+
+    /// Writes `manifest.json` (any version) and `manifest/$version.json` for each of [MANIFEST_VERSIONS] to `dir`, creating it if needed
+    pub(crate) fn export_json_schemas(dir: &Path) -> anyhow::Result<()> {
+        let versions = std::iter::once((None, dir.join("manifest.json"))).chain(
+            MANIFEST_VERSIONS
+                .iter()
+                .map(|x| (Some(*x), dir.join("manifest").join(format!("{x}.json")))),
+        );
+        for (version, path) in versions {
+            let schema = Self::json_schema(version).ok_or_else(|| {
+                anyhow::anyhow!("no JSON Schema for manifest version {version:?}")
+            })?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")?;
         }
+        Ok(())
     }
 }
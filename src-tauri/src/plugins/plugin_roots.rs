@@ -0,0 +1,159 @@
+//! Plugins can be loaded from several folders ("roots"), e.g. a read-only folder with curated team plugins next to the user's own plugin folder.
+//!
+//! If two roots contain a plugin with the same ID, the root with the higher precedence wins. From highest to lowest:
+//! 1. [PluginRootKind::Dev]: plugins linked for development, see [super::dev_plugins]
+//! 2. [PluginRootKind::User]: the user plugin dir, see [super::user_plugin_dir]
+//! 3. [PluginRootKind::Shared]: shared folders, e.g. on a network drive. In the order they are configured
+//! 4. [PluginRootKind::System]: system-wide folders. In the order they are configured
+//!
+//! Shared and System roots are stored in `store.json` under `plugin_roots`. They are treated as read-only.
+
+use std::path::PathBuf;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_store::StoreBuilder;
+
+use crate::portable::store_path;
+
+use super::user_plugin_dir;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub(crate) enum PluginRootKind {
+    Dev,
+    User,
+    Shared,
+    System,
+}
+
+/// A folder plugins were loaded from. For dev plugins, this is the linked plugin folder itself.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub(crate) struct PluginRoot {
+    pub(crate) kind: PluginRootKind,
+    pub(crate) path: PathBuf,
+}
+
+/// The configurable roots besides the user plugin dir
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub(crate) struct PluginRootsConfig {
+    pub(crate) shared: Vec<PathBuf>,
+    pub(crate) system: Vec<PathBuf>,
+}
+
+impl PluginRootsConfig {
+    pub(crate) fn load<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<Self> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        Ok(match store.get("plugin_roots") {
+            Some(v) => serde_json::from_value(v)?,
+            None => Self::default(),
+        })
+    }
+
+    pub(crate) fn commit<R: Runtime>(&self, app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+        let store = match StoreBuilder::new(app_handle, store_path("store.json")).build() {
+            Ok(x) => x,
+            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+        };
+
+        store.set("plugin_roots", serde_json::to_value(self)?);
+        Ok(())
+    }
+
+    /// Orders the user plugin dir and the configured roots by precedence, see [search_roots]
+    fn roots(self, user_plugin_dir: PathBuf) -> Vec<PluginRoot> {
+        let mut roots = vec![PluginRoot {
+            kind: PluginRootKind::User,
+            path: user_plugin_dir,
+        }];
+        roots.extend(self.shared.into_iter().map(|path| PluginRoot {
+            kind: PluginRootKind::Shared,
+            path,
+        }));
+        roots.extend(self.system.into_iter().map(|path| PluginRoot {
+            kind: PluginRootKind::System,
+            path,
+        }));
+        // A folder configured twice would shadow itself
+        let mut seen = vec![];
+        roots.retain(|x| {
+            let new = !seen.contains(&x.path);
+            seen.push(x.path.clone());
+            new
+        });
+        roots
+    }
+}
+
+/// Returns the roots that are searched for plugin folders, ordered from highest to lowest precedence.
+///
+/// Dev plugins are not part of this, as they are linked one by one.
+pub(crate) fn search_roots<R: Runtime>(
+    app_handle: &AppHandle<R>,
+) -> anyhow::Result<Vec<PluginRoot>> {
+    Ok(PluginRootsConfig::load(app_handle)?.roots(user_plugin_dir(app_handle)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root(kind: PluginRootKind, path: &str) -> PluginRoot {
+        PluginRoot {
+            kind,
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn roots_are_ordered_by_precedence() {
+        let config = PluginRootsConfig {
+            shared: vec!["/mnt/team".into(), "/mnt/squadron".into()],
+            system: vec!["/opt/b".into(), "/opt/a".into()],
+        };
+        assert_eq!(
+            config.roots("/home/cmdr/plugins".into()),
+            vec![
+                root(PluginRootKind::User, "/home/cmdr/plugins"),
+                root(PluginRootKind::Shared, "/mnt/team"),
+                root(PluginRootKind::Shared, "/mnt/squadron"),
+                root(PluginRootKind::System, "/opt/b"),
+                root(PluginRootKind::System, "/opt/a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn folders_configured_twice_keep_their_highest_precedence() {
+        let config = PluginRootsConfig {
+            shared: vec!["/mnt/team".into(), "/home/cmdr/plugins".into()],
+            system: vec!["/mnt/team".into(), "/opt/a".into(), "/opt/a".into()],
+        };
+        assert_eq!(
+            config.roots("/home/cmdr/plugins".into()),
+            vec![
+                root(PluginRootKind::User, "/home/cmdr/plugins"),
+                root(PluginRootKind::Shared, "/mnt/team"),
+                root(PluginRootKind::System, "/opt/a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_roots_in_the_store_default_to_none() {
+        let config: PluginRootsConfig =
+            serde_json::from_value(serde_json::json!({"shared": ["/mnt/team"]})).unwrap();
+        assert_eq!(
+            config.roots("/home/cmdr/plugins".into()),
+            vec![
+                root(PluginRootKind::User, "/home/cmdr/plugins"),
+                root(PluginRootKind::Shared, "/mnt/team"),
+            ]
+        );
+    }
+}
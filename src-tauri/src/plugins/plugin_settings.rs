@@ -1,20 +1,43 @@
 //! This Module handles CRUD ops for plugin-*specific* settings. This is something that each plugin can define. The structure of the config structure is not of relevance to EDPF
 //! When invoking this module, access control should already be handled
+//!
+//! Each plugin's store is limited in size (see [CommandLimits::settings_quota_bytes]). Writes that would grow a store beyond it fail with [QuotaExceeded].
+
+use std::fmt::Display;
 
 use itertools::Itertools;
 use tauri::{AppHandle, Runtime};
 use tauri_plugin_store::StoreBuilder;
 
+use crate::portable::store_path;
+
+use super::{contracts::CommandError, generic_plugin_settings::CommandLimits};
+
 pub(crate) fn write_setting<R: Runtime>(
     app_handle: &AppHandle<R>,
     key: &ParsedKey,
     value: serde_json::Value,
 ) -> anyhow::Result<serde_json::Value> {
-    let store =
-        match StoreBuilder::new(app_handle, format!("plugin-{}.json", &key.plugin_id)).build() {
-            Ok(x) => x,
-            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
-        };
+    let store = match StoreBuilder::new(
+        app_handle,
+        store_path(&format!("plugin-{}.json", &key.plugin_id)),
+    )
+    .build()
+    {
+        Ok(x) => x,
+        Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+    };
+    let quota = CommandLimits::for_plugin(app_handle, &key.plugin_id)?.settings_quota_bytes;
+    let mut entries: serde_json::Map<String, serde_json::Value> =
+        store.entries().into_iter().collect();
+    let current_size = serde_json::to_vec(&entries)?.len() as u64;
+    entries.insert(key.remainder.clone(), value.clone());
+    let new_size = serde_json::to_vec(&entries)?.len() as u64;
+    // a store that is already too large may still shrink
+    if new_size > quota && new_size > current_size {
+        return Err(QuotaExceeded.into());
+    }
+
     store.set(&key.remainder, value);
     store.save()?;
     Ok(store.get(&key.remainder).unwrap())
@@ -24,21 +47,47 @@ pub(crate) fn read_setting<R: Runtime>(
     app_handle: &AppHandle<R>,
     key: &ParsedKey,
 ) -> anyhow::Result<Option<serde_json::Value>> {
-    let store =
-        match StoreBuilder::new(app_handle, format!("plugin-{}.json", &key.plugin_id)).build() {
-            Ok(x) => x,
-            Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
-        };
+    let store = match StoreBuilder::new(
+        app_handle,
+        store_path(&format!("plugin-{}.json", &key.plugin_id)),
+    )
+    .build()
+    {
+        Ok(x) => x,
+        Err(e) => return Err(anyhow::anyhow!("failed to build store: {e}")),
+    };
     Ok(store.get(&key.remainder))
 }
 
+/// The write would grow the plugin's settings store beyond its quota
+#[derive(Debug)]
+pub(crate) struct QuotaExceeded;
+
+impl Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QUOTA_EXCEEDED")
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
 pub(crate) struct ParsedKey {
     plugin_id: String,
     remainder: String,
     is_public: bool,
 }
 
+impl Display for ParsedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.plugin_id, self.remainder)
+    }
+}
+
 impl ParsedKey {
+    /// The plugin the setting belongs to
+    pub(crate) fn plugin_id(&self) -> &str {
+        &self.plugin_id
+    }
     pub(crate) fn is_readable_by(&self, plugin_id: &str) -> bool {
         self.is_public || self.plugin_id == plugin_id
     }
@@ -47,19 +96,19 @@ impl ParsedKey {
     }
 }
 
-pub(crate) fn parse_key(input: &str) -> Result<ParsedKey, String> {
+pub(crate) fn parse_key(input: &str) -> Result<ParsedKey, CommandError> {
     if !input
         .chars()
         .all(|c| c.is_alphanumeric() || c == '.' || c == '_')
     {
-        return Err("KEY_INVALID_FORMAT".to_string());
+        return Err(CommandError::KeyInvalidFormat);
     }
     let segments = input.split(".").collect_vec();
     if segments.len() < 2 {
-        return Err("KEY_NOT_ENOUGH_SEGMENTS".to_string());
+        return Err(CommandError::KeyNotEnoughSegments);
     }
     if segments.iter().any(|x| x.is_empty()) {
-        return Err("KEY_EMPTY_SEGMENT".to_string());
+        return Err(CommandError::KeyEmptySegment);
     }
     let last = segments.last().unwrap().chars().next().unwrap();
     // we have an uppercase string. hence we're public
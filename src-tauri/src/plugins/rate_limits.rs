@@ -0,0 +1,270 @@
+//! Per-plugin rate limits on the commands plugins invoke.
+//!
+//! Each plugin has a token bucket per [CommandClass]. A call takes a token, tokens are refilled continuously at the configured rate (see [CommandLimits]).
+//! Calls without a token are rejected with `RATE_LIMITED`. Only calls made by plugins are limited, the core frontend is not.
+//! The size quota of plugin settings stores is enforced by [super::plugin_settings::write_setting].
+//!
+//! Rejections are recorded in the audit log, at most once per [RECORD_INTERVAL] for each plugin, command class and reason.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::warn;
+
+use super::{
+    audit_log::{AuditAction, AuditLog},
+    contracts::{CommandError, RateLimitedMeta},
+    generic_plugin_settings::CommandLimits,
+};
+
+/// How often a rejection of the same kind is recorded in the audit log
+const RECORD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Commands are limited by class. Each class has its own bucket
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum CommandClass {
+    OpenUrl,
+    WriteSetting,
+    ReadSetting,
+    /// Messages between a plugin's frontend and its sidecar or WASM backend
+    BackendMessage,
+    BusPublish,
+    RereadJournal,
+    WriteFile,
+    ReadFile,
+}
+
+impl CommandClass {
+    pub(crate) fn default_limit(self) -> RateLimit {
+        let (burst, per_minute) = match self {
+            CommandClass::OpenUrl => (3, 6),
+            CommandClass::WriteSetting => (20, 120),
+            CommandClass::ReadSetting => (100, 1200),
+            CommandClass::BackendMessage => (50, 600),
+            CommandClass::BusPublish => (50, 600),
+            CommandClass::RereadJournal => (2, 6),
+            CommandClass::WriteFile => (20, 120),
+            CommandClass::ReadFile => (100, 1200),
+        };
+        RateLimit { burst, per_minute }
+    }
+}
+
+/// A token bucket
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub(crate) struct RateLimit {
+    /// How many calls can be made at once
+    pub(crate) burst: u32,
+    /// How many calls per minute can be made in the long run
+    pub(crate) per_minute: u32,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Managed by Tauri. Holds the buckets of all plugins
+pub(crate) struct RateLimiter {
+    buckets: Mutex<HashMap<(String, CommandClass), Bucket>>,
+    /// When a rejection was last recorded, by plugin, command class and reason
+    recorded: Mutex<HashMap<(String, CommandClass, &'static str), Instant>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            recorded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from the `class` bucket of `plugin_id`. `None` is the core frontend, which is never limited.
+    /// Returns the error to reject the call with if the bucket is empty
+    pub(crate) fn check<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        plugin_id: Option<&str>,
+        class: CommandClass,
+    ) -> Result<(), CommandError> {
+        let Some(plugin_id) = plugin_id else {
+            return Ok(());
+        };
+        let limit = match CommandLimits::for_plugin(app_handle, plugin_id) {
+            Ok(x) => x.rate_limit(class),
+            Err(e) => {
+                warn!("failed to load command limits of {plugin_id}, using defaults: {e}");
+                class.default_limit()
+            }
+        };
+
+        let Err(retry_after_ms) = self.take(plugin_id, class, limit, Instant::now()) else {
+            return Ok(());
+        };
+        self.record(app_handle, plugin_id, class, "RATE_LIMITED");
+        Err(CommandError::RateLimited {
+            meta: RateLimitedMeta { retry_after_ms },
+        })
+    }
+
+    /// Takes a token from the `class` bucket of `plugin_id`, after refilling it up to `now`.
+    /// If the bucket is empty, returns in how many milliseconds the next token is available. `None` if the bucket is never refilled
+    fn take(
+        &self,
+        plugin_id: &str,
+        class: CommandClass,
+        limit: RateLimit,
+        now: Instant,
+    ) -> Result<(), Option<u64>> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry((plugin_id.to_string(), class))
+            .or_insert(Bucket {
+                tokens: limit.burst as f64,
+                refilled_at: now,
+            });
+        let refill = (now - bucket.refilled_at).as_secs_f64() * limit.per_minute as f64 / 60.0;
+        bucket.tokens = (bucket.tokens + refill).min(limit.burst as f64);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        // a bucket without refill stays empty
+        Err((limit.per_minute > 0)
+            .then(|| ((1.0 - bucket.tokens) * 60_000.0 / limit.per_minute as f64).ceil() as u64))
+    }
+
+    /// Records that a call of `plugin_id` was rejected for `reason`, unless the same kind of rejection was recorded recently
+    pub(crate) fn record<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        plugin_id: &str,
+        class: CommandClass,
+        reason: &'static str,
+    ) {
+        let now = Instant::now();
+        let key = (plugin_id.to_string(), class, reason);
+        let mut recorded = self.recorded.lock().unwrap();
+        if recorded
+            .get(&key)
+            .is_some_and(|x| now - *x < RECORD_INTERVAL)
+        {
+            return;
+        }
+        recorded.insert(key, now);
+        drop(recorded);
+        warn!("rejected {class:?} call of {plugin_id}: {reason}");
+        app_handle.state::<AuditLog>().record(
+            Some(plugin_id),
+            Some(plugin_id),
+            AuditAction::Throttled {
+                command: class,
+                reason: reason.to_string(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        burst: 2,
+        per_minute: 60,
+    };
+
+    #[test]
+    fn bucket_is_capped_at_burst() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        // an idle bucket does not save up more than `burst` tokens
+        let later = start + Duration::from_secs(3600);
+        for now in [start, later, later] {
+            assert_eq!(limiter.take("a", CommandClass::OpenUrl, LIMIT, now), Ok(()));
+        }
+        assert_eq!(
+            limiter.take("a", CommandClass::OpenUrl, LIMIT, later),
+            Err(Some(1000))
+        );
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        for _ in 0..LIMIT.burst {
+            assert_eq!(
+                limiter.take("a", CommandClass::OpenUrl, LIMIT, start),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            limiter.take("a", CommandClass::OpenUrl, LIMIT, start),
+            Err(Some(1000))
+        );
+        let half = start + Duration::from_millis(500);
+        assert_eq!(
+            limiter.take("a", CommandClass::OpenUrl, LIMIT, half),
+            Err(Some(500))
+        );
+        let full = start + Duration::from_secs(1);
+        assert_eq!(
+            limiter.take("a", CommandClass::OpenUrl, LIMIT, full),
+            Ok(())
+        );
+        assert!(limiter
+            .take("a", CommandClass::OpenUrl, LIMIT, full)
+            .is_err());
+    }
+
+    #[test]
+    fn bucket_without_refill_stays_empty() {
+        let limiter = RateLimiter::new();
+        let limit = RateLimit {
+            burst: 1,
+            per_minute: 0,
+        };
+        let start = Instant::now();
+        assert_eq!(
+            limiter.take("a", CommandClass::OpenUrl, limit, start),
+            Ok(())
+        );
+        assert_eq!(
+            limiter.take(
+                "a",
+                CommandClass::OpenUrl,
+                limit,
+                start + Duration::from_secs(3600)
+            ),
+            Err(None)
+        );
+    }
+
+    #[test]
+    fn buckets_are_per_plugin_and_class() {
+        let limiter = RateLimiter::new();
+        let now = Instant::now();
+        for _ in 0..LIMIT.burst {
+            assert_eq!(limiter.take("a", CommandClass::OpenUrl, LIMIT, now), Ok(()));
+        }
+        assert!(limiter
+            .take("a", CommandClass::OpenUrl, LIMIT, now)
+            .is_err());
+        assert_eq!(limiter.take("b", CommandClass::OpenUrl, LIMIT, now), Ok(()));
+        assert_eq!(
+            limiter.take("a", CommandClass::ReadSetting, LIMIT, now),
+            Ok(())
+        );
+    }
+}
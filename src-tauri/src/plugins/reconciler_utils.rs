@@ -1,11 +1,13 @@
 use std::fmt;
 
 use serde::Serialize;
-use serde_json::json;
-use tauri::{AppHandle, Emitter, Runtime};
-use tracing::{instrument, warn};
+use tauri::{AppHandle, Runtime};
+use tracing::instrument;
 
-use super::{PluginCurrentState, PluginState, PluginsState};
+use super::{
+    contracts::{Event, EventTarget, PluginRestart, PluginUpdate, PluginUpdated},
+    PluginCurrentState, PluginState, PluginsState,
+};
 use anyhow::anyhow;
 
 /// This enum is used as part of the reconciliation and tells us what the execution plan for a Plugin is.
@@ -35,6 +37,9 @@ pub(super) enum ReconcileAction {
     /// Stops and Restarts the Plugin, using a new import identified.
     /// Also adds in a patch to modify the previous state.  
     /// This is mainly used to modify the Manifest file. [ReconcileAction::Restart] is usually used when the plugin is already running, while [ReconcileAction::SyncInPlace] is used when it is not running.
+    ///
+    /// If the plugin has a UI loaded, it is moved into [PluginCurrentState::Restarting] and a `core/plugins/restart` event is emitted.
+    /// The Frontend unloads the plugin and acknowledges via `finalize_stop_plugin`, after which [super::PluginsState::finalize_stop] issues the Start with the new hash.
     Restart {
         plugin_id: String,
         #[serde(skip)]
@@ -91,13 +96,27 @@ impl fmt::Debug for ReconcileAction {
     }
 }
 
+/// An event for the core frontend that is emitted once the [PluginsState] was updated
 #[derive(Debug, Clone)]
-pub(crate) struct EventEmit(String, serde_json::Value);
+pub(crate) struct EventEmit(&'static str, serde_json::Value);
 
 impl EventEmit {
-    pub(crate) fn emit<R: Runtime>(&self, app: &AppHandle<R>) -> anyhow::Result<()> {
-        app.emit(&self.0, &self.1)?;
-        Ok(())
+    pub(crate) fn new<E: Event>(payload: &E::Payload) -> Self {
+        debug_assert!(E::TARGET == EventTarget::Root);
+        let payload = serde_json::to_value(payload).expect("event payloads serialize to JSON");
+        Self(E::NAME, payload)
+    }
+
+    /// Emits the event to the core frontend of all windows, armored with their root tokens
+    pub(crate) fn emit<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        state: &PluginsState,
+    ) -> anyhow::Result<()> {
+        state
+            .root_tokens
+            .emit(app, self.0, &self.1)
+            .map_err(|e| anyhow!("failed to encrypt {} event: {e}", self.0))
     }
 }
 
@@ -192,7 +211,7 @@ impl ReconcileAction {
                 }
                 .apply(plugins_states)?;
 
-                let state = match plugins_states.plugin_states.get(&plugin_id) {
+                let state = match plugins_states.plugin_states.get_mut(&plugin_id) {
                     None => {
                         return Err(anyhow!(
                             "Received reconcile to restart Plugin {}, but is missing in the plugins state",
@@ -203,23 +222,21 @@ impl ReconcileAction {
                 };
 
                 match state.current_state {
-                    PluginCurrentState::Disabling {} | PluginCurrentState::Disabled {} => {
-                        // We are already "on the way down" or already down.
-                        // Just start it up against
+                    PluginCurrentState::Disabling {}
+                    | PluginCurrentState::Disabled {}
+                    | PluginCurrentState::FailedToStart { .. } => {
+                        // We are already "on the way down" or already down. There is no UI to unload.
+                        // Just start it up again
                         ReconcileAction::Start {
                             plugin_id: plugin_id.clone(),
                         }
                         .apply(plugins_states)?;
                     }
                     PluginCurrentState::Starting { .. }
-                    | PluginCurrentState::FailedToStart { .. }
-                    | PluginCurrentState::Running { .. } => {
-                        // Tell the frontend to do a restart
-                        warn!("TODO - not implemented: restart");
-                        ReconcileAction::Start {
-                            plugin_id: plugin_id.clone(),
-                        }
-                        .apply(plugins_states)?;
+                    | PluginCurrentState::Running { .. }
+                    | PluginCurrentState::Restarting {} => {
+                        // Tell the frontend to unload the plugin. Once it acknowledges, the plugin is started with the new hash
+                        state.current_state = PluginCurrentState::Restarting {};
                     }
                 }
                 plugin_id
@@ -242,14 +259,22 @@ impl ReconcileAction {
             }
         };
 
+        // Any pending transition deadline for this plugin is now outdated
+        *plugins_states
+            .transition_generations
+            .entry(targeted_id.clone())
+            .or_default() += 1;
+
         Ok(plugins_states.get_cloned(&targeted_id).map(|x| {
-            EventEmit(
-                "core/plugins/update".into(),
-                json!({
-                    "id": targeted_id,
-                    "pluginState": x
-                }),
-            )
+            let restarting = matches!(x.current_state, PluginCurrentState::Restarting {});
+            let update = PluginUpdate::State {
+                id: targeted_id,
+                plugin_state: Box::new(x),
+            };
+            match restarting {
+                true => EventEmit::new::<PluginRestart>(&update),
+                false => EventEmit::new::<PluginUpdated>(&update),
+            }
         }))
     }
 }
@@ -0,0 +1,437 @@
+//! Native sidecar processes for plugins.
+//!
+//! A plugin may declare a sidecar executable in its manifest (see [PluginSidecar]). The sidecar runs exactly while the plugin is [super::PluginCurrentState::Running].
+//! [PluginsState::sync_backends](super::PluginsState::sync_backends) is called after each state change and hands the desired sidecars to the [SidecarSupervisor], which starts and stops processes accordingly.
+//! If a sidecar exits on its own, it is restarted with an exponential backoff.
+//!
+//! EDPF and the sidecar talk [JSON-RPC 2.0](https://www.jsonrpc.org/specification) over stdio, one message per line. stderr is forwarded to the log.
+//!
+//! EDPF sends these notifications to the sidecar:
+//! - `journalEvents`: the same batch of journal events the plugin's frontend receives, i.e. only the granted event classes
+//! - `message`: a message from the plugin's frontend. `params` is whatever the frontend sent
+//! - `shutdown`: the sidecar should exit. It is killed if it did not exit after [SHUTDOWN_TIMEOUT]
+//!
+//! At most [STDIN_QUEUE_CAPACITY] notifications are queued for a sidecar. If it does not read its stdin fast enough, further notifications are refused with [CommandError::SidecarBusy].
+//!
+//! The sidecar may send these notifications to EDPF:
+//! - `message`: forwarded to the plugin's frontend as an armored `core/plugins/sidecar_message` event
+//! - `log`: `{"level": "info", "message": "…"}`, written to the EDPF log
+//!
+//! Requests (i.e. messages with an `id`) for unknown methods are answered with a "method not found" error.
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, Runtime};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        RwLock,
+    },
+    time::Instant,
+};
+use tracing::{debug, error, info, warn};
+
+use super::{
+    contracts::{CommandError, SidecarMessage, SidecarMessageReceived},
+    PluginsState,
+};
+
+/// How many notifications may wait for a sidecar to read them from its stdin
+const STDIN_QUEUE_CAPACITY: usize = 256;
+/// How long a sidecar has to exit after it received `shutdown`
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Delay before the first restart of a sidecar that exited on its own. Doubles with each consecutive failure
+const RESTART_BACKOFF_BASE: Duration = Duration::from_secs(1);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// If a sidecar ran for this long before exiting, the backoff starts over
+const RESTART_BACKOFF_RESET: Duration = Duration::from_secs(60);
+
+/// The sidecar section of a plugin manifest
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+pub(crate) struct PluginSidecar {
+    /// Path to the executable, relative to the plugin folder. It may not leave the plugin folder.
+    /// On Windows, `.exe` is appended if the path has no extension
+    pub(crate) executable: String,
+    /// Arguments passed to the executable
+    #[serde(default)]
+    pub(crate) args: Vec<String>,
+}
+
+/// A sidecar as it should be running. If the spec of a running sidecar changes, it is restarted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SidecarSpec {
+    executable: PathBuf,
+    args: Vec<String>,
+    plugin_dir: PathBuf,
+}
+
+impl SidecarSpec {
+    /// Resolves the executable of `sidecar` inside `plugin_dir`
+    pub(crate) fn resolve(plugin_dir: &Path, sidecar: &PluginSidecar) -> anyhow::Result<Self> {
+        let executable = Self::executable_path(plugin_dir, sidecar)?;
+        if !executable.is_file() {
+            anyhow::bail!("sidecar executable {} does not exist", executable.display());
+        }
+        Ok(Self {
+            executable,
+            args: sidecar.args.clone(),
+            plugin_dir: plugin_dir.to_path_buf(),
+        })
+    }
+
+    /// Returns where the executable of `sidecar` is expected, without checking that it exists
+    pub(crate) fn executable_path(
+        plugin_dir: &Path,
+        sidecar: &PluginSidecar,
+    ) -> anyhow::Result<PathBuf> {
+        let relative = Path::new(&sidecar.executable);
+        if !relative
+            .components()
+            .all(|x| matches!(x, Component::Normal(_) | Component::CurDir))
+        {
+            anyhow::bail!(
+                "sidecar executable {} must be relative to the plugin folder",
+                sidecar.executable
+            );
+        }
+        let mut executable = plugin_dir.join(relative);
+        if cfg!(windows) && executable.extension().is_none() {
+            executable.set_extension("exe");
+        }
+        Ok(executable)
+    }
+}
+
+enum SidecarCommand {
+    /// A line to be written to the sidecar's stdin
+    Send(String),
+    Stop,
+}
+
+struct RunningSidecar {
+    spec: SidecarSpec,
+    tx: mpsc::Sender<SidecarCommand>,
+}
+
+/// Managed by Tauri. Owns the supervisor tasks of all sidecars
+pub(crate) struct SidecarSupervisor {
+    sidecars: Mutex<HashMap<String, RunningSidecar>>,
+}
+
+impl SidecarSupervisor {
+    pub(crate) fn new() -> Self {
+        Self {
+            sidecars: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Makes sure exactly the sidecars in `desired` are running. Sidecars whose spec changed are restarted
+    pub(crate) fn sync<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        desired: HashMap<String, SidecarSpec>,
+    ) {
+        let mut sidecars = self.sidecars.lock().unwrap();
+        sidecars.retain(|plugin_id, sidecar| {
+            let keep = desired.get(plugin_id) == Some(&sidecar.spec);
+            if !keep {
+                info!("stopping sidecar of {plugin_id}");
+                // If the queue is full, dropping the sender stops the sidecar once the queue is drained
+                _ = sidecar.tx.try_send(SidecarCommand::Stop);
+            }
+            keep
+        });
+        for (plugin_id, spec) in desired {
+            if sidecars.contains_key(&plugin_id) {
+                continue;
+            }
+            info!(
+                "starting sidecar of {plugin_id}: {}",
+                spec.executable.display()
+            );
+            let (tx, rx) = mpsc::channel(STDIN_QUEUE_CAPACITY);
+            tauri::async_runtime::spawn(supervise(
+                app_handle.clone(),
+                plugin_id.clone(),
+                spec.clone(),
+                rx,
+            ));
+            sidecars.insert(plugin_id, RunningSidecar { spec, tx });
+        }
+    }
+
+    /// Sends a JSON-RPC notification to the sidecar of `plugin_id`.
+    /// Fails with [CommandError::SidecarNotRunning] if the plugin has no sidecar and with [CommandError::SidecarBusy] if its queue is full
+    pub(crate) fn notify(
+        &self,
+        plugin_id: &str,
+        method: &str,
+        params: &impl Serialize,
+    ) -> Result<(), CommandError> {
+        let line = match notification(method, params) {
+            Some(x) => x,
+            None => return Err(CommandError::InternalFailedConversion),
+        };
+        match self.sidecars.lock().unwrap().get(plugin_id) {
+            Some(x) => {
+                x.tx.try_send(SidecarCommand::Send(line))
+                    .map_err(|e| match e {
+                        TrySendError::Full(_) => CommandError::SidecarBusy,
+                        TrySendError::Closed(_) => CommandError::SidecarNotRunning,
+                    })
+            }
+            None => Err(CommandError::SidecarNotRunning),
+        }
+    }
+}
+
+fn notification<T: Serialize>(method: &str, params: &T) -> Option<String> {
+    #[derive(Serialize)]
+    struct Notification<'a, T> {
+        jsonrpc: &'static str,
+        method: &'a str,
+        params: &'a T,
+    }
+
+    serde_json::to_string(&Notification {
+        jsonrpc: "2.0",
+        method,
+        params,
+    })
+    .map_err(|e| error!("failed to serialize sidecar notification {method}: {e}"))
+    .ok()
+}
+
+enum Exit {
+    /// The sidecar was asked to stop. The supervisor is done
+    Stopped,
+    /// The sidecar exited on its own or could not be started
+    Crashed,
+}
+
+/// Runs the sidecar until it is asked to stop, restarting it if it exits on its own
+async fn supervise<R: Runtime>(
+    app_handle: AppHandle<R>,
+    plugin_id: String,
+    spec: SidecarSpec,
+    mut rx: mpsc::Receiver<SidecarCommand>,
+) {
+    let mut failures = 0u32;
+    loop {
+        let started_at = Instant::now();
+        let exit = match Command::new(&spec.executable)
+            .args(&spec.args)
+            .current_dir(&spec.plugin_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => run(&app_handle, &plugin_id, child, &mut rx).await,
+            Err(e) => {
+                error!("failed to start sidecar of {plugin_id}: {e}");
+                Exit::Crashed
+            }
+        };
+        if let Exit::Stopped = exit {
+            return;
+        }
+
+        if started_at.elapsed() >= RESTART_BACKOFF_RESET {
+            failures = 0;
+        }
+        let backoff = RESTART_BACKOFF_BASE
+            .saturating_mul(2u32.saturating_pow(failures))
+            .min(RESTART_BACKOFF_MAX);
+        failures = failures.saturating_add(1);
+        warn!(
+            "sidecar of {plugin_id} exited. Restarting in {}s",
+            backoff.as_secs()
+        );
+
+        let restart_at = Instant::now() + backoff;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(restart_at) => break,
+                cmd = rx.recv() => match cmd {
+                    Some(SidecarCommand::Send(_)) => {
+                        debug!("dropping message to sidecar of {plugin_id} as it is not running")
+                    }
+                    Some(SidecarCommand::Stop) | None => return,
+                },
+            }
+        }
+    }
+}
+
+/// What woke up the main loop of [run]
+enum Wakeup {
+    Command(Option<SidecarCommand>),
+    Stdout(std::io::Result<Option<String>>),
+    Exited,
+}
+
+async fn run<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin_id: &str,
+    mut child: Child,
+    rx: &mut mpsc::Receiver<SidecarCommand>,
+) -> Exit {
+    let (Some(mut stdin), Some(stdout), Some(stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        error!("sidecar of {plugin_id} is missing a stdio pipe");
+        return Exit::Crashed;
+    };
+
+    let stderr_plugin_id = plugin_id.to_string();
+    tauri::async_runtime::spawn(async move {
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            warn!(plugin_id = %stderr_plugin_id, "sidecar: {line}");
+        }
+    });
+
+    let mut stdout = BufReader::new(stdout).lines();
+    loop {
+        let wakeup = tokio::select! {
+            cmd = rx.recv() => Wakeup::Command(cmd),
+            line = stdout.next_line() => Wakeup::Stdout(line),
+            _ = child.wait() => Wakeup::Exited,
+        };
+        match wakeup {
+            Wakeup::Command(Some(SidecarCommand::Send(line))) => {
+                if let Err(e) = write_line(&mut stdin, &line).await {
+                    warn!("failed to write to sidecar of {plugin_id}: {e}");
+                }
+            }
+            Wakeup::Command(Some(SidecarCommand::Stop) | None) => {
+                if let Some(line) = notification("shutdown", &Value::Null) {
+                    _ = write_line(&mut stdin, &line).await;
+                }
+                if tokio::time::timeout(SHUTDOWN_TIMEOUT, child.wait())
+                    .await
+                    .is_err()
+                {
+                    warn!("sidecar of {plugin_id} did not shut down in time. Killing it");
+                    _ = child.kill().await;
+                }
+                return Exit::Stopped;
+            }
+            Wakeup::Stdout(Ok(Some(line))) => {
+                handle_incoming(app_handle, plugin_id, &line, &mut stdin).await
+            }
+            Wakeup::Stdout(Ok(None) | Err(_)) | Wakeup::Exited => {
+                let status = child.wait().await;
+                warn!("sidecar of {plugin_id} exited: {status:?}");
+                return Exit::Crashed;
+            }
+        }
+    }
+}
+
+async fn write_line(stdin: &mut ChildStdin, line: &str) -> std::io::Result<()> {
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.write_all(b"\n").await?;
+    stdin.flush().await
+}
+
+#[derive(Deserialize)]
+struct IncomingMessage {
+    id: Option<Value>,
+    method: Option<String>,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct LogParams {
+    level: Option<String>,
+    message: String,
+}
+
+/// Handles a line the sidecar wrote to stdout
+async fn handle_incoming<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin_id: &str,
+    line: &str,
+    stdin: &mut ChildStdin,
+) {
+    let message: IncomingMessage = match serde_json::from_str(line) {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("sidecar of {plugin_id} sent invalid JSON-RPC: {e}");
+            return;
+        }
+    };
+    // Responses are ignored, as we only send notifications
+    let Some(method) = message.method else {
+        return;
+    };
+
+    let result = match method.as_str() {
+        "message" => {
+            forward_to_frontend(app_handle, plugin_id, message.params).await;
+            Ok(Value::Null)
+        }
+        "log" => match serde_json::from_value::<LogParams>(message.params) {
+            Ok(x) => {
+                match x.level.as_deref() {
+                    Some("error") => error!(plugin_id, "sidecar: {}", x.message),
+                    Some("warn") => warn!(plugin_id, "sidecar: {}", x.message),
+                    Some("debug") => debug!(plugin_id, "sidecar: {}", x.message),
+                    _ => info!(plugin_id, "sidecar: {}", x.message),
+                }
+                Ok(Value::Null)
+            }
+            Err(e) => Err((-32602, format!("invalid params: {e}"))),
+        },
+        _ => Err((-32601, format!("method not found: {method}"))),
+    };
+
+    // Notifications do not get a response
+    let Some(id) = message.id else {
+        return;
+    };
+    let response = match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => {
+            json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+        }
+    };
+    if let Err(e) = write_line(stdin, &response.to_string()).await {
+        warn!("failed to respond to sidecar of {plugin_id}: {e}");
+    }
+}
+
+/// Emits a message of a sidecar or [super::wasm_backend] to the plugin's frontend, armored with the plugin's key
+pub(super) async fn forward_to_frontend<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    plugin_id: &str,
+    payload: Value,
+) {
+    let state = app_handle.state::<Arc<RwLock<PluginsState>>>();
+    let message = SidecarMessage {
+        plugin_id: plugin_id.to_string(),
+        payload,
+    };
+    let emitted = state
+        .read()
+        .await
+        .emit_to_plugin::<SidecarMessageReceived, _>(app_handle, &["main"], plugin_id, &message);
+    if let Err(e) = emitted {
+        error!("failed to encrypt sidecar message of {plugin_id}: {e}");
+    }
+}
@@ -0,0 +1,573 @@
+//! Headless WASM backends for plugins.
+//!
+//! A plugin may ship a WASM module (see [PluginBackend]) that runs inside EDPF, independent of any window. Unlike a [super::sidecar], it is sandboxed:
+//! the module can only reach the outside world through the host API below. Each capability must be declared in the manifest and granted by the user (see [Permission::Backend]),
+//! the host functions check both.
+//! Like sidecars, the backend runs exactly while the plugin is [super::PluginCurrentState::Running]. Each backend gets its own thread.
+//!
+//! Every call into the module gets [FUEL_PER_CALL] fuel. A call that runs out of fuel or traps is aborted. After [MAX_CONSECUTIVE_TRAPS] failed calls in a row, the backend is unloaded.
+//!
+//! The module must export `memory` and `edpf_alloc(len: i32) -> i32`, which EDPF uses to pass inputs. All of the following exports are optional:
+//! - `edpf_init()`: called once after the module was loaded
+//! - `edpf_on_journal_events(ptr: i32, len: i32)`: a JSON array of journal events. Requires [BackendCapability::JournalEvents]
+//! - `edpf_on_message(ptr: i32, len: i32)`: a JSON message sent by the plugin's frontend. Requires [BackendCapability::FrontendMessages]
+//! - `edpf_on_tick()`: called every [TICK_INTERVAL]
+//!
+//! The host API is imported from the `edpf` module. Functions returning `i32` return a negative error code (see [HostError]) on failure.
+//! Like the commands of the frontend, they are rate limited (see [super::rate_limits]):
+//! - `log(level: i32, ptr: i32, len: i32)`: 0 = error, 1 = warn, 2 = info, 3 = debug
+//! - `send_message(ptr: i32, len: i32) -> i32`: sends JSON to the plugin's frontend. Requires [BackendCapability::FrontendMessages]
+//! - `read_setting(key_ptr: i32, key_len: i32, out_ptr: i32, out_cap: i32) -> i32`: writes the JSON value of one of the plugin's own settings to `out_ptr`
+//!   and returns its length. If the length exceeds `out_cap`, nothing is written and the call should be retried with a larger buffer. Requires [BackendCapability::Settings]
+//! - `write_setting(key_ptr: i32, key_len: i32, value_ptr: i32, value_len: i32) -> i32`: writes JSON to one of the plugin's own settings. Requires [BackendCapability::Settings]
+//!
+//! Setting keys are the same as for the Frontend, e.g. `my-plugin.someKey`. See [super::plugin_settings].
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+use wasmi::{
+    Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+use super::{
+    contracts::SettingValue,
+    permissions::{self, Permission},
+    plugin_settings::{self, parse_key, QuotaExceeded},
+    rate_limits::{CommandClass, RateLimiter},
+    sidecar::forward_to_frontend,
+    PluginsState,
+};
+
+/// Fuel each call into the module gets. Roughly one unit per executed instruction
+const FUEL_PER_CALL: u64 = 50_000_000;
+/// How much linear memory a module may use
+const MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+/// Inputs and host call arguments larger than this are rejected
+const MAX_PAYLOAD_BYTES: usize = 8 * 1024 * 1024;
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_CONSECUTIVE_TRAPS: u32 = 5;
+
+/// The backend section of a plugin manifest
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, JsonSchema)]
+pub(crate) struct PluginBackend {
+    /// Path to the WASM module, relative to the plugin folder. Defaults to `backend.wasm`
+    pub(crate) module: Option<String>,
+    /// What the module may access through the host API. Anything not listed is denied
+    #[serde(default)]
+    pub(crate) capabilities: Vec<BackendCapability>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BackendCapability {
+    /// Receive journal events via `edpf_on_journal_events`
+    JournalEvents,
+    /// Read and write the plugin's own settings
+    Settings,
+    /// Exchange messages with the plugin's frontend
+    FrontendMessages,
+}
+
+impl BackendCapability {
+    /// The permission the user has to grant for this capability
+    pub(crate) fn permission(self) -> Permission {
+        Permission::Backend { capability: self }
+    }
+}
+
+/// Error codes returned by host functions
+#[repr(i32)]
+enum HostError {
+    PermissionDenied = -1,
+    InvalidInput = -2,
+    Internal = -3,
+    /// See [super::rate_limits]
+    RateLimited = -4,
+    /// The settings store of the plugin would grow beyond its quota
+    QuotaExceeded = -5,
+}
+
+/// A backend as it should be running. If the spec of a running backend changes, it is reloaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WasmBackendSpec {
+    module: PathBuf,
+    capabilities: Vec<BackendCapability>,
+}
+
+impl WasmBackendSpec {
+    /// Resolves the module of `backend` inside `plugin_dir`
+    pub(crate) fn resolve(plugin_dir: &Path, backend: &PluginBackend) -> anyhow::Result<Self> {
+        let module = Self::module_path(plugin_dir, backend)?;
+        if !module.is_file() {
+            anyhow::bail!("backend module {} does not exist", module.display());
+        }
+        Ok(Self {
+            module,
+            capabilities: backend.capabilities.clone(),
+        })
+    }
+
+    /// Returns where the module of `backend` is expected, without checking that it exists
+    pub(crate) fn module_path(
+        plugin_dir: &Path,
+        backend: &PluginBackend,
+    ) -> anyhow::Result<PathBuf> {
+        let relative = Path::new(backend.module.as_deref().unwrap_or("backend.wasm"));
+        if !relative
+            .components()
+            .all(|x| matches!(x, Component::Normal(_) | Component::CurDir))
+        {
+            anyhow::bail!(
+                "backend module {} must be relative to the plugin folder",
+                relative.display()
+            );
+        }
+        Ok(plugin_dir.join(relative))
+    }
+}
+
+enum BackendEvent {
+    /// A JSON array of journal events
+    JournalEvents(Arc<str>),
+    /// A JSON message from the frontend
+    Message(String),
+    Stop,
+}
+
+struct RunningBackend {
+    spec: WasmBackendSpec,
+    tx: mpsc::Sender<BackendEvent>,
+}
+
+/// Managed by Tauri. Owns the threads of all WASM backends
+pub(crate) struct WasmBackends {
+    backends: Mutex<HashMap<String, RunningBackend>>,
+}
+
+impl WasmBackends {
+    pub(crate) fn new() -> Self {
+        Self {
+            backends: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Makes sure exactly the backends in `desired` are running. Backends whose spec changed are reloaded
+    pub(crate) fn sync<R: Runtime>(
+        &self,
+        app_handle: &AppHandle<R>,
+        desired: HashMap<String, WasmBackendSpec>,
+    ) {
+        let mut backends = self.backends.lock().unwrap();
+        backends.retain(|plugin_id, backend| {
+            let keep = desired.get(plugin_id) == Some(&backend.spec);
+            if !keep {
+                info!("stopping WASM backend of {plugin_id}");
+                _ = backend.tx.send(BackendEvent::Stop);
+            }
+            keep
+        });
+        for (plugin_id, spec) in desired {
+            if backends.contains_key(&plugin_id) {
+                continue;
+            }
+            info!(
+                "starting WASM backend of {plugin_id}: {}",
+                spec.module.display()
+            );
+            let (tx, rx) = mpsc::channel();
+            let app_handle = app_handle.clone();
+            let moved_plugin_id = plugin_id.clone();
+            let moved_spec = spec.clone();
+            if let Err(e) = std::thread::Builder::new()
+                .name(format!("wasm-backend-{plugin_id}"))
+                .spawn(move || run(app_handle, moved_plugin_id, moved_spec, rx))
+            {
+                error!("failed to spawn thread for WASM backend of {plugin_id}: {e}");
+                continue;
+            }
+            backends.insert(plugin_id, RunningBackend { spec, tx });
+        }
+    }
+
+    /// Passes a message from the frontend to the backend of `plugin_id`. Returns false if the plugin has no backend
+    pub(crate) fn send_message(&self, plugin_id: &str, message: &serde_json::Value) -> bool {
+        match self.backends.lock().unwrap().get(plugin_id) {
+            Some(x) => {
+                x.tx.send(BackendEvent::Message(message.to_string()))
+                    .is_ok()
+            }
+            None => false,
+        }
+    }
+
+    /// Passes journal events to the backend of `plugin_id`, if it has [BackendCapability::JournalEvents].
+    /// `events` must already be reduced to the event classes the plugin was granted
+    pub(crate) fn send_journal_events(&self, plugin_id: &str, events: &impl Serialize) -> bool {
+        let backends = self.backends.lock().unwrap();
+        let Some(backend) = backends.get(plugin_id).filter(|x| {
+            x.spec
+                .capabilities
+                .contains(&BackendCapability::JournalEvents)
+        }) else {
+            return false;
+        };
+        let events: Arc<str> = match serde_json::to_string(events) {
+            Ok(x) => x.into(),
+            Err(e) => {
+                error!(
+                    "failed to serialize journal events for the WASM backend of {plugin_id}: {e}"
+                );
+                return false;
+            }
+        };
+        backend.tx.send(BackendEvent::JournalEvents(events)).is_ok()
+    }
+}
+
+/// The body of a backend thread. Returns once the backend is stopped or unloaded
+fn run<R: Runtime>(
+    app_handle: AppHandle<R>,
+    plugin_id: String,
+    spec: WasmBackendSpec,
+    rx: mpsc::Receiver<BackendEvent>,
+) {
+    let mut backend = match LoadedBackend::load(app_handle, &plugin_id, &spec) {
+        Ok(x) => x,
+        Err(e) => {
+            error!("failed to load WASM backend of {plugin_id}: {e}");
+            return;
+        }
+    };
+
+    let mut consecutive_traps = 0;
+    let mut result = backend.call("edpf_init", None);
+    loop {
+        match result {
+            Ok(()) => consecutive_traps = 0,
+            Err(e) => {
+                consecutive_traps += 1;
+                error!("WASM backend of {plugin_id} failed: {e}");
+                if consecutive_traps >= MAX_CONSECUTIVE_TRAPS {
+                    error!("WASM backend of {plugin_id} failed {consecutive_traps} times in a row. Unloading it");
+                    return;
+                }
+            }
+        }
+
+        result = match rx.recv_timeout(TICK_INTERVAL) {
+            Ok(BackendEvent::JournalEvents(events)) => {
+                backend.call("edpf_on_journal_events", Some(events.as_bytes()))
+            }
+            Ok(BackendEvent::Message(message)) => {
+                backend.call("edpf_on_message", Some(message.as_bytes()))
+            }
+            Err(RecvTimeoutError::Timeout) => backend.call("edpf_on_tick", None),
+            Ok(BackendEvent::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+        };
+    }
+}
+
+struct HostState<R: Runtime> {
+    app_handle: AppHandle<R>,
+    plugin_id: String,
+    capabilities: Vec<BackendCapability>,
+    limits: StoreLimits,
+}
+
+impl<R: Runtime> HostState<R> {
+    /// Returns whether the backend declared `capability` and the user still grants it
+    fn can(&self, capability: BackendCapability) -> bool {
+        if !self.capabilities.contains(&capability) {
+            return false;
+        }
+        let state = self.app_handle.state::<Arc<RwLock<PluginsState>>>();
+        let Some(plugin) = state.blocking_read().get_cloned(&self.plugin_id) else {
+            return false;
+        };
+        permissions::check(&self.app_handle, &plugin, &capability.permission()).is_ok()
+    }
+
+    /// Takes a token from the plugin's bucket for `class`. Backends share the buckets with the plugin's frontend
+    fn within_rate_limit(&self, class: CommandClass) -> bool {
+        self.app_handle
+            .state::<RateLimiter>()
+            .check(&self.app_handle, Some(&self.plugin_id), class)
+            .is_ok()
+    }
+}
+
+struct LoadedBackend<R: Runtime> {
+    store: Store<HostState<R>>,
+    instance: Instance,
+}
+
+impl<R: Runtime> LoadedBackend<R> {
+    fn load(
+        app_handle: AppHandle<R>,
+        plugin_id: &str,
+        spec: &WasmBackendSpec,
+    ) -> anyhow::Result<Self> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &std::fs::read(&spec.module)?)?;
+
+        let mut store = Store::new(
+            &engine,
+            HostState {
+                app_handle,
+                plugin_id: plugin_id.to_string(),
+                capabilities: spec.capabilities.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_BYTES)
+                    .build(),
+            },
+        );
+        store.limiter(|x| &mut x.limits);
+        store.set_fuel(FUEL_PER_CALL)?;
+
+        let mut linker = Linker::<HostState<R>>::new(&engine);
+        link_host_api(&mut linker)?;
+        let instance = linker.instantiate(&mut store, &module)?.start(&mut store)?;
+        if instance.get_memory(&store, "memory").is_none() {
+            anyhow::bail!("module does not export its memory");
+        }
+        Ok(Self { store, instance })
+    }
+
+    /// Calls an optional export of the module. If `input` is given, it is copied into the module's memory first and passed as `(ptr, len)`
+    fn call(&mut self, name: &str, input: Option<&[u8]>) -> anyhow::Result<()> {
+        let Some(func) = self.instance.get_func(&self.store, name) else {
+            return Ok(());
+        };
+        self.store.set_fuel(FUEL_PER_CALL)?;
+        match input {
+            None => func
+                .typed::<(), ()>(&self.store)?
+                .call(&mut self.store, ())?,
+            Some(input) => {
+                if input.len() > MAX_PAYLOAD_BYTES {
+                    anyhow::bail!("input for {name} is too large ({} bytes)", input.len());
+                }
+                let len = input.len() as i32;
+                let ptr = self
+                    .instance
+                    .get_typed_func::<i32, i32>(&self.store, "edpf_alloc")?
+                    .call(&mut self.store, len)?;
+                self.instance
+                    .get_memory(&self.store, "memory")
+                    .ok_or_else(|| anyhow!("module does not export its memory"))?
+                    .write(&mut self.store, ptr as u32 as usize, input)
+                    .map_err(|e| anyhow!("failed to write input for {name}: {e}"))?;
+                func.typed::<(i32, i32), ()>(&self.store)?
+                    .call(&mut self.store, (ptr, len))?
+            }
+        };
+        Ok(())
+    }
+}
+
+/// Reads `len` bytes at `ptr` from the calling module's memory
+fn read_guest<R: Runtime>(
+    caller: &Caller<'_, HostState<R>>,
+    ptr: i32,
+    len: i32,
+) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|x| *x <= MAX_PAYLOAD_BYTES)?;
+    let mut buffer = vec![0; len];
+    memory.read(caller, ptr as u32 as usize, &mut buffer).ok()?;
+    Some(buffer)
+}
+
+fn read_guest_json<R: Runtime>(
+    caller: &Caller<'_, HostState<R>>,
+    ptr: i32,
+    len: i32,
+) -> Option<serde_json::Value> {
+    serde_json::from_slice(&read_guest(caller, ptr, len)?).ok()
+}
+
+/// Parses the key at `ptr` and makes sure it belongs to the calling plugin
+fn read_own_key<R: Runtime>(
+    caller: &Caller<'_, HostState<R>>,
+    ptr: i32,
+    len: i32,
+) -> Result<plugin_settings::ParsedKey, HostError> {
+    let key = read_guest(caller, ptr, len)
+        .and_then(|x| String::from_utf8(x).ok())
+        .ok_or(HostError::InvalidInput)?;
+    let key = parse_key(&key).map_err(|_| HostError::InvalidInput)?;
+    match key.is_writable_by(&caller.data().plugin_id) {
+        true => Ok(key),
+        false => Err(HostError::PermissionDenied),
+    }
+}
+
+fn link_host_api<R: Runtime>(linker: &mut Linker<HostState<R>>) -> anyhow::Result<()> {
+    linker.func_wrap(
+        "edpf",
+        "log",
+        |caller: Caller<'_, HostState<R>>, level: i32, ptr: i32, len: i32| {
+            let Some(message) = read_guest(&caller, ptr, len) else {
+                return;
+            };
+            let message = String::from_utf8_lossy(&message);
+            let plugin_id = caller.data().plugin_id.as_str();
+            match level {
+                0 => error!(plugin_id, "backend: {message}"),
+                1 => warn!(plugin_id, "backend: {message}"),
+                3 => debug!(plugin_id, "backend: {message}"),
+                _ => info!(plugin_id, "backend: {message}"),
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "edpf",
+        "send_message",
+        |caller: Caller<'_, HostState<R>>, ptr: i32, len: i32| -> i32 {
+            if !caller.data().can(BackendCapability::FrontendMessages) {
+                return HostError::PermissionDenied as i32;
+            }
+            if !caller
+                .data()
+                .within_rate_limit(CommandClass::BackendMessage)
+            {
+                return HostError::RateLimited as i32;
+            }
+            let Some(message) = read_guest_json(&caller, ptr, len) else {
+                return HostError::InvalidInput as i32;
+            };
+            let state = caller.data();
+            tauri::async_runtime::block_on(forward_to_frontend(
+                &state.app_handle,
+                &state.plugin_id,
+                message,
+            ));
+            0
+        },
+    )?;
+
+    linker.func_wrap(
+        "edpf",
+        "read_setting",
+        |mut caller: Caller<'_, HostState<R>>,
+         key_ptr: i32,
+         key_len: i32,
+         out_ptr: i32,
+         out_cap: i32|
+         -> i32 {
+            if !caller.data().can(BackendCapability::Settings) {
+                return HostError::PermissionDenied as i32;
+            }
+            if !caller.data().within_rate_limit(CommandClass::ReadSetting) {
+                return HostError::RateLimited as i32;
+            }
+            let key = match read_own_key(&caller, key_ptr, key_len) {
+                Ok(x) => x,
+                Err(e) => return e as i32,
+            };
+            let value = match plugin_settings::read_setting(&caller.data().app_handle, &key) {
+                Ok(x) => x.unwrap_or_default().to_string(),
+                Err(e) => {
+                    error!("failed to read setting for WASM backend: {e}");
+                    return HostError::Internal as i32;
+                }
+            };
+            if value.len() > out_cap.max(0) as usize {
+                return value.len() as i32;
+            }
+            let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+                return HostError::Internal as i32;
+            };
+            match memory.write(&mut caller, out_ptr as u32 as usize, value.as_bytes()) {
+                Ok(()) => value.len() as i32,
+                Err(_) => HostError::InvalidInput as i32,
+            }
+        },
+    )?;
+
+    linker.func_wrap(
+        "edpf",
+        "write_setting",
+        |caller: Caller<'_, HostState<R>>,
+         key_ptr: i32,
+         key_len: i32,
+         value_ptr: i32,
+         value_len: i32|
+         -> i32 {
+            if !caller.data().can(BackendCapability::Settings) {
+                return HostError::PermissionDenied as i32;
+            }
+            if !caller.data().within_rate_limit(CommandClass::WriteSetting) {
+                return HostError::RateLimited as i32;
+            }
+            let key = match read_own_key(&caller, key_ptr, key_len) {
+                Ok(x) => x,
+                Err(e) => return e as i32,
+            };
+            let Some(value) = read_guest_json(&caller, value_ptr, value_len) else {
+                return HostError::InvalidInput as i32;
+            };
+            let state = caller.data();
+            match plugin_settings::write_setting(&state.app_handle, &key, value) {
+                Ok(value) => {
+                    notify_settings_update(&state.app_handle, &key, value);
+                    0
+                }
+                Err(e) if e.is::<QuotaExceeded>() => {
+                    state.app_handle.state::<RateLimiter>().record(
+                        &state.app_handle,
+                        &state.plugin_id,
+                        CommandClass::WriteSetting,
+                        "QUOTA_EXCEEDED",
+                    );
+                    HostError::QuotaExceeded as i32
+                }
+                Err(e) => {
+                    error!("failed to write setting for WASM backend: {e}");
+                    HostError::Internal as i32
+                }
+            }
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Emits the same armored `settings_update` event as [super::commands::write_setting], so frontends see changes made by the backend
+fn notify_settings_update<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    key: &plugin_settings::ParsedKey,
+    value: serde_json::Value,
+) {
+    let state = app_handle.state::<Arc<RwLock<PluginsState>>>();
+    let resp = SettingValue {
+        key: key.to_string(),
+        value: Some(value),
+    };
+    let emitted = state
+        .blocking_read()
+        .emit_settings_update(app_handle, key, &resp);
+    if let Err(e) = emitted {
+        error!("failed to encrypt settings update: {e}");
+    }
+}
@@ -0,0 +1,104 @@
+//! Portable mode keeps all data in a folder next to the executable, so EDPF can be run from a USB stick or a self-contained folder.
+//!
+//! It is enabled if a file named [PORTABLE_MARKER] exists next to the executable, or if EDPF is started with [PORTABLE_FLAG].
+//! All data then lives in [PORTABLE_DATA_DIR] beside the executable:
+//! - `store.json` and the per-plugin `plugin-<id>.json` stores
+//! - the window state
+//! - the default user plugin directory (`plugins/`)
+//! - the files plugins store (`plugin-files/`)
+//! - logs and the audit log (`logs/`)
+//! - the WebView cache (`webview/`)
+//!
+//! Everything that persists data should resolve its path through this module.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{error, info};
+
+/// If a file with this name is placed next to the executable, portable mode is enabled
+pub const PORTABLE_MARKER: &str = "edpf-portable";
+/// Enables portable mode, regardless of the marker file
+pub const PORTABLE_FLAG: &str = "--portable";
+/// Name of the folder next to the executable that holds all data in portable mode
+pub const PORTABLE_DATA_DIR: &str = "edpf-data";
+
+/// Returns the folder holding all data if portable mode is enabled. This is evaluated once.
+pub fn data_dir() -> Option<&'static Path> {
+    static MEM: OnceLock<Option<PathBuf>> = OnceLock::new();
+    MEM.get_or_init(|| {
+        let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+        let enabled =
+            exe_dir.join(PORTABLE_MARKER).exists() || std::env::args().any(|x| x == PORTABLE_FLAG);
+        enabled.then(|| exe_dir.join(PORTABLE_DATA_DIR))
+    })
+    .as_deref()
+}
+
+/// Returns the path of a store file, e.g. `store.json`.
+///
+/// In portable mode, this is an absolute path into the portable data dir. Otherwise, the name is returned as-is and the store ends up in Tauri's app data dir.
+pub(crate) fn store_path(name: &str) -> PathBuf {
+    match data_dir() {
+        Some(x) => x.join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+/// Returns the file name for the window state. Absolute in portable mode, which makes the window state plugin ignore its default directory
+pub(crate) fn window_state_filename() -> String {
+    store_path(".window-state.json")
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The user plugin directory that is used if none was configured
+pub(crate) fn default_plugin_dir() -> PathBuf {
+    match data_dir() {
+        Some(x) => x.join("plugins"),
+        None => dirs::data_local_dir().unwrap().join("edpf-plugins"),
+    }
+}
+
+/// The file logs are written to in portable mode. Outside of portable mode, we only log to stdout
+pub fn log_file() -> Option<PathBuf> {
+    data_dir().map(|x| x.join("logs").join("edpf.log"))
+}
+
+/// The directory of the audit log (see [crate::plugins::audit_log]). Outside of portable mode, this is Tauri's app log dir
+pub(crate) fn audit_log_dir<R: Runtime>(app: &AppHandle<R>) -> Option<PathBuf> {
+    match data_dir() {
+        Some(x) => Some(x.join("logs")),
+        None => app.path().app_log_dir().ok(),
+    }
+}
+
+/// The directory holding the storage folders of all plugins (see [crate::plugins::plugin_files]). Outside of portable mode, this is in Tauri's app data dir
+pub(crate) fn plugin_files_dir<R: Runtime>(app: &AppHandle<R>) -> Option<PathBuf> {
+    match data_dir() {
+        Some(x) => Some(x.join("plugin-files")),
+        None => app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|x| x.join("plugin-files")),
+    }
+}
+
+/// Creates the portable data dir and points the WebView cache into it. Does nothing outside of portable mode.
+///
+/// Must be called before any window is created.
+pub(crate) fn prepare() {
+    let Some(x) = data_dir() else {
+        return;
+    };
+    if let Err(e) = std::fs::create_dir_all(x) {
+        error!("failed to create portable data dir {}: {e}", x.display());
+    }
+    info!("Running in portable mode. Data is kept in {}", x.display());
+    // WebView2 reads this when the first webview is created
+    std::env::set_var("WEBVIEW2_USER_DATA_FOLDER", x.join("webview"));
+}
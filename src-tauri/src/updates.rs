@@ -1,10 +1,11 @@
 use std::str::FromStr;
 
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tauri::Url;
 use tauri_plugin_updater::Update;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub(crate) enum ReleaseChannel {
     #[serde(rename = "prerelease")]
     Prerelease,
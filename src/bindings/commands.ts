@@ -0,0 +1,1356 @@
+// Generated by `npm run bindings` from `src-tauri/src/plugins/contracts.rs`. Do not edit
+
+export type AckJournalFramesRequest = {
+  channelId: number;
+  pluginId: string;
+  /**
+   * How many frames of the stream the facade handled so far
+   */
+  received: number;
+};
+
+export type AnswerUrlPromptRequest = {
+  allow: boolean;
+  promptId: string;
+  /**
+   * If set, the decision applies to all further URLs of the plugin on that host
+   */
+  remember: boolean;
+};
+
+/**
+ * A privileged action
+ */
+export type AuditAction = {
+  type: "openUrl";
+  url: string;
+} | {
+  allowed: boolean;
+  remembered: boolean;
+  type: "answerUrlPrompt";
+  url: string;
+} | {
+  key: string;
+  type: "writeSetting";
+} | {
+  path: string;
+  type: "writeFile";
+} | {
+  path: string;
+  type: "deleteFile";
+} | {
+  type: "start";
+} | {
+  type: "retry";
+} | {
+  type: "stop";
+} | {
+  pluginDir: string;
+  type: "linkDevPlugin";
+} | {
+  type: "unlinkDevPlugin";
+} | {
+  granted: Array<Permission>;
+  type: "grantPermissions";
+} | {
+  type: "updateEdpf";
+  version: string;
+} | {
+  command: CommandClass;
+  reason: string;
+  type: "throttled";
+};
+
+export type AuditEntry = {
+  action: AuditAction;
+  /**
+   * The plugin that performed the action, `None` if the core frontend (i.e. the user) did
+   */
+  actor?: string | null;
+  at: string;
+  /**
+   * The plugin the action concerns, `None` for actions concerning EDPF itself
+   */
+  pluginId?: string | null;
+};
+
+export type BackendCapability = "journal_events" | "settings" | "frontend_messages";
+
+/**
+ * A single operation of `run_batch`: the name of a read-only command and the request it takes
+ */
+export type BatchOperation = {
+  command: "fetch_all_plugins";
+  request: Empty;
+} | {
+  command: "fetch_broken_plugins";
+  request: Empty;
+} | {
+  command: "get_plugin";
+  request: PluginIdRequest;
+} | {
+  command: "get_import_path_for_plugin";
+  request: PluginIdRequest;
+} | {
+  command: "read_setting";
+  request: ReadSettingRequest;
+} | {
+  command: "fetch_retained_bus_messages";
+  request: PluginIdRequest;
+} | {
+  command: "fetch_plugin_permissions";
+  request: PluginIdRequest;
+} | {
+  command: "fetch_dev_plugins";
+  request: Empty;
+} | {
+  command: "fetch_plugin_roots";
+  request: Empty;
+};
+
+/**
+ * The result of a single operation of `run_batch`. `ok` holds what the command would have answered with on its own
+ */
+export type BatchOutcome = {
+  ok: unknown;
+} | {
+  error: CommandError;
+};
+
+export type BatchRequest = {
+  operations: Array<BatchOperation>;
+};
+
+/**
+ * A folder in the plugin directory that looks like a plugin, but could not be loaded.
+ */
+export type BrokenPlugin = {
+  /**
+   * The plugin ID derived from the folder name
+   */
+  id: string;
+  plugin_dir: string;
+  problem: BrokenPluginReason;
+};
+
+export type BrokenPluginReason = {
+  message: string;
+  type: "UnreadableManifest";
+} | {
+  column: number;
+  line: number;
+  message: string;
+  type: "InvalidManifest";
+} | {
+  message: string;
+  type: "InvalidPluginId";
+} | ({
+  conflicting_dir?: string | null;
+  type: "DuplicateId";
+}) | {
+  shadowed_by: string;
+  type: "Shadowed";
+};
+
+export type BusMessage = {
+  payload: unknown;
+  publishedAt: string;
+  publisher: string;
+  /**
+   * Full topic name, i.e. `$publisher/$name`
+   */
+  topic: string;
+};
+
+export type CheckUpdateRequest = {
+  channel: ReleaseChannel;
+};
+
+/**
+ * Commands are limited by class. Each class has its own bucket
+ */
+export type CommandClass = ("openUrl" | "writeSetting" | "readSetting" | "busPublish" | "rereadJournal" | "writeFile" | "readFile") | "backendMessage";
+
+/**
+ * Why a command failed. Serialized as the `reason` of the error response, next to `"success": false` and the variant's fields
+ */
+export type CommandError = {
+  reason: "INCORRECT_AES_KEY";
+} | {
+  reason: "FAILED_PARSE_PAYLOAD_STRUCTURE";
+} | {
+  reason: "INTERNAL_AES_ERROR";
+} | {
+  reason: "STALE_PAYLOAD";
+} | {
+  reason: "REPLAYED_PAYLOAD";
+} | {
+  reason: "CALLED_FROM_INVALID_WINDOW";
+} | {
+  reason: "TOKEN_ALREADY_REQUESTED";
+} | {
+  reason: "INTERNAL_FAILED_CONVERSION";
+} | {
+  reason: "INTERNAL_FETCH_STORE_ERROR";
+} | {
+  reason: "INTERNAL_MISSING_GENERAL_SETTINGS";
+} | {
+  reason: "PLUGIN_STATE_NOT_FOUND";
+} | {
+  reason: "PLUGIN_NOT_FOUND";
+} | {
+  reason: "PLUGIN_NOT_USERPROVIDED";
+} | {
+  reason: "INTERNAL_OPEN_PLUGIN_DIR_ERROR";
+} | {
+  reason: "MISSING_OR_BAD_TOKEN";
+} | {
+  permission: Permission;
+  reason: "PERMISSION_DENIED";
+} | {
+  meta: RateLimitedMeta;
+  reason: "RATE_LIMITED";
+} | {
+  reason: "QUOTA_EXCEEDED";
+} | {
+  reason: "INVALID_URL";
+} | {
+  reason: "URL_SCHEME_NOT_ALLOWED";
+} | {
+  reason: "URL_DENIED";
+} | {
+  reason: "URL_PROMPT_PENDING";
+} | {
+  reason: "URL_PROMPT_TIMEOUT";
+} | {
+  reason: "PROMPT_NOT_FOUND";
+} | {
+  message: string;
+  reason: "OPEN_URL_FAILED";
+} | {
+  message: string;
+  reason: "WINDOW_FAILED";
+} | {
+  message: string;
+  reason: "TRANSITION_FAILED";
+} | {
+  reason: "KEY_INVALID_FORMAT";
+} | {
+  reason: "KEY_NOT_ENOUGH_SEGMENTS";
+} | {
+  reason: "KEY_EMPTY_SEGMENT";
+} | {
+  reason: "SECRET_NOT_WRITABLE_MISSING_PERMISSION";
+} | {
+  reason: "SECRET_NOT_READABLE_MISSING_PERMISSION";
+} | {
+  reason: "FAILED_WRITE";
+} | {
+  reason: "FAILED_READ";
+} | {
+  reason: "FILE_PATH_INVALID";
+} | {
+  reason: "FILE_NOT_FOUND";
+} | {
+  message: string;
+  reason: "UPDATE_CHECK_FAILED";
+} | {
+  reason: "NO_PENDING_UPDATE";
+} | {
+  meta: string;
+  reason: "UPDATE_FAILED";
+} | {
+  reason: "PLUGIN_NOT_VALIDLY_SIGNED";
+} | {
+  reason: "NO_PINNED_KEY";
+} | {
+  reason: "INVALID_PLUGIN_ID";
+} | {
+  reason: "DEV_PLUGIN_DIR_NOT_FOUND";
+} | {
+  reason: "DEV_PLUGIN_NOT_LINKED";
+} | {
+  reason: "INVALID_PLUGIN_DIR";
+} | {
+  reason: "PLUGIN_DIR_NOT_A_DIRECTORY";
+} | {
+  reason: "PLUGIN_DIR_NESTED";
+} | {
+  reason: "PLUGIN_DIR_NOT_CREATABLE";
+} | {
+  reason: "MOVE_PLUGINS_FAILED";
+} | {
+  reason: "SIDECAR_NOT_RUNNING";
+} | {
+  reason: "SIDECAR_BUSY";
+} | {
+  reason: "TOPIC_INVALID";
+} | {
+  reason: "TOPIC_NOT_DECLARED";
+} | {
+  reason: "STREAM_NOT_FOUND";
+} | {
+  reason: "STREAM_CLOSED";
+} | {
+  reason: "STREAM_STALLED";
+} | {
+  reason: "BATCH_TOO_LARGE";
+};
+
+/**
+ * Limits on what plugins may do, see [super::rate_limits]. Stored in `store.json` under `command_limits`.
+ * A plugin may have its own limits, see [GenericPluginSettings::command_limits]
+ */
+export type CommandLimits = {
+  /**
+   * How large all files in the storage folder of a plugin may grow together, in bytes
+   */
+  files_quota_bytes?: number;
+  /**
+   * Command classes missing here use [CommandClass::default_limit]
+   */
+  rate_limits?: { [key: string]: RateLimit };
+  /**
+   * How large the settings store of a plugin may grow, in bytes
+   */
+  settings_quota_bytes?: number;
+};
+
+/**
+ * Maps the ID of a dev plugin to the folder containing its `manifest.json`
+ */
+export type DevPluginLinks = { [key: string]: string };
+
+/**
+ * The request of commands that take no arguments
+ */
+export type Empty = Record<string, never>;
+
+export type FetchAuditLogRequest = {
+  limit?: number | null;
+  /**
+   * If set, only entries the plugin performed or is concerned by are returned
+   */
+  pluginId?: string | null;
+};
+
+export type ForgetUrlDecisionRequest = {
+  host: string;
+  pluginId: string;
+};
+
+export type GrantPluginPermissionsRequest = {
+  permissions: Array<Permission>;
+  pluginId: string;
+};
+
+export type ImportPath = {
+  /**
+   * The hash of the plugin's frontend. Changes whenever the frontend changes
+   */
+  hash: string;
+  import: string;
+};
+
+/**
+ * What a frame decrypts to
+ */
+export type JournalFrame = {
+  /**
+   * How many events were dropped before this frame, because the facade fell behind
+   */
+  dropped: number;
+  events: Array<LogEventWithContext>;
+};
+
+export type LinkDevPluginRequest = {
+  pluginDir: string;
+  pluginId: string;
+};
+
+/**
+ * An "enhanced" Log Entry containing where that log entry is from (which file), and who it belongs to
+ */
+export type LogEventWithContext = {
+  cmdr: string;
+  event: string;
+  file: string;
+};
+
+export type OpenPluginsDirRequest = {
+  pluginId?: string | null;
+};
+
+export type OpenUrlRequest = {
+  pluginId: string;
+  url: string;
+};
+
+/**
+ * A single permission. Used both for what a plugin requested and what it was granted
+ */
+export type Permission = {
+  domain: string;
+  type: "urlDomain";
+} | {
+  class: string;
+  type: "journalEvents";
+} | {
+  pluginId: string;
+  type: "settingsRead";
+} | {
+  type: "sidecar";
+} | {
+  type: "fileStorage";
+} | {
+  capability: BackendCapability;
+  type: "backend";
+} | {
+  topic: string;
+  type: "busPublish";
+} | {
+  topic: string;
+  type: "busSubscribe";
+};
+
+export type PermissionRequest = {
+  /**
+   * e.g. the permissions of an upgraded `v1alpha` manifest were filled in by EDPF
+   */
+  manifestWarnings: Array<string>;
+  /**
+   * The name from the manifest
+   */
+  name: string;
+  pluginId: string;
+  requested: Array<Permission>;
+};
+
+/**
+ * A publisher key that is pinned to a plugin ID.
+ */
+export type PinnedKey = {
+  /**
+   * If false, this key was pinned automatically because it was the first key seen for this plugin (trust-on-first-use)
+   */
+  explicit: boolean;
+  pinned_at: string;
+  /**
+   * The minisign public key, base64 encoded
+   */
+  public_key: string;
+};
+
+/**
+ * The backend section of a plugin manifest
+ */
+export type PluginBackend = {
+  /**
+   * What the module may access through the host API. Anything not listed is denied
+   */
+  capabilities?: Array<BackendCapability>;
+  /**
+   * Path to the WASM module, relative to the plugin folder. Defaults to `backend.wasm`
+   */
+  module?: string | null;
+};
+
+export type PluginCurrentState = {
+  type: "Disabled";
+} | {
+  metadata: Array<string>;
+  type: "Starting";
+} | {
+  reasons: Array<string>;
+  type: "FailedToStart";
+} | {
+  type: "Running";
+} | {
+  type: "Disabling";
+} | {
+  type: "Restarting";
+};
+
+/**
+ * A file in the storage folder of a plugin
+ */
+export type PluginFile = {
+  /**
+   * Relative to the storage folder, with `/` as separator
+   */
+  path: string;
+  size: number;
+};
+
+export type PluginFileRequest = {
+  /**
+   * Relative to the storage folder, with `/` as separator
+   */
+  path: string;
+  pluginId: string;
+};
+
+export type PluginIdRequest = {
+  pluginId: string;
+};
+
+/**
+ * Each Plugin must have a `manifest.json` which describes the plugin, it's requirements, updating strategies, and so on.
+ * 
+ * When a manifest is loaded, it is upgraded to the latest version using [PluginManifest::upgrade]. The rest of EDPF therefore only ever sees [PluginManifest::V1].
+ */
+export type PluginManifest = PluginManifestV1Alpha | PluginManifestV1;
+
+/**
+ * Version 1 is the stable manifest version. Changes to it must be backwards-compatible.
+ * 
+ * Point `$schema` to `http://localhost:$port/schemas/manifest/v1.json` to get validation and autocompletion in your editor.
+ * The port is only stable if EDPF could bind its preferred port. A copy of the schema is committed to `src-tauri/schemas/manifest/v1.json`, which can be referenced as a file instead.
+ */
+export type PluginManifestV1 = {
+  /**
+   * optionally, a WASM module EDPF runs headless for the plugin. See [super::wasm_backend] for details
+   */
+  backend?: PluginBackend | null;
+  /**
+   * A short description about what this Plugin is doing
+   */
+  description?: string | null;
+  /**
+   * optionally, which message bus topics this plugin publishes and subscribes to. See [super::message_bus] for details
+   */
+  message_bus?: PluginMessageBus | null;
+  /**
+   * What is this plugin's name?
+   * This name shouldn't change over time as the internal ID and plugin-stored settings are tied to it
+   */
+  name: string;
+  /**
+   * optionally, the permissions this plugin needs. The user is asked to grant them. See [super::permissions] for details
+   */
+  permissions?: PluginPermissions | null;
+  /**
+   * optionally, a minisign public key (base64). If set, the plugin folder must contain a signature made with this key.
+   * See [super::package_signature] for details
+   */
+  publisher_key?: string | null;
+  /**
+   * This contains the strategy the plugin should take during updating to find out if there is a new update
+   */
+  remote_manifest?: PluginRemoteManifestResolutionStrategy | null;
+  /**
+   * optionally, a URL to the Git Repository
+   */
+  repository_url?: string | null;
+  /**
+   * optionally, a native executable EDPF runs alongside the plugin. See [super::sidecar] for details
+   */
+  sidecar?: PluginSidecar | null;
+  /**
+   * optionally, a link where the user can get support. Can be a Discord Link, Github Issues, etc.
+   */
+  support_url?: string | null;
+  /**
+   * Put a semantic version here (e.g. `0.0.1`)
+   */
+  version?: string | null;
+  /**
+   * A list of versions. This is ignored from the local file and only the remote manifest is considered. Look at [PluginManifestV1::remote_manifest]
+   */
+  versions?: Array<PluginVersionOption> | null;
+};
+
+/**
+ * Version 1alpha is the initial version that may introduce breaking changes.
+ * 
+ * **Deprecated**: This version is automatically upgraded to [PluginManifestV1] when loaded. Plugin authors should switch to `v1`.
+ */
+export type PluginManifestV1Alpha = {
+  /**
+   * A short description about what this Plugin is doing
+   */
+  description?: string | null;
+  /**
+   * What is this plugin's name?
+   * This name shouldn't change over time as the internal ID and plugin-stored settings are tied to it
+   * The internal name is derived from this name by replacing spaces with dashes and removing any unsafe characters
+   */
+  name: string;
+  /**
+   * optionally, a minisign public key (base64). If set, the plugin folder must contain a signature made with this key.
+   * See [super::package_signature] for details
+   */
+  publisher_key?: string | null;
+  /**
+   * This contains the strategy the plugin should take during updating to find out if there is a new update
+   */
+  remote_manifest?: PluginRemoteManifestResolutionStrategy | null;
+  /**
+   * optionally, a URL to the Git Repository
+   */
+  repository_url?: string | null;
+  /**
+   * optionally, a link where the user can get support. Can be a Discord Link, Github Issues, etc.
+   */
+  support_url?: string | null;
+  /**
+   * Put a semantic version here (e.g. `0.0.1`)
+   */
+  version?: string | null;
+  /**
+   * A list of versions. This is ignored from the local file and only the remote manifest is considered. Look at [PluginManifest::remote_manifest]
+   */
+  versions?: Array<PluginVersionOption> | null;
+};
+
+/**
+ * The message bus section of a plugin manifest
+ */
+export type PluginMessageBus = {
+  /**
+   * Topic names this plugin publishes on, without the plugin ID. Only lowercase letters, digits, dashes, underscores and dots are allowed
+   */
+  publishes?: Array<string>;
+  /**
+   * Full topic names this plugin subscribes to, e.g. `other-plugin/status` or `other-plugin/*`
+   */
+  subscribes?: Array<string>;
+};
+
+/**
+ * The permissions section of a plugin manifest
+ */
+export type PluginPermissions = {
+  /**
+   * Allows the plugin to store files in its own storage folder, see `writeFile` of the plugin context
+   */
+  file_storage?: boolean;
+  /**
+   * Journal event classes (the `event` field, e.g. `FSDJump`) the plugin receives. `*` for all events
+   */
+  journal_events?: Array<string>;
+  /**
+   * IDs of other plugins whose public settings this plugin may read. `*` for all plugins
+   */
+  settings_read?: Array<string>;
+  /**
+   * Domains the plugin may open URLs on without asking the user. `*.example.com` matches `example.com` and all of its subdomains
+   */
+  url_domains?: Array<string>;
+};
+
+export type PluginPermissionsResponse = {
+  granted: Array<Permission>;
+  requested: Array<Permission>;
+  /**
+   * Remembered answers to `open_url` prompts, by host
+   */
+  urlDecisions: { [key: string]: UrlDecision };
+};
+
+export type PluginRemoteManifestResolutionStrategy = "GitReleaseAsset" | {
+  Http: {
+    address: string;
+  };
+} | "OfficialRegistry" | {
+  UnofficialRegistry: {
+    address: string;
+  };
+};
+
+/**
+ * A folder plugins were loaded from. For dev plugins, this is the linked plugin folder itself.
+ */
+export type PluginRoot = {
+  kind: PluginRootKind;
+  path: string;
+};
+
+export type PluginRootKind = "Dev" | "User" | "Shared" | "System";
+
+/**
+ * The sidecar section of a plugin manifest
+ */
+export type PluginSidecar = {
+  /**
+   * Arguments passed to the executable
+   */
+  args?: Array<string>;
+  /**
+   * Path to the executable, relative to the plugin folder. It may not leave the plugin folder.
+   * On Windows, `.exe` is appended if the path has no extension
+   */
+  executable: string;
+};
+
+/**
+ * Defines the current state of the plugin. Mainly used for reconciliation and for the Frontend to display all plugins / specific plugin
+ */
+export type PluginState = {
+  current_state: PluginCurrentState;
+  frontend_hash: string;
+  id: string;
+  manifest: PluginManifest;
+  /**
+   * Deprecation warnings from loading the manifest, meant for the plugin author
+   */
+  manifest_warnings: Array<string>;
+  plugin_dir: string;
+  /**
+   * The root this plugin was loaded from. [None] for embedded plugins
+   */
+  root?: PluginRoot | null;
+  source: PluginStateSource;
+  /**
+   * The result of checking the package signature against the Trust Store. See [package_signature]
+   */
+  verification: PluginVerification;
+};
+
+export type PluginStateSource = "UserProvided" | "Dev" | "Embedded";
+
+export type PluginUiConfigNode = {
+  children: Array<PluginUiConfigNode>;
+  /**
+   * The Identifier uniquely identifies a "container" node (basically a node containing either plugins or other containers)
+   * When containers are moved around, that node can then be identified when diffing.  
+   * When new containers are spawned, they must immediately get an ID (this is done on the frontend)
+   * 
+   * This doesn't have to be a UUID, but a UUIDv4 was choosen as it is sufficiently random
+   */
+  identifier: string;
+  meta: PluginUiConfigNodeMetadata;
+  type: "VerticalLayout";
+} | {
+  meta: PluginUiConfigNodeMetadata;
+  plugin_id: string;
+  type: "PluginCell";
+};
+
+export type PluginUiConfigNodeMetadata = {
+  max_height?: string | null;
+  max_width?: string | null;
+  min_height?: string | null;
+  min_width?: string | null;
+};
+
+/**
+ * The payload of `core/plugins/update` and `core/plugins/restart`
+ */
+export type PluginUpdate = {
+  id: string;
+  pluginState: PluginState;
+} | ({
+  broken?: BrokenPlugin | null;
+  id: string;
+});
+
+/**
+ * Describes how far we can trust the contents of a plugin folder.
+ * 
+ * The variants are ordered from least to most trustworthy. This ordering is used when comparing against [TrustStore::minimum_level]
+ */
+export type PluginVerification = "Tampered" | "Unsigned" | "SignedUnknown" | "SignedTrusted" | "Embedded";
+
+export type PluginVersionOption = {
+  /**
+   * Contains the full path to a tar / tgz / zip which contains the entire plugin folder.
+   */
+  download_url: string;
+  /**
+   * users may opt into beta releases to test new features
+   */
+  is_pre_release: boolean;
+  /**
+   * A semantic version (e.g. 1.2.3)
+   */
+  version: string;
+};
+
+export type PluginsUiConfig = {
+  root: PluginUiConfigNode;
+};
+
+export type PublishBusMessageRequest = {
+  payload: unknown;
+  pluginId: string;
+  retain?: boolean;
+  /**
+   * The topic name without the plugin ID
+   */
+  topic: string;
+};
+
+export type Quarantine = {
+  failures: Array<string>;
+  id: string;
+};
+
+/**
+ * A token bucket
+ */
+export type RateLimit = {
+  /**
+   * How many calls can be made at once
+   */
+  burst: number;
+  /**
+   * How many calls per minute can be made in the long run
+   */
+  per_minute: number;
+};
+
+export type RateLimitedMeta = {
+  /**
+   * When the next call would be accepted. `None` if the limit has no refill
+   */
+  retryAfterMs?: number | null;
+};
+
+export type ReadSettingRequest = {
+  key: string;
+  pluginId: string;
+};
+
+export type ReleaseChannel = "prerelease" | "stable" | "merged";
+
+export type SendSidecarMessageRequest = {
+  payload: unknown;
+  pluginId: string;
+};
+
+export type SetPluginDirRequest = {
+  /**
+   * Moves the plugins of the current directory over. This is best-effort
+   */
+  movePlugins?: boolean;
+  pluginDir: string;
+};
+
+export type SetPluginDirResponse = {
+  /**
+   * Plugins that could not be moved
+   */
+  notMoved: Array<string>;
+  pluginDir: string;
+};
+
+export type SetPluginRootsRequest = {
+  /**
+   * Ordered from highest to lowest precedence
+   */
+  shared: Array<string>;
+  /**
+   * Ordered from highest to lowest precedence
+   */
+  system: Array<string>;
+};
+
+export type SetTrustPolicyRequest = {
+  minimumLevel?: PluginVerification | null;
+  trustOnFirstUse?: boolean | null;
+};
+
+/**
+ * A setting and its stored value. `None` if the setting is not set
+ */
+export type SettingValue = {
+  key: string;
+  value?: unknown;
+};
+
+export type SidecarMessage = {
+  payload: unknown;
+  pluginId: string;
+};
+
+export type StartPluginFailedRequest = {
+  pluginId: string;
+  reasons: Array<string>;
+};
+
+export type SyncCommandLimitsRequest = {
+  /**
+   * Drops the plugin's own limits, so the global ones apply
+   */
+  inherit?: boolean;
+  limits?: CommandLimits | null;
+  /**
+   * If set, the limits of that plugin are synced instead of the global ones
+   */
+  pluginId?: string | null;
+};
+
+export type SyncMainLayoutRequest = {
+  layout?: PluginsUiConfig | null;
+};
+
+export type SyncTransitionTimeoutsRequest = {
+  timeouts?: TransitionTimeouts | null;
+};
+
+export type TransitionExpiry = {
+  /**
+   * The state the plugin did not leave in time
+   */
+  expiredState: PluginCurrentState;
+  id: string;
+};
+
+/**
+ * Deadlines for the transitional plugin states, in seconds. Stored in `store.json` under `transition_timeouts`.
+ * 
+ * Once a deadline passes without the Frontend acknowledging the transition, the Backend resolves it on its own.
+ */
+export type TransitionTimeouts = {
+  /**
+   * How long the Frontend has to unload a plugin before it is forced to be disabled
+   */
+  disabling_secs?: number;
+  /**
+   * How long the Frontend has to unload a plugin during a restart before the restart is considered failed
+   */
+  restarting_secs?: number;
+  /**
+   * How long the Frontend has to load a plugin before it is considered failed
+   */
+  starting_secs?: number;
+};
+
+/**
+ * The persisted Trust Store. It lives in `store.json` under the `trust_store` key.
+ */
+export type TrustStore = {
+  /**
+   * Plugins with a verification level below this are not started.
+   */
+  minimum_level?: PluginVerification;
+  /**
+   * Maps a plugin ID to the publisher key that is allowed to sign it
+   */
+  pinned_keys?: { [key: string]: PinnedKey };
+  /**
+   * If set, the first valid publisher key seen for a plugin is pinned automatically.
+   */
+  trust_on_first_use?: boolean;
+};
+
+export type UpdateInfo = {
+  current_version: string;
+  new_version: string;
+};
+
+/**
+ * Sent on the channel of `commit_update_edpf`
+ */
+export type UpdateProgress = ({
+  content_len?: number | null;
+  type: "Started";
+}) | {
+  chunk_len: number;
+  type: "Progress";
+} | {
+  type: "Finished";
+};
+
+/**
+ * A decision the user asked to remember for a host
+ */
+export type UrlDecision = "allow" | "deny";
+
+/**
+ * What the frontend shows for a prompt. Sent as `core/open_url/prompt`
+ */
+export type UrlPrompt = {
+  /**
+   * False if the host is not among the domains declared in the manifest
+   */
+  declared: boolean;
+  host: string;
+  pluginId: string;
+  promptId: string;
+  url: string;
+};
+
+export type WritePluginFileRequest = {
+  contents: string;
+  /**
+   * Relative to the storage folder, with `/` as separator
+   */
+  path: string;
+  pluginId: string;
+};
+
+export type WriteSettingRequest = {
+  key: string;
+  pluginId: string;
+  value: unknown;
+};
+
+export interface Commands {
+  /**
+   * Hands out the root token of the calling window, once per page load. See [super::get_root_token_once]
+   */
+  "get_root_token_once": {
+    access: "unarmored";
+    request: Empty;
+    response: string;
+  };
+  /**
+   * All plugins that were loaded, by ID
+   */
+  "fetch_all_plugins": {
+    access: "root";
+    request: Empty;
+    response: { [key: string]: PluginState };
+  };
+  /**
+   * All plugins that were found during the last reconcile, but could not be loaded
+   */
+  "fetch_broken_plugins": {
+    access: "root";
+    request: Empty;
+    response: Array<BrokenPlugin>;
+  };
+  "get_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: PluginState;
+  };
+  "get_import_path_for_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: ImportPath;
+  };
+  /**
+   * Opens the folder of a plugin, or the user plugin folder if no plugin is given
+   */
+  "open_plugins_dir": {
+    access: "root";
+    request: OpenPluginsDirRequest;
+    response: null;
+  };
+  "open_settings": {
+    access: "root";
+    request: Empty;
+    response: null;
+  };
+  /**
+   * Opens a URL in the user's browser. URLs outside the plugin's granted domains need the user's confirmation
+   */
+  "open_url": {
+    access: "caller";
+    request: OpenUrlRequest;
+    response: null;
+  };
+  "answer_url_prompt": {
+    access: "root";
+    request: AnswerUrlPromptRequest;
+    response: null;
+  };
+  "forget_url_decision": {
+    access: "root";
+    request: ForgetUrlDecisionRequest;
+    response: null;
+  };
+  /**
+   * Looks for an update of EDPF. `null` if there is none
+   */
+  "check_update_edpf": {
+    access: "root";
+    request: CheckUpdateRequest;
+    response: UpdateInfo | null;
+  };
+  /**
+   * Installs the update found by the last `check_update_edpf`, reporting the progress on the channel
+   */
+  "commit_update_edpf": {
+    access: "root";
+    request: Empty;
+    response: null;
+    channel: UpdateProgress;
+  };
+  "start_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  /**
+   * Clears the failure history of a plugin, lifts its quarantine and starts it right away
+   */
+  "retry_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  "finalize_start_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  "start_plugin_failed": {
+    access: "root";
+    request: StartPluginFailedRequest;
+    response: null;
+  };
+  "stop_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  "finalize_stop_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  /**
+   * Writes a setting and returns the value as stored
+   */
+  "write_setting": {
+    access: "caller";
+    request: WriteSettingRequest;
+    response: SettingValue;
+  };
+  "read_setting": {
+    access: "caller";
+    request: ReadSettingRequest;
+    response: SettingValue;
+  };
+  /**
+   * The files in the plugin's storage folder. Needs [Permission::FileStorage]
+   */
+  "list_plugin_files": {
+    access: "caller";
+    request: PluginIdRequest;
+    response: Array<PluginFile>;
+  };
+  /**
+   * Reads a file from the plugin's storage folder as UTF-8 text. Needs [Permission::FileStorage]
+   */
+  "read_plugin_file": {
+    access: "caller";
+    request: PluginFileRequest;
+    response: string;
+  };
+  /**
+   * Creates or replaces a file in the plugin's storage folder. Needs [Permission::FileStorage]
+   */
+  "write_plugin_file": {
+    access: "caller";
+    request: WritePluginFileRequest;
+    response: null;
+  };
+  "delete_plugin_file": {
+    access: "caller";
+    request: PluginFileRequest;
+    response: null;
+  };
+  /**
+   * Opens the live journal stream of a plugin. Frames are sent on the channel until the page is unloaded
+   */
+  "open_journal_stream": {
+    access: "caller";
+    request: PluginIdRequest;
+    response: null;
+    channel: JournalFrame;
+  };
+  "ack_journal_frames": {
+    access: "caller";
+    request: AckJournalFramesRequest;
+    response: null;
+  };
+  /**
+   * Sends all events of the active journals as frames on the channel. Returns once all frames were acknowledged
+   */
+  "reread_active_journal": {
+    access: "caller";
+    request: PluginIdRequest;
+    response: null;
+    channel: JournalFrame;
+  };
+  /**
+   * The latest entries of the audit log, newest first
+   */
+  "fetch_audit_log": {
+    access: "root";
+    request: FetchAuditLogRequest;
+    response: Array<AuditEntry>;
+  };
+  /**
+   * Replaces the layout of the main window if one is given, then returns the layout in effect
+   */
+  "sync_main_layout": {
+    access: "root";
+    request: SyncMainLayoutRequest;
+    response: PluginsUiConfig;
+  };
+  "trust_plugin_publisher": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  "untrust_plugin_publisher": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  "set_trust_policy": {
+    access: "root";
+    request: SetTrustPolicyRequest;
+    response: TrustStore;
+  };
+  "sync_transition_timeouts": {
+    access: "root";
+    request: SyncTransitionTimeoutsRequest;
+    response: TransitionTimeouts;
+  };
+  "sync_command_limits": {
+    access: "root";
+    request: SyncCommandLimitsRequest;
+    response: CommandLimits;
+  };
+  "fetch_dev_plugins": {
+    access: "root";
+    request: Empty;
+    response: DevPluginLinks;
+  };
+  "link_dev_plugin": {
+    access: "root";
+    request: LinkDevPluginRequest;
+    response: null;
+  };
+  "unlink_dev_plugin": {
+    access: "root";
+    request: PluginIdRequest;
+    response: null;
+  };
+  "set_plugin_dir": {
+    access: "root";
+    request: SetPluginDirRequest;
+    response: SetPluginDirResponse;
+  };
+  "fetch_plugin_roots": {
+    access: "root";
+    request: Empty;
+    response: Array<PluginRoot>;
+  };
+  "set_plugin_roots": {
+    access: "root";
+    request: SetPluginRootsRequest;
+    response: null;
+  };
+  "send_sidecar_message": {
+    access: "caller";
+    request: SendSidecarMessageRequest;
+    response: null;
+  };
+  "publish_bus_message": {
+    access: "caller";
+    request: PublishBusMessageRequest;
+    response: null;
+  };
+  "fetch_retained_bus_messages": {
+    access: "caller";
+    request: PluginIdRequest;
+    response: Array<BusMessage>;
+  };
+  "fetch_plugin_permissions": {
+    access: "root";
+    request: PluginIdRequest;
+    response: PluginPermissionsResponse;
+  };
+  /**
+   * Replaces the permissions granted to a plugin. Permissions it doesn't request are dropped
+   */
+  "grant_plugin_permissions": {
+    access: "root";
+    request: GrantPluginPermissionsRequest;
+    response: null;
+  };
+  /**
+   * Plugins that request permissions the user was not asked about yet. See `core/plugins/permissions_requested`
+   */
+  "fetch_permission_requests": {
+    access: "root";
+    request: Empty;
+    response: Array<PermissionRequest>;
+  };
+  /**
+   * Runs several read-only commands in one round trip, all against the same state. Answers with one outcome per operation, in order
+   */
+  "run_batch": {
+    access: "caller";
+    request: BatchRequest;
+    response: Array<BatchOutcome>;
+  };
+}
+
+export interface Events {
+  /**
+   * A plugin was added or changed its state, or a broken plugin was found or fixed
+   */
+  "core/plugins/update": {
+    target: "root";
+    payload: PluginUpdate;
+  };
+  /**
+   * The frontend has to unload the plugin, so it can be started again with a new frontend hash
+   */
+  "core/plugins/restart": {
+    target: "root";
+    payload: PluginUpdate;
+  };
+  /**
+   * A plugin did not leave a transitional state in time, see [TransitionTimeouts]
+   */
+  "core/plugins/transition_expired": {
+    target: "root";
+    payload: TransitionExpiry;
+  };
+  /**
+   * A plugin failed too often in a row and is no longer started on its own
+   */
+  "core/plugins/quarantined": {
+    target: "root";
+    payload: Quarantine;
+  };
+  /**
+   * A new plugin was discovered that requests permissions. It runs with none of them until the user answers with `grant_plugin_permissions`
+   */
+  "core/plugins/permissions_requested": {
+    target: "root";
+    payload: PermissionRequest;
+  };
+  "settings_update": {
+    target: "root";
+    payload: SettingValue;
+  };
+  /**
+   * A setting the plugin may read changed
+   */
+  "core/plugins/settings_update": {
+    target: "plugin";
+    payload: SettingValue;
+  };
+  /**
+   * A message of the plugin's sidecar or WASM backend
+   */
+  "core/plugins/sidecar_message": {
+    target: "plugin";
+    payload: SidecarMessage;
+  };
+  "core/plugins/bus_message": {
+    target: "plugin";
+    payload: BusMessage;
+  };
+  /**
+   * Asks the user whether a plugin may open a URL. Only sent to the window the plugin runs in
+   */
+  "core/open_url/prompt": {
+    target: "root";
+    payload: UrlPrompt;
+  };
+}
+
+/** What a failed command answers with */
+export type CommandFailure = { success: false } & CommandError;
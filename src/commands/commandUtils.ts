@@ -8,18 +8,29 @@ export function base64ToBytesNoPadding(b64: string) {
   return Uint8Array.fromBase64(b64) as Uint8Array;
 }
 
+/**
+ * Increases with every payload this window encrypts, no matter the key. The backend rejects reused counters.
+ */
+let payloadCounter = 0;
+
 /**
  * This
- * 1. takes a JSON payload and stringifies it
- * 2. encodes the resulting UTF8 string to a u8 array.
- * 3. encrypts using a random vi and the provided CryptoKey using AES-GCM
- * 4. takes the resulting u8 array and converts it back to b64 (no pad)
- * 5. returns the iv and encrypted, b64-encoded payload
+ * 1. wraps the JSON payload in an envelope with the current time and the next counter, so the backend can reject replayed payloads
+ * 2. stringifies the envelope
+ * 3. encodes the resulting UTF8 string to a u8 array.
+ * 4. encrypts using a random vi and the provided CryptoKey using AES-GCM
+ * 5. takes the resulting u8 array and converts it back to b64 (no pad)
+ * 6. returns the iv and encrypted, b64-encoded payload
  */
 export async function encryptPayload(root_token: CryptoKey, payload: object) {
   const te = new TextEncoder();
   const iv = crypto.getRandomValues(new Uint8Array(12));
-  const encoded = te.encode(JSON.stringify(payload));
+  const envelope = {
+    issuedAt: Date.now(),
+    counter: ++payloadCounter,
+    body: payload,
+  };
+  const encoded = te.encode(JSON.stringify(envelope));
 
   const ciphertext = new Uint8Array(
     await crypto.subtle.encrypt(
@@ -64,3 +75,38 @@ export async function decryptPayload(
 
   return JSON.parse(decoded);
 }
+
+/** Set in the first byte of a journal frame if its body is compressed. Must match `FLAG_DEFLATE` in `journal_stream.rs` */
+const FRAME_FLAG_DEFLATE = 1;
+
+/**
+ * Decodes a binary journal frame (see `journal_stream.rs` for the layout):
+ * 1. splits off the flags and the iv
+ * 2. decrypts the rest using the provided CryptoKey
+ * 3. inflates the result if the frame is compressed
+ * 4. parses the resulting JSON
+ */
+export async function decryptFrame(key: CryptoKey, frame: ArrayBuffer) {
+  const bytes = new Uint8Array(frame);
+  const flags = bytes[0];
+  const iv = bytes.slice(1, 13);
+  const cipher = bytes.slice(13);
+
+  let cleartext = await crypto.subtle.decrypt(
+    {
+      name: "AES-GCM",
+      iv,
+    },
+    key,
+    cipher,
+  );
+  if (flags & FRAME_FLAG_DEFLATE) {
+    const inflated = new Blob([cleartext])
+      .stream()
+      .pipeThrough(new DecompressionStream("deflate-raw"));
+    cleartext = await new Response(inflated).arrayBuffer();
+  }
+
+  const td = new TextDecoder();
+  return JSON.parse(td.decode(cleartext));
+}
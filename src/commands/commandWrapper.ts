@@ -1,11 +1,19 @@
-import { invoke } from "@tauri-apps/api/core";
-import { decryptPayload, encryptPayload } from "./commandUtils";
-import { PluginStateZod } from "../types/PluginState";
+import { Channel, invoke } from "@tauri-apps/api/core";
+import { decryptFrame, decryptPayload, encryptPayload } from "./commandUtils";
+import { PluginRootZod, PluginStateZod } from "../types/PluginState";
+import { BrokenPluginZod } from "../types/BrokenPlugin";
 import z from "zod";
 import { PluginViewStructureZod } from "../main/layouts/types";
 import { getRootToken } from "./getRootToken";
-
-const ErrorZod = z.object({
+import { Permission, PermissionZod } from "../types/PluginManifest";
+import type {
+  CommandFailure,
+  Commands,
+  ReleaseChannel,
+} from "../bindings/commands";
+
+/** Failures carry further fields depending on `reason`, these are kept. See `CommandFailure` */
+const ErrorZod = z.looseObject({
   success: z.literal(false),
   reason: z.string(),
 });
@@ -20,6 +28,115 @@ export const EncryptedCommandResponse = z.discriminatedUnion("success", [
   ErrorZod,
   EncryptedHappyResponse,
 ]);
+const BusMessageZod = z.object({
+  topic: z.string(),
+  publisher: z.string(),
+  payload: z.unknown(),
+  publishedAt: z.string(),
+});
+export type BusMessage = z.infer<typeof BusMessageZod>;
+const AuditEntryZod = z.object({
+  at: z.string(),
+  actor: z.string().nullable(),
+  pluginId: z.string().nullable(),
+  action: z.discriminatedUnion("type", [
+    z.object({ type: z.literal("openUrl"), url: z.string() }),
+    z.object({
+      type: z.literal("answerUrlPrompt"),
+      url: z.string(),
+      allowed: z.boolean(),
+      remembered: z.boolean(),
+    }),
+    z.object({ type: z.literal("writeSetting"), key: z.string() }),
+    z.object({ type: z.literal("writeFile"), path: z.string() }),
+    z.object({ type: z.literal("deleteFile"), path: z.string() }),
+    z.object({ type: z.literal("start") }),
+    z.object({ type: z.literal("retry") }),
+    z.object({ type: z.literal("stop") }),
+    z.object({ type: z.literal("linkDevPlugin"), pluginDir: z.string() }),
+    z.object({ type: z.literal("unlinkDevPlugin") }),
+    z.object({
+      type: z.literal("grantPermissions"),
+      granted: z.array(PermissionZod),
+    }),
+    z.object({ type: z.literal("updateEdpf"), version: z.string() }),
+    z.object({
+      type: z.literal("throttled"),
+      command: z.string(),
+      reason: z.string(),
+    }),
+  ]),
+});
+export type AuditEntry = z.infer<typeof AuditEntryZod>;
+/** What a `core/open_url/prompt` event decrypts to. See `UrlPrompt` in `url_policy.rs` */
+const UrlPromptZod = z.object({
+  promptId: z.string(),
+  pluginId: z.string(),
+  url: z.string(),
+  host: z.string(),
+  declared: z.boolean(),
+});
+export type UrlPrompt = z.infer<typeof UrlPromptZod>;
+/** What `fetch_permission_requests` returns and a `core/plugins/permissions_requested` event decrypts to */
+const PermissionRequestZod = z.object({
+  pluginId: z.string(),
+  name: z.string(),
+  requested: z.array(PermissionZod),
+  manifestWarnings: z.array(z.string()),
+});
+export type PermissionRequest = z.infer<typeof PermissionRequestZod>;
+const UrlDecisionZod = z.enum(["allow", "deny"]);
+const JournalEventZod = z.object({
+  cmdr: z.string(),
+  file: z.string(),
+  event: z.string(),
+});
+export type JournalEvent = z.infer<typeof JournalEventZod>;
+/** What a journal frame decrypts to. See `FrameBody` in `journal_stream.rs` */
+const JournalFrameZod = z.object({
+  events: z.array(JournalEventZod),
+  dropped: z.number(),
+});
+
+export const ImportPathZod = z.object({ hash: z.string(), import: z.string() });
+/** What `read_setting`, `write_setting` and the settings update events decrypt to */
+const SettingValueZod = z.object({
+  key: z.string(),
+  value: z.any().optional().nullable(),
+});
+/** The progress `commit_update_edpf` reports on its channel */
+const UpdateProgressZod = z.discriminatedUnion("type", [
+  z.object({ type: z.literal("Started"), content_len: z.number().nullable() }),
+  z.object({ type: z.literal("Progress"), chunk_len: z.number() }),
+  z.object({ type: z.literal("Finished") }),
+]);
+export type UpdateProgress = z.infer<typeof UpdateProgressZod>;
+
+/** The commands `runBatch` accepts */
+type BatchCommand =
+  Commands["run_batch"]["request"]["operations"][number]["command"];
+/** A single operation of `runBatch`. The response of the command is checked against `zod` */
+export type BatchOperation = {
+  [K in BatchCommand]: {
+    command: K;
+    request: Commands[K]["request"];
+    zod: z.ZodType;
+  };
+}[BatchCommand];
+/** What a single operation of `runBatch` resolves to, like the command would on its own */
+type BatchResult<T> =
+  | { success: true; data: T }
+  | CommandFailure
+  | {
+      success: false;
+      reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID";
+      meta: unknown;
+    };
+const BatchOutcomeZod = z.union([
+  z.object({ error: z.looseObject({ reason: z.string() }) }),
+  z.object({ ok: z.unknown() }),
+]);
+
 const EncryptedCommandEmptyResponse = z.discriminatedUnion("success", [
   ErrorZod,
   EncryptedHappyResponse.omit({ iv: true, payload: true }),
@@ -27,11 +144,16 @@ const EncryptedCommandEmptyResponse = z.discriminatedUnion("success", [
 
 /**
  * This util handled encryption and decryption for commands. It is highly priviledged and mustn't be exposed to plugins!
+ * What each command takes and returns is generated from `contracts.rs` into `src/bindings`, run `npm run bindings` after changing it
  */
 export class CommandWrapper {
+  /** Encrypts commands and decrypts their responses. Either the root token or a plugin's key */
   #key: CryptoKey;
-  constructor(key: CryptoKey) {
+  /** Only set for the root token. Used to derive the plugin keys */
+  #derivationKey: CryptoKey | undefined;
+  constructor(key: CryptoKey, derivationKey?: CryptoKey) {
     this.#key = key;
+    this.#derivationKey = derivationKey;
   }
 
   /**
@@ -39,7 +161,32 @@ export class CommandWrapper {
    * the IPC Impl.
    */
   public static async createNew() {
-    return new CommandWrapper(await getRootToken());
+    const { key, derivationKey } = await getRootToken();
+    return new CommandWrapper(key, derivationKey);
+  }
+
+  /**
+   * Returns a wrapper for the facade of a plugin. Its commands are encrypted with the plugin's own key, which is derived from the root token via HKDF.
+   * The backend resolves the calling plugin from that key, so the facade can only act on behalf of this plugin.
+   * Must match `plugin_key` in `commands_armor.rs`
+   */
+  public async forPlugin(pluginId: string) {
+    if (!this.#derivationKey) {
+      throw new Error("only the root command wrapper can derive plugin keys");
+    }
+    const key = await crypto.subtle.deriveKey(
+      {
+        name: "HKDF",
+        hash: "SHA-256",
+        salt: new Uint8Array(),
+        info: new TextEncoder().encode(`edpf/plugin/${pluginId}`),
+      },
+      this.#derivationKey,
+      { name: "AES-GCM", length: 128 },
+      false,
+      ["encrypt", "decrypt"],
+    );
+    return new CommandWrapper(key);
   }
 
   /** Used to arbitrarily encrypt payloads. This is mainly used by the main / settings window for private commands */
@@ -52,567 +199,562 @@ export class CommandWrapper {
   }
 
   public async fetchAllPlugins() {
-    const { iv: reqIv, payload: reqPayload } = await this.encryptPayload({});
-
-    const response = await invoke("fetch_all_plugins", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
-    const parsedEncrypted = EncryptedCommandResponse.safeParse(response);
-    console.log({ response, parsedEncrypted });
-
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    if (!parsedEncrypted.data.success) {
-      return parsedEncrypted.data;
-    }
-
-    // at this point we are successful. Time to decrypt
-    let payload: object;
-    try {
-      payload = await this.decryptPayload(
-        parsedEncrypted.data.iv,
-        parsedEncrypted.data.payload
-      );
-    } catch (e) {
-      return {
-        success: false as const,
-        reason: "DECRYPT_FAILED",
-        meta: e,
-      };
-    }
+    return await this.#invoke(
+      "fetch_all_plugins",
+      {},
+      z.record(z.string(), PluginStateZod),
+    );
+  }
 
-    const verifiedPayload = z
-      .record(z.string(), PluginStateZod)
-      .safeParse(payload);
-    if (verifiedPayload.error) {
-      return {
-        success: false as const,
-        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(verifiedPayload.error),
-      };
-    }
-    return {
-      success: true as const,
-      data: verifiedPayload.data,
-    };
+  public async fetchBrokenPlugins() {
+    return await this.#invoke(
+      "fetch_broken_plugins",
+      {},
+      z.array(BrokenPluginZod),
+    );
   }
 
   public async getImportPathForPlugin(pluginId: string) {
-    const { iv: reqIv, payload: reqPayload } = await this.encryptPayload({
-      pluginId,
-    });
-    const response = await invoke("get_import_path_for_plugin", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
-
-    const parsedEncrypted = EncryptedCommandResponse.safeParse(response);
-
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    if (!parsedEncrypted.data.success) {
-      return parsedEncrypted.data;
-    }
-
-    // at this point we are successful. Time to decrypt
-    let payload: object;
-    try {
-      payload = await this.decryptPayload(
-        parsedEncrypted.data.iv,
-        parsedEncrypted.data.payload
-      );
-    } catch (e) {
-      return {
-        success: false as const,
-        reason: "DECRYPT_FAILED",
-        meta: e,
-      };
-    }
-
-    const verifiedPayload = z
-      .object({ hash: z.string(), import: z.string() })
-      .safeParse(payload);
-    if (verifiedPayload.error) {
-      return {
-        success: false as const,
-        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(verifiedPayload.error),
-      };
-    }
-    return {
-      success: true as const,
-      data: verifiedPayload.data,
-    };
+    return await this.#invoke(
+      "get_import_path_for_plugin",
+      { pluginId },
+      ImportPathZod,
+    );
   }
 
   public async openPluginsDir(pluginId?: string | undefined) {
     if (!pluginId) {
       pluginId = undefined;
     }
-    const { iv: reqIv, payload: reqPayload } = await this.encryptPayload({
-      pluginId,
-    });
-    const response = await invoke("open_plugins_dir", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
-
-    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
-
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    return parsedEncrypted.data;
+    return await this.#invokeEmpty("open_plugins_dir", { pluginId });
   }
 
   public async openSettings() {
-    const { iv, payload } = await this.encryptPayload({});
-    console.log({ iv, payload });
-    const response = await invoke("open_settings", { iv: iv, payload });
+    return await this.#invokeEmpty("open_settings", {});
+  }
 
-    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
+  /**
+   * Looks for an update of EDPF on the given release channel. Resolves to `null` if there is none
+   */
+  public async checkUpdateEdpf(channel: ReleaseChannel) {
+    return await this.#invoke(
+      "check_update_edpf",
+      { channel },
+      z
+        .object({ new_version: z.string(), current_version: z.string() })
+        .nullable(),
+    );
+  }
 
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    return parsedEncrypted.data;
+  /**
+   * Installs the update found by the last `checkUpdateEdpf`. `onProgress` is invoked as the update downloads
+   */
+  public async commitUpdateEdpf(onProgress: (progress: UpdateProgress) => void) {
+    const onEvent = new Channel<unknown>();
+    onEvent.onmessage = (message) => {
+      const progress = UpdateProgressZod.safeParse(message);
+      if (!progress.success) {
+        console.error("invalid update progress", message);
+        return;
+      }
+      onProgress(progress.data);
+    };
+    return await this.#invokeEmpty("commit_update_edpf", {}, { onEvent });
   }
 
   public async startPlugin(pluginId: string) {
-    const { iv: reqIv, payload: reqPayload } = await this.encryptPayload({
-      pluginId,
-    });
-    const response = await invoke("start_plugin", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
-
-    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
-
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    return parsedEncrypted.data;
+    return await this.#invokeEmpty("start_plugin", { pluginId });
   }
 
   public async finalizeStartPlugin(pluginId: string) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
-      pluginId,
-    });
-    const response = await invoke("finalize_start_plugin", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
-
-    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
-
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    return parsedEncrypted.data;
+    return await this.#invokeEmpty("finalize_start_plugin", { pluginId });
   }
 
   public async startPluginFailed(pluginId: string, reasons: string[]) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
+    return await this.#invokeEmpty("start_plugin_failed", {
       pluginId,
       reasons,
     });
-    const response = await invoke("start_plugin_failed", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
+  }
 
-    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
+  public async stopPlugin(pluginId: string) {
+    return await this.#invokeEmpty("stop_plugin", { pluginId });
+  }
 
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    return parsedEncrypted.data;
+  public async finalizeStopPlugin(pluginId: string) {
+    return await this.#invokeEmpty("finalize_stop_plugin", { pluginId });
   }
 
-  public async stopPlugin(pluginId: string) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
-      pluginId,
-    });
-    const response = await invoke("stop_plugin", {
-      iv: reqIv,
-      payload: reqPayload,
+  public async openUrl(pluginId: string, url: string) {
+    return await this.#invokeEmpty("open_url", { url, pluginId });
+  }
+
+  /**
+   * Answers a prompt of `open_url`. With `remember`, the plugin is not asked about the host again
+   */
+  public async answerUrlPrompt(
+    promptId: string,
+    allow: boolean,
+    remember: boolean,
+  ) {
+    return await this.#invokeEmpty("answer_url_prompt", {
+      promptId,
+      allow,
+      remember,
     });
+  }
 
-    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
+  /**
+   * Forgets a remembered answer to a prompt of `open_url`, so the user is asked again for that host
+   */
+  public async forgetUrlDecision(pluginId: string, host: string) {
+    return await this.#invokeEmpty("forget_url_decision", { pluginId, host });
+  }
 
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    return parsedEncrypted.data;
+  public async getPlugin(pluginId: string) {
+    return await this.#invoke("get_plugin", { pluginId }, PluginStateZod);
   }
 
-  public async finalizeStopPlugin(pluginId: string) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
-      pluginId,
-    });
-    const response = await invoke("finalize_stop_plugin", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
+  public async writeSetting(pluginId: string, key: string, value: any) {
+    return await this.#invoke(
+      "write_setting",
+      { pluginId, key, value },
+      SettingValueZod,
+    );
+  }
 
-    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
+  public async readSetting(pluginId: string, key: string) {
+    return await this.#invoke("read_setting", { pluginId, key }, SettingValueZod);
+  }
 
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    return parsedEncrypted.data;
+  /** Lists the files in the plugin's storage folder, with paths relative to it */
+  public async listPluginFiles(pluginId: string) {
+    return await this.#invoke(
+      "list_plugin_files",
+      { pluginId },
+      z.array(z.object({ path: z.string(), size: z.number() })),
+    );
   }
 
-  public async openUrl(pluginId: string, url: string) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
-      url,
-      pluginId,
-    });
-    const response = await invoke("open_url", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
-    console.log(response);
-    return z
-      .discriminatedUnion("success", [
-        z.object({ success: z.literal(true) }),
-        z.object({
-          success: z.literal(false),
-          reason: z.string(),
-        }),
-      ])
-      .parse(response);
+  /** Reads a file from the plugin's storage folder as text */
+  public async readPluginFile(pluginId: string, path: string) {
+    return await this.#invoke(
+      "read_plugin_file",
+      { pluginId, path },
+      z.string(),
+    );
   }
 
-  public async getPlugin(pluginId: string) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
+  /** Creates or replaces a file in the plugin's storage folder */
+  public async writePluginFile(
+    pluginId: string,
+    path: string,
+    contents: string,
+  ) {
+    return await this.#invokeEmpty("write_plugin_file", {
       pluginId,
+      path,
+      contents,
     });
+  }
 
-    const response = await invoke("get_plugin", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
+  public async deletePluginFile(pluginId: string, path: string) {
+    return await this.#invokeEmpty("delete_plugin_file", { pluginId, path });
+  }
 
-    const parsedEncrypted = EncryptedCommandResponse.safeParse(response);
+  /**
+   * Not a command directly. Decrypts the payload of a `settings_update` or `core/plugins/settings_update` event
+   */
+  public async decryptSettingsPayload(response: unknown) {
+    return await this.#decrypt(response, SettingValueZod);
+  }
 
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    if (!parsedEncrypted.data.success) {
-      return parsedEncrypted.data;
-    }
+  /**
+   * Opens the live journal stream of a plugin. `onFrame` is invoked with each batch of journal events, one after another.
+   * `dropped` is the number of events lost before this batch, because the window fell behind.
+   * The stream stays open until the page is unloaded or the plugin opens a new one
+   */
+  public async openJournalStream(
+    pluginId: string,
+    onFrame: (events: JournalEvent[], dropped: number) => void | Promise<void>,
+  ) {
+    const channel = this.#journalChannel(pluginId, onFrame);
+    return await this.#invokeEmpty(
+      "open_journal_stream",
+      { pluginId },
+      { onFrame: channel },
+    );
+  }
 
-    // at this point we are successful. Time to decrypt
-    let payload: object;
-    try {
-      payload = await decryptPayload(
-        this.#key,
-        parsedEncrypted.data.iv,
-        parsedEncrypted.data.payload
-      );
-    } catch (e) {
-      return {
-        success: false as const,
-        reason: "DECRYPT_FAILED",
-        meta: e,
-      };
-    }
+  /**
+   * Tells the backend that the first `received` frames of a journal stream were handled, so it can send more
+   */
+  public async ackJournalFrames(
+    pluginId: string,
+    channelId: number,
+    received: number,
+  ) {
+    return await this.#invokeEmpty("ack_journal_frames", {
+      pluginId,
+      channelId,
+      received,
+    });
+  }
 
-    const verifiedPayload = PluginStateZod.safeParse(payload);
-    if (verifiedPayload.error) {
-      return {
-        success: false as const,
-        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(verifiedPayload.error),
-      };
+  /**
+   * Creates a channel that receives binary journal frames. Frames are decrypted and handed to `onFrame` in order.
+   * Each frame is acknowledged once `onFrame` returned
+   */
+  #journalChannel(
+    pluginId: string,
+    onFrame: (events: JournalEvent[], dropped: number) => void | Promise<void>,
+  ) {
+    const channel = new Channel<ArrayBuffer>();
+    let received = 0;
+    let queue = Promise.resolve();
+    channel.onmessage = (frame) => {
+      queue = queue.then(async () => {
+        try {
+          const body = JournalFrameZod.parse(
+            await decryptFrame(this.#key, frame),
+          );
+          await onFrame(body.events, body.dropped);
+        } catch (e) {
+          console.error("failed to handle journal frame", e);
+        }
+        received += 1;
+        const ack = await this.ackJournalFrames(pluginId, channel.id, received);
+        if (!ack.success) {
+          console.error("failed to ack journal frame", { reason: ack.reason });
+        }
+      });
+    };
+    return channel;
+  }
+
+  /**
+   * Rereads the active journals of all CMDRs. The backend streams their events as frames and only returns once all frames were handled
+   */
+  public async rereadActiveJournals(pluginId: string) {
+    const events: JournalEvent[] = [];
+    const channel = this.#journalChannel(pluginId, (frame) => {
+      events.push(...frame);
+    });
+    const response = await this.#invokeEmpty(
+      "reread_active_journal",
+      { pluginId },
+      { onFrame: channel },
+    );
+    if (!response.success) {
+      return response;
     }
     return {
       success: true as const,
-      data: verifiedPayload.data,
+      data: events,
     };
   }
 
-  public async writeSetting(pluginId: string, key: string, value: any) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
-      pluginId,
-      key,
-      value,
-    });
+  public async syncMainLayout(
+    maybeNewLayout?: undefined | z.infer<typeof PluginViewStructureZod>
+  ) {
+    if (!maybeNewLayout) {
+      maybeNewLayout = undefined;
+    }
+    return await this.#invoke(
+      "sync_main_layout",
+      { layout: maybeNewLayout },
+      PluginViewStructureZod,
+    );
+  }
 
-    const response = await invoke("write_setting", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
+  public async trustPluginPublisher(pluginId: string) {
+    return await this.#invokeEmpty("trust_plugin_publisher", { pluginId });
+  }
 
-    const parsedEncrypted = EncryptedCommandResponse.safeParse(response);
+  public async untrustPluginPublisher(pluginId: string) {
+    return await this.#invokeEmpty("untrust_plugin_publisher", { pluginId });
+  }
 
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    if (!parsedEncrypted.data.success) {
-      return parsedEncrypted.data;
-    }
+  public async retryPlugin(pluginId: string) {
+    return await this.#invokeEmpty("retry_plugin", { pluginId });
+  }
 
-    // at this point we are successful. Time to decrypt
-    let payload: object;
-    try {
-      payload = await decryptPayload(
-        this.#key,
-        parsedEncrypted.data.iv,
-        parsedEncrypted.data.payload
-      );
-    } catch (e) {
-      return {
-        success: false as const,
-        reason: "DECRYPT_FAILED",
-        meta: e,
-      };
-    }
+  public async fetchDevPlugins() {
+    return await this.#invoke(
+      "fetch_dev_plugins",
+      {},
+      z.record(z.string(), z.string()),
+    );
+  }
 
-    const verifiedPayload = z
-      .object({ key: z.string(), value: z.any().optional().nullable() })
-      .safeParse(payload);
-    if (verifiedPayload.error) {
-      return {
-        success: false as const,
-        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(verifiedPayload.error),
-      };
-    }
-    return {
-      success: true as const,
-      data: verifiedPayload.data,
-    };
+  public async linkDevPlugin(pluginId: string, pluginDir: string) {
+    return await this.#invokeEmpty("link_dev_plugin", { pluginId, pluginDir });
   }
 
-  public async readSetting(pluginId: string, key: string) {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
+  public async unlinkDevPlugin(pluginId: string) {
+    return await this.#invokeEmpty("unlink_dev_plugin", { pluginId });
+  }
+
+  public async setPluginDir(pluginDir: string, movePlugins: boolean) {
+    return await this.#invoke(
+      "set_plugin_dir",
+      { pluginDir, movePlugins },
+      z.object({ pluginDir: z.string(), notMoved: z.array(z.string()) }),
+    );
+  }
+
+  public async fetchPluginRoots() {
+    return await this.#invoke(
+      "fetch_plugin_roots",
+      {},
+      z.array(PluginRootZod),
+    );
+  }
+
+  public async setPluginRoots(shared: string[], system: string[]) {
+    return await this.#invokeEmpty("set_plugin_roots", { shared, system });
+  }
+
+  public async sendSidecarMessage(pluginId: string, message: unknown) {
+    return await this.#invokeEmpty("send_sidecar_message", {
       pluginId,
-      key,
+      payload: message,
     });
+  }
 
-    const response = await invoke("read_setting", {
-      iv: reqIv,
-      payload: reqPayload,
+  /**
+   * Not a command directly. Decrypts the payload of a `core/plugins/sidecar_message` event
+   */
+  public async decryptSidecarMessage(response: unknown) {
+    return await this.#decrypt(
+      response,
+      z.object({ pluginId: z.string(), payload: z.unknown() }),
+    );
+  }
+
+  public async publishBusMessage(
+    pluginId: string,
+    topic: string,
+    message: unknown,
+    retain: boolean,
+  ) {
+    return await this.#invokeEmpty("publish_bus_message", {
+      pluginId,
+      topic,
+      payload: message,
+      retain,
     });
-    return await this.decryptSettingsPayload(response);
+  }
+
+  public async fetchRetainedBusMessages(pluginId: string) {
+    return await this.#invoke(
+      "fetch_retained_bus_messages",
+      { pluginId },
+      z.array(BusMessageZod),
+    );
+  }
+
+  public async fetchPluginPermissions(pluginId: string) {
+    return await this.#invoke(
+      "fetch_plugin_permissions",
+      { pluginId },
+      z.object({
+        requested: z.array(PermissionZod),
+        granted: z.array(PermissionZod),
+        urlDecisions: z.record(z.string(), UrlDecisionZod),
+      }),
+    );
   }
 
   /**
-   * Not a command directly
+   * Replaces the permissions granted to the plugin. Permissions the plugin doesn't request are dropped
    */
-  public async decryptSettingsPayload(response: unknown) {
-    const parsedEncrypted = EncryptedCommandResponse.safeParse(response);
+  public async grantPluginPermissions(
+    pluginId: string,
+    permissions: Permission[],
+  ) {
+    return await this.#invokeEmpty("grant_plugin_permissions", {
+      pluginId,
+      permissions,
+    });
+  }
 
-    if (!parsedEncrypted.success) {
-      return {
-        success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(parsedEncrypted.error),
-      };
-    }
-    if (!parsedEncrypted.data.success) {
-      return parsedEncrypted.data;
-    }
+  /**
+   * Returns the plugins whose requested permissions were not shown to the user yet
+   */
+  public async fetchPermissionRequests() {
+    return await this.#invoke(
+      "fetch_permission_requests",
+      {},
+      z.array(PermissionRequestZod),
+    );
+  }
 
-    // at this point we are successful. Time to decrypt
-    let payload: object;
-    try {
-      payload = await decryptPayload(
-        this.#key,
-        parsedEncrypted.data.iv,
-        parsedEncrypted.data.payload
-      );
-    } catch (e) {
-      return {
-        success: false as const,
-        reason: "DECRYPT_FAILED",
-        meta: e,
-      };
-    }
+  /**
+   * Returns the latest entries of the audit log, newest first. If `pluginId` is set, only entries the plugin performed or is concerned by are returned
+   */
+  public async fetchAuditLog(pluginId?: string, limit?: number) {
+    return await this.#invoke(
+      "fetch_audit_log",
+      { pluginId, limit },
+      z.array(AuditEntryZod),
+    );
+  }
 
-    const verifiedPayload = z
-      .object({ key: z.string(), value: z.any().optional().nullable() })
-      .safeParse(payload);
-    if (verifiedPayload.error) {
-      console.error(payload);
-      return {
-        success: false as const,
-        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(verifiedPayload.error),
-      };
-    }
+  /**
+   * Not a command directly. Decrypts the payload of a `core/plugins/bus_message` event
+   */
+  public async decryptBusMessage(response: unknown) {
+    return await this.#decrypt(response, BusMessageZod);
+  }
+
+  /**
+   * Not a command directly. Decrypts the payload of a `core/plugins/update` or `core/plugins/restart` event.
+   * Updates about broken plugins carry no plugin state, these resolve to `null`
+   */
+  public async decryptPluginUpdate(response: unknown) {
+    return await this.#decrypt(
+      response,
+      z.union([
+        z.object({ id: z.string(), pluginState: PluginStateZod }),
+        z.object({ broken: z.unknown() }).transform(() => null),
+      ]),
+    );
+  }
+
+  /**
+   * Not a command directly. Decrypts the payload of a `core/open_url/prompt` event
+   */
+  public async decryptUrlPrompt(response: unknown) {
+    return await this.#decrypt(response, UrlPromptZod);
+  }
+
+  /**
+   * Not a command directly. Decrypts the payload of a `core/plugins/permissions_requested` event
+   */
+  public async decryptPermissionRequest(response: unknown) {
+    return await this.#decrypt(response, PermissionRequestZod);
+  }
+
+  /**
+   * Runs several read-only commands in one round trip, all against the same state of the backend.
+   * Resolves to one result per operation, in order. A failing operation does not affect the others
+   */
+  public async runBatch<const T extends readonly BatchOperation[]>(
+    operations: T,
+  ) {
+    const response = await this.#invoke(
+      "run_batch",
+      {
+        operations: operations.map(({ command, request }) => ({
+          command,
+          request,
+        })) as Commands["run_batch"]["request"]["operations"],
+      },
+      z.array(BatchOutcomeZod).length(operations.length),
+    );
+    if (!response.success) {
+      return response;
+    }
+    const results = response.data.map((outcome, i) => {
+      if ("error" in outcome) {
+        return { success: false as const, ...outcome.error } as CommandFailure;
+      }
+      const verifiedPayload = operations[i].zod.safeParse(outcome.ok);
+      if (verifiedPayload.error) {
+        return {
+          success: false as const,
+          reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID" as const,
+          meta: z.treeifyError(verifiedPayload.error),
+        };
+      }
+      return { success: true as const, data: verifiedPayload.data };
+    });
     return {
       success: true as const,
-      data: verifiedPayload.data,
+      data: results as {
+        -readonly [I in keyof T]: BatchResult<z.output<T[I]["zod"]>>;
+      },
     };
   }
 
-  public async rereadActiveJournals() {
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(
-      this.#key,
-      {}
-    );
+  /**
+   * Encrypts `request`, invokes `command` and decrypts what it answers with. The decrypted response is checked against `zod`
+   */
+  async #invoke<K extends keyof Commands, T extends z.ZodType>(
+    command: K,
+    request: Commands[K]["request"],
+    zod: T,
+  ) {
+    const { iv, payload } = await encryptPayload(this.#key, request);
+    const response = await invoke(command, { iv, payload });
+    return await this.#decrypt(response, zod);
+  }
 
-    const response = await invoke("reread_active_journal", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
+  /**
+   * Like `#invoke`, for commands that only answer with whether they succeeded. `args` are passed to `invoke` next to the payload, e.g. channels
+   */
+  async #invokeEmpty<K extends keyof Commands>(
+    command: K,
+    request: Commands[K]["request"],
+    args: Record<string, unknown> = {},
+  ) {
+    const { iv, payload } = await encryptPayload(this.#key, request);
+    const response = await invoke(command, { ...args, iv, payload });
 
-    const parsedEncrypted = EncryptedCommandResponse.safeParse(response);
+    const parsedEncrypted = EncryptedCommandEmptyResponse.safeParse(response);
 
     if (!parsedEncrypted.success) {
       return {
         success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
+        reason: "RESPONSE_STRUCTURE_INVALID" as const,
         meta: z.treeifyError(parsedEncrypted.error),
       };
     }
     if (!parsedEncrypted.data.success) {
-      return parsedEncrypted.data;
+      return parsedEncrypted.data as CommandFailure;
     }
-
-    // at this point we are successful. Time to decrypt
-    let payload: object;
-    try {
-      payload = await decryptPayload(
-        this.#key,
-        parsedEncrypted.data.iv,
-        parsedEncrypted.data.payload
-      );
-    } catch (e) {
-      return {
-        success: false as const,
-        reason: "DECRYPT_FAILED",
-        meta: e,
-      };
-    }
-
-    const verifiedPayload = z
-      .array(
-        z.object({
-          cmdr: z.string(),
-          file: z.string(),
-          entries: z.array(z.string()),
-        })
-      )
-      .safeParse(payload);
-    if (!verifiedPayload.success) {
-      return {
-        success: false as const,
-        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID",
-        meta: z.treeifyError(verifiedPayload.error),
-      };
-    }
-    return {
-      success: true as const,
-      data: verifiedPayload.data,
-    };
+    return { success: true as const };
   }
 
-  public async syncMainLayout(
-    maybeNewLayout?: undefined | z.infer<typeof PluginViewStructureZod>
-  ) {
-    if (!maybeNewLayout) {
-      maybeNewLayout = undefined;
-    }
-    const { iv: reqIv, payload: reqPayload } = await encryptPayload(this.#key, {
-      layout: maybeNewLayout,
-    });
-    const response = await invoke("sync_main_layout", {
-      iv: reqIv,
-      payload: reqPayload,
-    });
-
+  /** Decrypts the response of a command or the payload of an event, and checks it against `zod` */
+  async #decrypt<T extends z.ZodType>(response: unknown, zod: T) {
     const parsedEncrypted = EncryptedCommandResponse.safeParse(response);
 
     if (!parsedEncrypted.success) {
       return {
         success: false as const,
-        reason: "RESPONSE_STRUCTURE_INVALID",
+        reason: "RESPONSE_STRUCTURE_INVALID" as const,
         meta: z.treeifyError(parsedEncrypted.error),
       };
     }
     if (!parsedEncrypted.data.success) {
-      return parsedEncrypted.data;
+      return parsedEncrypted.data as CommandFailure;
     }
 
-    // at this point we are successful. Time to decrypt
     let payload: object;
     try {
       payload = await decryptPayload(
         this.#key,
         parsedEncrypted.data.iv,
-        parsedEncrypted.data.payload
+        parsedEncrypted.data.payload,
       );
     } catch (e) {
       return {
         success: false as const,
-        reason: "DECRYPT_FAILED",
+        reason: "DECRYPT_FAILED" as const,
         meta: e,
       };
     }
 
-    const verifiedPayload = PluginViewStructureZod.safeParse(payload);
+    const verifiedPayload = zod.safeParse(payload);
     if (verifiedPayload.error) {
       return {
         success: false as const,
-        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID",
+        reason: "DECRYPTED_RESPONSE_STRUCTURE_INVALID" as const,
         meta: z.treeifyError(verifiedPayload.error),
       };
     }
-    return {
-      success: true as const,
-      data: verifiedPayload.data,
-    };
+    return { success: true as const, data: verifiedPayload.data as z.output<T> };
   }
 }
@@ -16,12 +16,14 @@ export async function getRootToken() {
   } else {
     const keyBytes = base64ToBytesNoPadding(response.data) as BufferSource;
 
-    return crypto.subtle.importKey(
-      "raw",
-      keyBytes,
-      { name: "AES-GCM" },
-      false,
-      ["encrypt", "decrypt"],
-    );
+    // the same bytes are imported a second time as HKDF key material to derive the plugin keys
+    const [key, derivationKey] = await Promise.all([
+      crypto.subtle.importKey("raw", keyBytes, { name: "AES-GCM" }, false, [
+        "encrypt",
+        "decrypt",
+      ]),
+      crypto.subtle.importKey("raw", keyBytes, "HKDF", false, ["deriveKey"]),
+    ]);
+    return { key, derivationKey };
   }
 }
@@ -9,6 +9,8 @@ export function PluginTypeIcon(
     return <IconoirPackageLock {...props} />;
   } else if (props.type === "UserProvided") {
     return <IconoirUser {...props} />;
+  } else if (props.type === "Dev") {
+    return <IconoirCode {...props} />;
   } else {
     throw new Error("unknown plugin type");
   }
@@ -36,6 +38,28 @@ function IconoirPackageLock(props: SVGProps<SVGSVGElement>) {
   );
 }
 
+function IconoirCode(props: SVGProps<SVGSVGElement>) {
+  return (
+    <svg
+      xmlns="http://www.w3.org/2000/svg"
+      width="1em"
+      height="1em"
+      viewBox="0 0 24 24"
+      {...props}
+    >
+      {/* Icon from Iconoir by Luca Burgio - https://github.com/iconoir-icons/iconoir/blob/main/LICENSE */}
+      <path
+        fill="none"
+        stroke="currentColor"
+        strokeLinecap="round"
+        strokeLinejoin="round"
+        strokeWidth="1.5"
+        d="M13.5 6L10 18.5m-3.5-10L3 12l3.5 3.5m11-7L21 12l-3.5 3.5"
+      />
+    </svg>
+  );
+}
+
 function IconoirUser(props: SVGProps<SVGSVGElement>) {
   return (
     <svg
@@ -19,6 +19,8 @@ import {
 import { CommandWrapper } from "../commands/commandWrapper";
 import { listen, UnlistenFn } from "@tauri-apps/api/event";
 import { useTranslation } from "react-i18next";
+import { UrlPromptDialog } from "./UrlPromptDialog";
+import { PermissionRequestDialog } from "./PermissionRequestDialog";
 
 export default function App() {
   const { i18n } = useTranslation("settings");
@@ -33,6 +35,7 @@ export default function App() {
   const [layout, setLayout] =
     useState<z.infer<typeof PluginViewStructureZod>>();
   const [currentDraggingItem, setCurrentDraggingItem] = useState<Active>();
+  const [commands, setCommands] = useState<CommandWrapper>();
 
   const pluginsInParkingLot = makePluginIdsInParkingLot(
     pluginState ?? {},
@@ -44,8 +47,9 @@ export default function App() {
 
     let manager: PluginsManager | undefined;
     if (!pluginManagerRef.current || pluginManagerRef.current.destroyed) {
-      getRootToken().then((rootToken) => {
-        const command = new CommandWrapper(rootToken);
+      getRootToken().then(({ key, derivationKey }) => {
+        const command = new CommandWrapper(key, derivationKey);
+        setCommands(command);
         command.syncMainLayout().then((e) => {
           if (e.success) {
             setLayout(e.data);
@@ -248,6 +252,8 @@ export default function App() {
           ) : null}
         </DragOverlay>
       </DndContext>
+      {commands && <UrlPromptDialog commands={commands} />}
+      {commands && <PermissionRequestDialog commands={commands} />}
     </main>
   );
 }
@@ -0,0 +1,107 @@
+import { useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { useTranslation } from "react-i18next";
+import { CommandWrapper, PermissionRequest } from "../commands/commandWrapper";
+
+/**
+ * Asks the user to grant the permissions of a newly discovered plugin.
+ * The backend sends `core/plugins/permissions_requested` when it finds the plugin, requests made before this window loaded are fetched once.
+ * Either answer marks the plugin as known, so it is only asked once. Permissions can still be changed in the settings
+ */
+export function PermissionRequestDialog({
+  commands,
+}: {
+  commands: CommandWrapper;
+}) {
+  const { t } = useTranslation(["main", "settings"]);
+  const [requests, setRequests] = useState<PermissionRequest[]>([]);
+
+  useEffect(() => {
+    const add = (request: PermissionRequest) =>
+      setRequests((e) => [
+        ...e.filter((f) => f.pluginId !== request.pluginId),
+        request,
+      ]);
+
+    commands.fetchPermissionRequests().then((resp) => {
+      if (!resp.success) {
+        console.error("failed to fetch permission requests", resp);
+        return;
+      }
+      resp.data.forEach(add);
+    });
+    const unlisten = listen(
+      "core/plugins/permissions_requested",
+      async ({ payload }) => {
+        const decrypted = await commands.decryptPermissionRequest(payload);
+        if (!decrypted.success) {
+          console.error("failed to RX permission request", decrypted);
+          return;
+        }
+        add(decrypted.data);
+      },
+    );
+    return () => {
+      unlisten.then((e) => e());
+    };
+  }, [commands]);
+
+  const current = requests[0];
+  if (!current) {
+    return null;
+  }
+
+  // "Not now" grants nothing, which still marks the plugin as known
+  const answer = async (grantAll: boolean) => {
+    const resp = await commands.grantPluginPermissions(
+      current.pluginId,
+      grantAll ? current.requested : [],
+    );
+    // PLUGIN_STATE_NOT_FOUND means the plugin was removed in the meantime
+    if (!resp.success && resp.reason !== "PLUGIN_STATE_NOT_FOUND") {
+      console.error("failed to answer permission request", resp);
+    }
+    setRequests((e) => e.filter((f) => f.pluginId !== current.pluginId));
+  };
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
+      <section
+        role="alertdialog"
+        aria-labelledby="permission_request_heading"
+        className="flex flex-col gap-2 max-w-md rounded-lg bg-neutral-800 p-4 text-white"
+      >
+        <h2 id="permission_request_heading" className="font-bold">
+          {t("permissionRequest.heading", { name: current.name })}
+        </h2>
+        <p className="text-sm">{t("permissionRequest.intro")}</p>
+        <ul className="list-disc pl-5 text-sm">
+          {current.requested.map((e) => (
+            <li key={JSON.stringify(e)}>
+              {t(`settings:permissions.${e.type}`, e)}
+            </li>
+          ))}
+        </ul>
+        {current.manifestWarnings.map((e) => (
+          <p key={e} className="text-sm text-orange-300">
+            {e}
+          </p>
+        ))}
+        <span className="inline-flex justify-end gap-1">
+          <button
+            className="rounded-lg px-2 py-1 bg-white/10 hover:bg-white/20 cursor-pointer text-sm"
+            onClick={() => answer(false)}
+          >
+            {t("permissionRequest.notNow")}
+          </button>
+          <button
+            className="rounded-lg px-2 py-1 bg-blue-700 hover:bg-blue-600 cursor-pointer text-sm"
+            onClick={() => answer(true)}
+          >
+            {t("permissionRequest.grantAll")}
+          </button>
+        </span>
+      </section>
+    </div>
+  );
+}
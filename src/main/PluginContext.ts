@@ -1,6 +1,11 @@
 import { listen } from "@tauri-apps/api/event";
 import {} from "@elite-dangerous-plugin-framework/core/v1alpha";
-import { CommandWrapper } from "../commands/commandWrapper";
+import {
+  BusMessage,
+  CommandWrapper,
+  ImportPathZod,
+} from "../commands/commandWrapper";
+import { PluginStateZod } from "../types/PluginState";
 import z from "zod";
 import {
   JournalEventItemV1Alpha,
@@ -12,6 +17,14 @@ import {
   PluginManifestV1AlphaWithId,
 } from "@elite-dangerous-plugin-framework/core/v1alpha/internal";
 
+/**
+ * Plugin specific events are encrypted with the key of the receiving plugin, which is named in plain text.
+ * Other plugins could not decrypt them anyways, so they skip them before trying.
+ */
+function isAddressedTo(payload: unknown, pluginId: string) {
+  return z.object({ pluginId: z.literal(pluginId) }).safeParse(payload).success;
+}
+
 export class PluginContextV1AlphaImpl implements PluginContextV1Alpha {
   /**
    * Dangerous property. Peek internal and never expose
@@ -75,6 +88,7 @@ export class PluginContextV1AlphaImpl implements PluginContextV1Alpha {
     }
 
     this.#eventListenerDestructors = {};
+    this.#journalEventListeners = {};
     this.#destroyed = true;
   }
 
@@ -84,21 +98,145 @@ export class PluginContextV1AlphaImpl implements PluginContextV1Alpha {
   #eventListenerDestructors: Record<symbol, "awaitingResolve" | (() => void)> =
     {};
 
+  /**
+   * All journal event listeners. They share the plugin's journal stream, which is opened with the first listener
+   */
+  #journalEventListeners: Record<
+    symbol,
+    (events: JournalEventItemV1Alpha[]) => void
+  > = {};
+  #journalStream: Promise<unknown> | undefined;
+
   public registerEventListener(
     callback: (events: JournalEventItemV1Alpha[]) => void,
   ): () => void {
-    const unlisten = listen("journal_events", (ev) => {
-      const verifiedPayload = z
-        .array(
-          z.object({
-            cmdr: z.string(),
-            file: z.string(),
-            event: z.string(),
-          }),
-        )
-        .parse(ev.payload);
-
-      callback(verifiedPayload as any);
+    const sym = Symbol();
+    this.#journalEventListeners[sym] = callback;
+    this.#journalStream ??= this.#commands
+      .openJournalStream(this.manifest.id, (events, dropped) => {
+        if (this.#destroyed) {
+          return;
+        }
+        if (dropped > 0) {
+          console.warn(
+            `${this.manifest.id} fell behind, ${dropped} journal events were dropped`,
+          );
+        }
+        for (const listener of Object.getOwnPropertySymbols(
+          this.#journalEventListeners,
+        )) {
+          this.#journalEventListeners[listener](events as any);
+        }
+      })
+      .then((resp) => {
+        if (!resp.success) {
+          console.error("failed to open journal stream", {
+            reason: resp.reason,
+          });
+          this.#journalStream = undefined;
+        }
+      });
+    return () => {
+      delete this.#journalEventListeners[sym];
+    };
+  }
+
+  /**
+   * Sends a message to the plugin's sidecar and WASM backend, if its manifest declares one.
+   * The sidecar receives it as a JSON-RPC `message` notification, the WASM backend via `edpf_on_message`.
+   */
+  public async sendSidecarMessage(message: unknown): Promise<void> {
+    const resp = await this.#commands.sendSidecarMessage(
+      this.manifest.id,
+      message,
+    );
+    if (!resp.success) {
+      throw new Error("failed to send sidecar message: " + resp.reason);
+    }
+  }
+
+  /**
+   * Invokes the callback for each message the plugin's sidecar or WASM backend sends.
+   */
+  public registerSidecarMessageListener(
+    callback: (message: unknown) => void,
+  ): () => void {
+    const unlisten = listen("core/plugins/sidecar_message", async (ev) => {
+      if (!isAddressedTo(ev.payload, this.manifest.id)) {
+        return;
+      }
+      const decrypted = await this.#commands.decryptSidecarMessage(ev.payload);
+      if (!decrypted.success) {
+        console.error("failed to RX sidecar message", {
+          reason: decrypted.reason,
+        });
+        return;
+      }
+      callback(decrypted.data.payload);
+    });
+    const sym = Symbol();
+    this.#eventListenerDestructors[sym] = "awaitingResolve";
+    unlisten.then((e) => (this.#eventListenerDestructors[sym] = e));
+    return () => {
+      this.#eventListenerDestructors[sym] &&
+        typeof this.#eventListenerDestructors[sym] === "function" &&
+        this.#eventListenerDestructors[sym]();
+      delete this.#eventListenerDestructors[sym];
+    };
+  }
+
+  /**
+   * Publishes a message on one of this plugin's topics. The topic must be listed in `message_bus.publishes` of the manifest.
+   * Subscribers receive it on `$pluginId/$topic`.
+   * @param retain keep this as the topic's last message, so plugins that start later can catch up
+   */
+  public async publish(
+    topic: string,
+    message: unknown,
+    retain = false,
+  ): Promise<void> {
+    const resp = await this.#commands.publishBusMessage(
+      this.manifest.id,
+      topic,
+      message,
+      retain,
+    );
+    if (!resp.success) {
+      throw new Error("failed to publish bus message: " + resp.reason);
+    }
+  }
+
+  /**
+   * Returns the retained messages of all topics this plugin subscribes to
+   */
+  public async fetchRetainedMessages(): Promise<BusMessage[]> {
+    const resp = await this.#commands.fetchRetainedBusMessages(
+      this.manifest.id,
+    );
+    if (!resp.success) {
+      throw new Error("failed to fetch retained bus messages: " + resp.reason);
+    }
+    return resp.data;
+  }
+
+  /**
+   * Invokes the callback for each message published on a topic this plugin subscribes to via `message_bus.subscribes` in the manifest.
+   */
+  public registerBusMessageListener(
+    callback: (message: BusMessage) => void,
+  ): () => void {
+    const unlisten = listen("core/plugins/bus_message", async (ev) => {
+      if (!isAddressedTo(ev.payload, this.manifest.id)) {
+        return;
+      }
+      const decrypted = await this.#commands.decryptBusMessage(ev.payload);
+      if (!decrypted.success) {
+        console.error("failed to RX bus message", {
+          reason: decrypted.reason,
+        });
+        return;
+      }
+      callback(decrypted.data);
     });
     const sym = Symbol();
     this.#eventListenerDestructors[sym] = "awaitingResolve";
@@ -111,6 +249,51 @@ export class PluginContextV1AlphaImpl implements PluginContextV1Alpha {
     };
   }
 
+  /**
+   * Lists the files in this plugin's storage folder. Needs `permissions.file_storage` in the manifest.
+   * Paths are relative to the folder, with `/` as separator
+   */
+  public async listFiles(): Promise<{ path: string; size: number }[]> {
+    const resp = await this.#commands.listPluginFiles(this.manifest.id);
+    if (!resp.success) {
+      throw new Error("failed to list files: " + resp.reason);
+    }
+    return resp.data;
+  }
+
+  /**
+   * Reads a text file from this plugin's storage folder. Needs `permissions.file_storage` in the manifest
+   */
+  public async readFile(path: string): Promise<string> {
+    const resp = await this.#commands.readPluginFile(this.manifest.id, path);
+    if (!resp.success) {
+      throw new Error("failed to read file: " + resp.reason);
+    }
+    return resp.data;
+  }
+
+  /**
+   * Creates or replaces a text file in this plugin's storage folder. Missing folders are created.
+   * Path segments may only contain letters, digits, `-`, `_` and `.`. Needs `permissions.file_storage` in the manifest
+   */
+  public async writeFile(path: string, contents: string): Promise<void> {
+    const resp = await this.#commands.writePluginFile(
+      this.manifest.id,
+      path,
+      contents,
+    );
+    if (!resp.success) {
+      throw new Error("failed to write file: " + resp.reason);
+    }
+  }
+
+  public async deleteFile(path: string): Promise<void> {
+    const resp = await this.#commands.deletePluginFile(this.manifest.id, path);
+    if (!resp.success) {
+      throw new Error("failed to delete file: " + resp.reason);
+    }
+  }
+
   #shutdownListener: Record<symbol, () => Promise<void>> = {};
   public registerShutdownListener(callback: () => Promise<void>): () => void {
     const sym = Symbol();
@@ -123,16 +306,15 @@ export class PluginContextV1AlphaImpl implements PluginContextV1Alpha {
   public async rereadCurrentJournals(): Promise<
     Record<string, JournalEventItemV1Alpha[]>
   > {
-    const result = await this.#commands.rereadActiveJournals();
+    const result = await this.#commands.rereadActiveJournals(this.manifest.id);
     if (!result.success) {
       throw new Error("failed to reread active journals: " + result.reason);
     }
-    return Object.fromEntries(
-      result.data.map((e) => [
-        e.cmdr,
-        e.entries.map((f) => ({ cmdr: e.cmdr, file: e.file, event: f })),
-      ]),
-    );
+    const journals: Record<string, JournalEventItemV1Alpha[]> = {};
+    for (const event of result.data) {
+      (journals[event.cmdr] ??= []).push(event as any);
+    }
+    return journals;
   }
 
   /**
@@ -142,10 +324,18 @@ export class PluginContextV1AlphaImpl implements PluginContextV1Alpha {
    * The instanceID also acts as Pointer to the Plugin State, which means we can get the Plugin ID that way.
    */
   public static async create(pluginId: string, commands: CommandWrapper) {
-    const [stateZod, importPathZod] = await Promise.all([
-      commands.getPlugin(pluginId),
-      commands.getImportPathForPlugin(pluginId),
+    const batch = await commands.runBatch([
+      { command: "get_plugin", request: { pluginId }, zod: PluginStateZod },
+      {
+        command: "get_import_path_for_plugin",
+        request: { pluginId },
+        zod: ImportPathZod,
+      },
     ]);
+    if (!batch.success) {
+      throw new Error("failed to get Plugin: " + batch.reason);
+    }
+    const [stateZod, importPathZod] = batch.data;
     if (!stateZod.success) {
       throw new Error("failed to get Plugin: " + stateZod.reason);
     }
@@ -156,13 +346,15 @@ export class PluginContextV1AlphaImpl implements PluginContextV1Alpha {
     }
     const importPath = importPathZod.data.import;
     const assetsBase = importPath.substring(0, importPath.lastIndexOf("/") + 1);
+    // the context only gets the plugin's own key, never the root token
+    const pluginCommands = await commands.forPlugin(pluginId);
 
     const ctx = new PluginContextV1AlphaImpl(
-      commands,
+      pluginCommands,
       assetsBase,
       { ...stateZod.data.manifest, id: pluginId },
       PluginContextCapabilitiesV1AlphaImpl.create(
-        commands,
+        pluginCommands,
         pluginId,
         assetsBase,
       ),
@@ -239,7 +431,10 @@ export class PluginContextV1AlphaCapabilitiesSettingsImpl implements PluginConte
   ): () => void {
     const sym = Symbol();
     this.#eventListenerDestructors[sym] = "awaitingResolve";
-    listen("settings_update", async ({ payload }) => {
+    listen("core/plugins/settings_update", async ({ payload }) => {
+      if (!isAddressedTo(payload, this.pluginId)) {
+        return;
+      }
       const decrypted = await this.commands.decryptSettingsPayload(payload);
       if (!decrypted || !decrypted.success) {
         console.error("failed to RX settings update", {
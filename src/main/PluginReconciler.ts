@@ -77,6 +77,14 @@ export default class PluginReconcilerImpl implements PluginReconciler {
             console.error("failed to finalize stop: " + resp.reason)
           }
           return [];
+        case "Restarting": {
+          // Nothing to unload. We acknowledge right away so the Backend can start the plugin with the new hash
+          const resp = await this.#command.finalizeStopPlugin(state.id);
+          if (!resp.success) {
+            console.error("failed to finalize stop during restart: " + resp.reason)
+          }
+          return [];
+        }
       }
     }
     if (state.currentUiState.type === "Running") {
@@ -85,6 +93,8 @@ export default class PluginReconcilerImpl implements PluginReconciler {
         case "Disabled":
         // getting a Disabled with the UI State Running is illogical, but we should still handle it. We get the state to converge by killing the UI State
         case "Disabling":
+        // A restart is a stop followed by a start. The Backend issues the start once we acknowledged the stop
+        case "Restarting":
           // We got a signal to stop the plugin. If we have a running state, this also means we have a context. We shut it down here
           if (!state.currentUiState.context.destroyed) {
             try {
@@ -102,7 +112,8 @@ export default class PluginReconcilerImpl implements PluginReconciler {
 
           if (
             state.current_state.type === "Disabled" ||
-            state.current_state.type === "Disabling"
+            state.current_state.type === "Disabling" ||
+            state.current_state.type === "Restarting"
           ) {
             const resp = await this.#command.finalizeStopPlugin(state.id);
             if (!resp.success) {
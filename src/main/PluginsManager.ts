@@ -136,10 +136,19 @@ export default class PluginsManager {
 
     this.#pluginStateUpdatedCb = updatePluginState;
 
-    const unlisten = await listen("core/plugins/update", async (ev) => {
-      const resp = z
-        .object({ id: z.string(), pluginState: PluginStateZod })
-        .parse(ev.payload);
+    const onPluginUpdate = async (ev: { payload: unknown }) => {
+      const decrypted = await this.#commands.decryptPluginUpdate(ev.payload);
+      if (!decrypted.success) {
+        console.error("failed to RX plugin update", {
+          reason: decrypted.reason,
+        });
+        return;
+      }
+      if (!decrypted.data) {
+        // Updates about broken plugins carry no plugin state. The main window has nothing to do with them
+        return;
+      }
+      const resp = decrypted.data;
 
       const patch = (pluginState: PluginStates) => {
         if (!pluginState[resp.id]) {
@@ -163,8 +172,12 @@ export default class PluginsManager {
       } finally {
         unlock();
       }
-    });
+    };
+    const unlisten = await listen("core/plugins/update", onPluginUpdate);
     this.#destructorCallbacks.push(unlisten);
+    // A restart carries the same payload. The Restarting state tells the reconciler to unload the plugin and acknowledge
+    const unlistenRestart = await listen("core/plugins/restart", onPluginUpdate);
+    this.#destructorCallbacks.push(unlistenRestart);
   }
 
   /**
@@ -0,0 +1,92 @@
+import { useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { useTranslation } from "react-i18next";
+import { CommandWrapper, UrlPrompt } from "../commands/commandWrapper";
+
+/**
+ * Asks the user whether a plugin may open a URL outside of its granted domains.
+ * The backend sends `core/open_url/prompt` to the window the plugin runs in and holds the plugin's `openUrl` call until it is answered.
+ * Prompts are shown one at a time, in the order they arrived
+ */
+export function UrlPromptDialog({ commands }: { commands: CommandWrapper }) {
+  const { t } = useTranslation("main");
+  const [prompts, setPrompts] = useState<UrlPrompt[]>([]);
+  const [remember, setRemember] = useState(false);
+
+  useEffect(() => {
+    const unlisten = listen("core/open_url/prompt", async ({ payload }) => {
+      const decrypted = await commands.decryptUrlPrompt(payload);
+      if (!decrypted.success) {
+        console.error("failed to RX URL prompt", decrypted);
+        return;
+      }
+      setPrompts((e) => [...e, decrypted.data]);
+    });
+    return () => {
+      unlisten.then((e) => e());
+    };
+  }, [commands]);
+
+  const current = prompts[0];
+  if (!current) {
+    return null;
+  }
+
+  const answer = async (allow: boolean) => {
+    const resp = await commands.answerUrlPrompt(
+      current.promptId,
+      allow,
+      remember,
+    );
+    // PROMPT_NOT_FOUND means the prompt timed out, there is nothing left to answer
+    if (!resp.success && resp.reason !== "PROMPT_NOT_FOUND") {
+      console.error("failed to answer URL prompt", resp);
+    }
+    setRemember(false);
+    setPrompts((e) => e.filter((f) => f.promptId !== current.promptId));
+  };
+
+  return (
+    <div className="fixed inset-0 z-50 flex items-center justify-center bg-black/60">
+      <section
+        role="alertdialog"
+        aria-labelledby="url_prompt_heading"
+        className="flex flex-col gap-2 max-w-md rounded-lg bg-neutral-800 p-4 text-white"
+      >
+        <h2 id="url_prompt_heading" className="font-bold">
+          {t("urlPrompt.heading", { pluginId: current.pluginId })}
+        </h2>
+        <p className="break-all rounded bg-black/30 p-2 font-mono text-sm">
+          {current.url}
+        </p>
+        {!current.declared && (
+          <p className="text-sm text-orange-300">
+            {t("urlPrompt.undeclared", { host: current.host })}
+          </p>
+        )}
+        <label className="inline-flex gap-2 text-sm">
+          <input
+            type="checkbox"
+            checked={remember}
+            onChange={(e) => setRemember(e.target.checked)}
+          />
+          {t("urlPrompt.remember", { host: current.host })}
+        </label>
+        <span className="inline-flex justify-end gap-1">
+          <button
+            className="rounded-lg px-2 py-1 bg-white/10 hover:bg-white/20 cursor-pointer text-sm"
+            onClick={() => answer(false)}
+          >
+            {t("urlPrompt.deny")}
+          </button>
+          <button
+            className="rounded-lg px-2 py-1 bg-blue-700 hover:bg-blue-600 cursor-pointer text-sm"
+            onClick={() => answer(true)}
+          >
+            {t("urlPrompt.allow")}
+          </button>
+        </span>
+      </section>
+    </div>
+  );
+}
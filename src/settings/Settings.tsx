@@ -10,6 +10,7 @@ import { ZondiconsFolder } from "../icons/pluginType";
 import { CommandWrapper } from "../commands/commandWrapper";
 import { getRootToken } from "../commands/getRootToken";
 import { useTranslation } from "react-i18next";
+import { UrlPromptDialog } from "../main/UrlPromptDialog";
 
 export default function Settings() {
   const [pluginStates, setPluginStates] =
@@ -38,7 +39,8 @@ export default function Settings() {
     ];
     (async () => {
       if (!commandWrapperRef.current) {
-        commandWrapperRef.current = new CommandWrapper(await getRootToken());
+        const { key, derivationKey } = await getRootToken();
+        commandWrapperRef.current = new CommandWrapper(key, derivationKey);
       }
       commandWrapperRef.current.readSetting("core", "core.Locale").then((e) => {
         if (!e.success) {
@@ -174,6 +176,9 @@ export default function Settings() {
           />
         )}
       </section>
+      {commandWrapperRef.current && (
+        <UrlPromptDialog commands={commandWrapperRef.current} />
+      )}
     </main>
   );
 }
@@ -208,7 +213,7 @@ function SettingsSidebarPlugin({
   selected: boolean;
 }) {
   let name = plugin.id;
-  if (plugin.manifest.type === "v1alpha") {
+  if (plugin.manifest.type === "v1alpha" || plugin.manifest.type === "v1") {
     name = plugin.manifest.name;
   }
 
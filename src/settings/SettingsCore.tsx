@@ -43,6 +43,7 @@ function SettingsStateVisualizer({
           "FailedToStart",
           "Running",
           "Disabling",
+          "Restarting",
         ] as PluginCurrentStateKeys[]
       )
         .filter((e) => pluginStateCount[e] > 0)
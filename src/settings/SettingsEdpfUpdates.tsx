@@ -1,12 +1,8 @@
 import { useTranslation } from "react-i18next";
-import {
-  CommandWrapper,
-  EncryptedCommandResponse,
-} from "../commands/commandWrapper";
+import { CommandWrapper } from "../commands/commandWrapper";
 import { useCallback, useEffect, useState } from "react";
 import { z } from "zod";
 import { IconCannotUpdate, IconDownload, IconUpToDate } from "../icons/updates";
-import { Channel, invoke } from "@tauri-apps/api/core";
 
 type UpdateState =
   | { type: "loading" }
@@ -121,51 +117,31 @@ export function SettingsEdpfUpdates({ cmd }: { cmd: CommandWrapper }) {
     }
     (async () => {
       setUpdateState({ type: "loading" });
-      const { iv, payload } = await cmd.encryptPayload({
-        channel: releaseChannel,
-      });
-      const r = await invoke("check_update_edpf", { iv, payload });
-      const encryptedResp = EncryptedCommandResponse.parse(r);
-      if (!encryptedResp.success) {
-        console.error(encryptedResp.reason);
+      const resp = await cmd.checkUpdateEdpf(releaseChannel);
+      if (!resp.success) {
+        console.error(resp.reason);
         return;
       }
 
-      const resp = z
-        .object({ new_version: z.string(), current_version: z.string() })
-        .nullable()
-        .parse(
-          await cmd.decryptPayload(encryptedResp.iv, encryptedResp.payload)
-        );
-      if (resp === null) {
+      if (resp.data === null) {
         setUpdateState({ type: "noUpdate" });
       } else {
         setUpdateState({
           type: "update",
-          newVersion: resp.new_version,
-          currentVersion: resp.current_version,
+          newVersion: resp.data.new_version,
+          currentVersion: resp.data.current_version,
         });
       }
     })();
   }, [releaseChannel]);
 
   const startUpdate = useCallback(async () => {
-    const { iv, payload } = await cmd.encryptPayload({})
-
-    const responseZod = z.discriminatedUnion("type", [
-      z.object({ type: z.literal("Started"), content_len: z.number() }),
-      z.object({ type: z.literal("Progress"), chunk_len: z.number() }),
-      z.object({ type: z.literal("Finished") }),
-    ])
-
-    const onEvent = new Channel<z.infer<typeof responseZod>>()
     let totalLen = 0
     let currentLen = 0
-    onEvent.onmessage = (m) => {
-      m = responseZod.parse(m)
+    cmd.commitUpdateEdpf((m) => {
       switch (m.type) {
         case "Started":
-          totalLen = m.content_len
+          totalLen = m.content_len ?? 0
           return
         case "Progress":
           currentLen += m.chunk_len
@@ -174,9 +150,7 @@ export function SettingsEdpfUpdates({ cmd }: { cmd: CommandWrapper }) {
         case "Finished":
         // noop. 
       }
-    }
-
-    invoke("commit_update_edpf", { iv, payload, onEvent })
+    })
   }, [])
 
   return (
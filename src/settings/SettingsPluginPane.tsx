@@ -6,13 +6,14 @@ import {
 } from "../icons/pluginType";
 import { PluginState } from "../types/PluginState";
 import { StatusIndicator } from "./Settings";
-import { CommandWrapper } from "../commands/commandWrapper";
+import { AuditEntry, CommandWrapper } from "../commands/commandWrapper";
 import {
   SettingsComponentLoadState,
   startAndLoadSettings,
 } from "./startAndLoadSettings";
 import { SettingsCell } from "./SettingsCell";
 import { useTranslation } from "react-i18next";
+import { Permission } from "../types/PluginManifest";
 
 export interface SettingsPluginPaneProps {
   plugin: PluginState;
@@ -20,24 +21,230 @@ export interface SettingsPluginPaneProps {
 }
 
 function getName(plugin: PluginState) {
-  if (plugin.manifest.type === "v1alpha") {
+  if (plugin.manifest.type === "v1alpha" || plugin.manifest.type === "v1") {
     return plugin.manifest.name;
   }
 }
 
 function getDescription(plugin: PluginState) {
-  if (plugin.manifest.type === "v1alpha") {
+  if (plugin.manifest.type === "v1alpha" || plugin.manifest.type === "v1") {
     return plugin.manifest.description;
   }
 }
 
 function getDisplayVersion(plugin: PluginState) {
-  if (plugin.manifest.type === "v1alpha") {
+  if (plugin.manifest.type === "v1alpha" || plugin.manifest.type === "v1") {
     return plugin.manifest.version;
   }
   return undefined;
 }
 
+function samePermission(a: Permission, b: Permission) {
+  return JSON.stringify(a) === JSON.stringify(b);
+}
+
+function PluginPermissions({ plugin, commands }: SettingsPluginPaneProps) {
+  const { t } = useTranslation("settings");
+  const [permissions, setPermissions] = useState<
+    | {
+        requested: Permission[];
+        granted: Permission[];
+        urlDecisions: Record<string, "allow" | "deny">;
+      }
+    | undefined
+  >(undefined);
+
+  const load = async () => {
+    const resp = await commands.fetchPluginPermissions(plugin.id);
+    if (!resp.success) {
+      console.error("failed to fetch plugin permissions", resp);
+      return;
+    }
+    setPermissions(resp.data);
+  };
+  useEffect(() => {
+    load();
+  }, [plugin.id]);
+
+  const grant = async (granted: Permission[]) => {
+    const resp = await commands.grantPluginPermissions(plugin.id, granted);
+    if (!resp.success) {
+      console.error("failed to grant plugin permissions", resp);
+    }
+    await load();
+  };
+
+  const forgetUrlDecision = async (host: string) => {
+    const resp = await commands.forgetUrlDecision(plugin.id, host);
+    if (!resp.success) {
+      console.error("failed to forget URL decision", resp);
+    }
+    await load();
+  };
+
+  // Embedded plugins have all permissions
+  if (!permissions || plugin.source === "Embedded") {
+    return null;
+  }
+  return (
+    <section className="flex flex-col gap-1" id="plugin_permissions">
+      <h3 className="font-bold">{t("permissions.heading")}</h3>
+      {permissions.requested.length === 0 ? (
+        <p className="text-sm text-gray-400">{t("permissions.none")}</p>
+      ) : (
+        <>
+          <ul className="text-sm">
+            {permissions.requested.map((e) => {
+              const isGranted = permissions.granted.some((f) =>
+                samePermission(e, f),
+              );
+              return (
+                <li key={JSON.stringify(e)} className="flex gap-2">
+                  <input
+                    type="checkbox"
+                    checked={isGranted}
+                    title={t(
+                      isGranted
+                        ? "permissions.granted"
+                        : "permissions.notGranted",
+                    )}
+                    onChange={() =>
+                      grant(
+                        isGranted
+                          ? permissions.granted.filter(
+                              (f) => !samePermission(e, f),
+                            )
+                          : [...permissions.granted, e],
+                      )
+                    }
+                  />
+                  <span>{t(`permissions.${e.type}`, e)}</span>
+                </li>
+              );
+            })}
+          </ul>
+          <span className="inline-flex gap-1">
+            <button
+              className="rounded-lg px-2 py-1 bg-white/10 hover:bg-white/20 cursor-pointer text-sm"
+              onClick={() => grant(permissions.requested)}
+            >
+              {t("permissions.grantAll")}
+            </button>
+            <button
+              className="rounded-lg px-2 py-1 bg-white/10 hover:bg-white/20 cursor-pointer text-sm"
+              onClick={() => grant([])}
+            >
+              {t("permissions.revokeAll")}
+            </button>
+          </span>
+        </>
+      )}
+      {Object.keys(permissions.urlDecisions).length > 0 && (
+        <>
+          <h4 className="text-sm font-bold">
+            {t("permissions.urlDecisionsHeading")}
+          </h4>
+          <ul className="text-sm">
+            {Object.entries(permissions.urlDecisions).map(([host, e]) => (
+              <li key={host} className="flex gap-2 items-center">
+                <span className="break-all">
+                  {t(
+                    e === "allow"
+                      ? "permissions.urlDecisionAllow"
+                      : "permissions.urlDecisionDeny",
+                    { host },
+                  )}
+                </span>
+                <button
+                  className="rounded-lg px-2 py-1 bg-white/10 hover:bg-white/20 cursor-pointer"
+                  onClick={() => forgetUrlDecision(host)}
+                >
+                  {t("permissions.forgetUrlDecision")}
+                </button>
+              </li>
+            ))}
+          </ul>
+        </>
+      )}
+      <hr className=" text-neutral-600 my-2" />
+    </section>
+  );
+}
+
+function PluginActivity({ plugin, commands }: SettingsPluginPaneProps) {
+  const { t } = useTranslation("settings");
+  const [entries, setEntries] = useState<AuditEntry[] | undefined>(undefined);
+
+  const load = async () => {
+    const resp = await commands.fetchAuditLog(plugin.id, 50);
+    if (!resp.success) {
+      console.error("failed to fetch audit log", resp);
+      return;
+    }
+    setEntries(resp.data);
+  };
+  useEffect(() => {
+    load();
+  }, [plugin.id]);
+
+  if (!entries) {
+    return null;
+  }
+  return (
+    <section className="flex flex-col gap-1" id="plugin_activity">
+      <span className="inline-flex gap-2 items-center">
+        <h3 className="font-bold">{t("activity.heading")}</h3>
+        <button
+          className="rounded-lg px-2 py-1 bg-white/10 hover:bg-white/20 cursor-pointer text-sm"
+          onClick={load}
+        >
+          {t("activity.refresh")}
+        </button>
+      </span>
+      {entries.length === 0 ? (
+        <p className="text-sm text-gray-400">{t("activity.none")}</p>
+      ) : (
+        <ul className="text-sm max-h-48 overflow-y-auto">
+          {entries.map((e, i) => (
+            <li key={i} className="flex gap-2">
+              <span className="text-gray-400 shrink-0">
+                {new Date(e.at).toLocaleString()}
+              </span>
+              <span className="shrink-0">
+                {e.actor ?? t("activity.user")}
+              </span>
+              <span
+                className={
+                  e.action.type === "throttled"
+                    ? "break-all text-red-400"
+                    : "break-all"
+                }
+              >
+                {e.action.type === "answerUrlPrompt"
+                  ? `${t(
+                      e.action.allowed
+                        ? "activity.answerUrlPromptAllowed"
+                        : "activity.answerUrlPromptDenied",
+                      { url: e.action.url, pluginId: e.pluginId },
+                    )}${e.action.remembered ? ` ${t("activity.remembered")}` : ""}`
+                  : t(`activity.${e.action.type}`, {
+                      ...e.action,
+                      pluginId: e.pluginId,
+                      count:
+                        e.action.type === "grantPermissions"
+                          ? e.action.granted.length
+                          : undefined,
+                    })}
+              </span>
+            </li>
+          ))}
+        </ul>
+      )}
+      <hr className=" text-neutral-600 my-2" />
+    </section>
+  );
+}
+
 export function SettingsPluginPane({
   plugin,
   commands,
@@ -63,7 +270,12 @@ export function SettingsPluginPane({
         <div className="flex flex-col">
           <span className="inline-flex gap-1 items-center">
             <PluginTypeIcon type={plugin.source} />{" "}
-            <span className=" text-xs text-gray-500">{t(plugin.source === "Embedded" ? "pluginVariant.embedded" : "pluginVariant.userprovided")} · </span>
+            <span className=" text-xs text-gray-500">{t(plugin.source === "Embedded" ? "pluginVariant.embedded" : plugin.source === "Dev" ? "pluginVariant.dev" : "pluginVariant.userprovided")} · </span>
+            {plugin.source === "Dev" && (
+              <span className="rounded bg-amber-500/20 px-1 text-xs text-amber-300">
+                {t("devBadge")}
+              </span>
+            )}
             <span className="text-xs text-gray-500">
               {pluginVersion ?? t("pluginVersionMissing")}
             </span>
@@ -93,10 +305,12 @@ export function SettingsPluginPane({
             id="plugin-start-stop"
             disabled={
               currentStateType === "Starting" ||
-              currentStateType === "Disabling"
+              currentStateType === "Disabling" ||
+              currentStateType === "Restarting"
             }
             className={`rounded-lg p-2 bg-white/10 hover:bg-white/20 ${currentStateType === "Starting" ||
-              currentStateType === "Disabling"
+              currentStateType === "Disabling" ||
+              currentStateType === "Restarting"
               ? "cursor-progress animate-pulse"
               : "cursor-pointer"
               } `}
@@ -116,7 +330,7 @@ export function SettingsPluginPane({
               }}
             />
           </button>
-          {plugin.source === "UserProvided" && (
+          {plugin.source !== "Embedded" && (
             <button
               id="plugin-start-stop"
               onClick={async () => await commands.openPluginsDir(plugin.id)}
@@ -150,6 +364,8 @@ export function SettingsPluginPane({
           ))}
         </div>
       )}
+      <PluginPermissions plugin={plugin} commands={commands} />
+      <PluginActivity plugin={plugin} commands={commands} />
       <section className="" id="plugin_settings">
         {settingsLoadState.type === "Loading" && <p>{t("settingsLoadState.Loading")}</p>}
         {settingsLoadState.type === "FailedAwaitImport" && (
@@ -1,4 +1,5 @@
-import { CommandWrapper } from "../commands/commandWrapper";
+import { CommandWrapper, ImportPathZod } from "../commands/commandWrapper";
+import { PluginStateZod } from "../types/PluginState";
 import { PluginSettingsContextV1AlphaImpl } from "./SettingsContext";
 
 export type SettingsComponentLoadState =
@@ -29,7 +30,16 @@ export async function startAndLoadSettings(
   pluginID: string,
   commands: CommandWrapper
 ): Promise<SettingsComponentLoadState> {
-  const result = await commands.getImportPathForPlugin(pluginID);
+  const batch = await commands.runBatch([
+    { command: "get_import_path_for_plugin", request: { pluginId: pluginID }, zod: ImportPathZod },
+    { command: "get_plugin", request: { pluginId: pluginID }, zod: PluginStateZod },
+  ])
+  if (!batch.success) {
+    return {
+      type: "PluginNotFound"
+    }
+  }
+  const [result, manifest] = batch.data
   if (!result.success) {
     return {
       type: "PluginNotFound"
@@ -100,11 +110,10 @@ export async function startAndLoadSettings(
     }
   }
   const assetsBase = result.data.import.substring(0, result.data.import.lastIndexOf("/") + 1);
-  const manifest = await commands.getPlugin(pluginID)
   if (!manifest.success) {
     throw new Error("failed to get plugin: " + manifest.reason)
   }
-  const settingsContext = new PluginSettingsContextV1AlphaImpl({ ...manifest.data.manifest, id: manifest.data.id }, commands, assetsBase)
+  const settingsContext = new PluginSettingsContextV1AlphaImpl({ ...manifest.data.manifest, id: manifest.data.id }, await commands.forPlugin(manifest.data.id), assetsBase)
   try {
     item.initSettings(settingsContext);
   } catch (e) {
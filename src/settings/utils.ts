@@ -10,6 +10,7 @@ export function countPluginStates(
     FailedToStart: 0,
     Running: 0,
     Disabling: 0,
+    Restarting: 0,
   };
 
   for (const plugin of plugins) {
@@ -43,6 +44,10 @@ export const PluginStateUIData: Record<
     colour: "#39C655",
     pulsating: false,
   },
+  Restarting: {
+    colour: "#DBBE57",
+    pulsating: true,
+  },
   Missing: {
     colour: "#5B5B5B",
     pulsating: false,
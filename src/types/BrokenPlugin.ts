@@ -0,0 +1,35 @@
+import z from "zod";
+
+export const BrokenPluginReasonZod = z.discriminatedUnion("type", [
+  z.object({
+    type: z.literal("UnreadableManifest"),
+    message: z.string(),
+  }),
+  z.object({
+    type: z.literal("InvalidManifest"),
+    message: z.string(),
+    line: z.number(),
+    column: z.number(),
+  }),
+  z.object({
+    type: z.literal("InvalidPluginId"),
+    message: z.string(),
+  }),
+  z.object({
+    type: z.literal("DuplicateId"),
+    conflicting_dir: z.string().nullable(),
+  }),
+  z.object({
+    type: z.literal("Shadowed"),
+    shadowed_by: z.string(),
+  }),
+]);
+export type BrokenPluginReason = z.infer<typeof BrokenPluginReasonZod>;
+
+export const BrokenPluginZod = z.object({
+  id: z.string(),
+  plugin_dir: z.string(),
+  problem: BrokenPluginReasonZod,
+});
+
+export type BrokenPlugin = z.infer<typeof BrokenPluginZod>;
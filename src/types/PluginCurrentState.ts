@@ -27,6 +27,13 @@ export type PluginCurrentStateDisabling = z.infer<
   typeof PluginCurrentStateDisablingZod
 >;
 
+export const PluginCurrentStateRestartingZod = z.object({
+  type: z.literal("Restarting"),
+});
+export type PluginCurrentStateRestarting = z.infer<
+  typeof PluginCurrentStateRestartingZod
+>;
+
 export const PluginCurrentStateRunningZod = z.object({
   type: z.literal("Running"),
 });
@@ -40,6 +47,7 @@ export const PluginCurrentStateZod = z.union([
   PluginCurrentStateFailedToStartZod,
   PluginCurrentStateDisablingZod,
   PluginCurrentStateRunningZod,
+  PluginCurrentStateRestartingZod,
 ]);
 
 export type PluginCurrentState = z.infer<typeof PluginCurrentStateZod>;
@@ -7,11 +7,63 @@ export const V1AlphaManifestZod = z.object({
   repository_url: z.string().optional().nullable(),
   support_url: z.string().optional().nullable(),
   version: z.string().optional().nullable(),
+  publisher_key: z.string().optional().nullable(),
 });
 
 export type V1AlphaManifest = z.infer<typeof V1AlphaManifestZod>;
 
+export const PluginSidecarZod = z.object({
+  executable: z.string(),
+  args: z.array(z.string()).optional(),
+});
+
+export const BackendCapabilityZod = z.enum([
+  "journal_events",
+  "settings",
+  "frontend_messages",
+]);
+
+export const PluginBackendZod = z.object({
+  module: z.string().optional().nullable(),
+  capabilities: z.array(BackendCapabilityZod).optional(),
+});
+
+export const PluginMessageBusZod = z.object({
+  publishes: z.array(z.string()).optional(),
+  subscribes: z.array(z.string()).optional(),
+});
+
+export const PluginPermissionsZod = z.object({
+  url_domains: z.array(z.string()).optional(),
+  journal_events: z.array(z.string()).optional(),
+  settings_read: z.array(z.string()).optional(),
+  file_storage: z.boolean().optional(),
+});
+
+export const PermissionZod = z.discriminatedUnion("type", [
+  z.object({ type: z.literal("urlDomain"), domain: z.string() }),
+  z.object({ type: z.literal("journalEvents"), class: z.string() }),
+  z.object({ type: z.literal("settingsRead"), pluginId: z.string() }),
+  z.object({ type: z.literal("sidecar") }),
+  z.object({ type: z.literal("fileStorage") }),
+  z.object({ type: z.literal("backend"), capability: BackendCapabilityZod }),
+  z.object({ type: z.literal("busPublish"), topic: z.string() }),
+  z.object({ type: z.literal("busSubscribe"), topic: z.string() }),
+]);
+export type Permission = z.infer<typeof PermissionZod>;
+
+export const V1ManifestZod = V1AlphaManifestZod.extend({
+  type: z.literal("v1"),
+  sidecar: PluginSidecarZod.optional().nullable(),
+  backend: PluginBackendZod.optional().nullable(),
+  message_bus: PluginMessageBusZod.optional().nullable(),
+  permissions: PluginPermissionsZod.optional().nullable(),
+});
+
+export type V1Manifest = z.infer<typeof V1ManifestZod>;
+
 export const ManifestZod = z.union([
-  V1AlphaManifestZod
+  V1AlphaManifestZod,
+  V1ManifestZod,
 ]);
 export type Manifest = z.infer<typeof ManifestZod>;
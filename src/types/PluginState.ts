@@ -2,13 +2,27 @@ import z from "zod";
 import { PluginCurrentStateZod } from "./PluginCurrentState";
 import { ManifestZod } from "./PluginManifest";
 
+export const PluginRootZod = z.object({
+  kind: z.enum(["Dev", "User", "Shared", "System"]),
+  path: z.string(),
+});
+
 export const PluginStateZod = z.object({
   id: z.string(),
   current_state: PluginCurrentStateZod,
   plugin_dir: z.string(),
   frontend_hash: z.string(),
   manifest: ManifestZod,
-  source: z.enum(["UserProvided", "Embedded"]),
+  manifest_warnings: z.array(z.string()),
+  source: z.enum(["UserProvided", "Dev", "Embedded"]),
+  verification: z.enum([
+    "Tampered",
+    "Unsigned",
+    "SignedUnknown",
+    "SignedTrusted",
+    "Embedded",
+  ]),
+  root: PluginRootZod.nullable(),
 });
 
 export type PluginState = z.infer<typeof PluginStateZod>;